@@ -0,0 +1,25 @@
+use std::env::current_dir;
+use std::fs::create_dir_all;
+
+use airdrop_distributor::msg::*;
+use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
+
+use airdrop_distributor::state::*;
+
+fn main() {
+    let mut out_dir = current_dir().unwrap();
+    out_dir.push("schema");
+    create_dir_all(&out_dir).unwrap();
+    remove_schemas(&out_dir).unwrap();
+
+    export_schema(&schema_for!(InstantiateMsg), &out_dir);
+    export_schema(&schema_for!(ExecuteMsg), &out_dir);
+    export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(Config), &out_dir);
+    export_schema(&schema_for!(Distribution), &out_dir);
+    export_schema(&schema_for!(GetConfigResponse), &out_dir);
+    export_schema(&schema_for!(DistributionResponse), &out_dir);
+    export_schema(&schema_for!(DistributionsResponse), &out_dir);
+    export_schema(&schema_for!(ClaimedResponse), &out_dir);
+    export_schema(&schema_for!(TmpManagerStoreResponse), &out_dir);
+}
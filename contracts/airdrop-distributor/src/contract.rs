@@ -0,0 +1,392 @@
+#[cfg(not(feature = "library"))]
+use cosmwasm_std::entry_point;
+use cosmwasm_std::{
+    attr, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult,
+    Timestamp, Uint128,
+};
+
+use crate::error::ContractError;
+use crate::helpers::{
+    decode_merkle_root, get_airdrop_balance, get_airdrop_contracts, get_airdrop_transfer_msg,
+    verify_merkle_proof,
+};
+use crate::msg::{
+    ClaimedResponse, DistributionResponse, DistributionsResponse, ExecuteMsg, GetConfigResponse,
+    InstantiateMsg, MigrateMsg, QueryMsg, TmpManagerStoreResponse,
+};
+use crate::state::{
+    Config, Distribution, TmpManagerStore, CLAIMED, CONFIG, DISTRIBUTIONS, DISTRIBUTION_COUNT,
+    RESERVED_AMOUNTS, TMP_MANAGER_STORE,
+};
+use cw2::set_contract_version;
+use cw_storage_plus::{Bound, U64Key};
+
+const CONTRACT_NAME: &str = "airdrop-distributor";
+const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let config = Config {
+        manager: info.sender,
+        airdrops_registry_contract: deps
+            .api
+            .addr_validate(msg.airdrops_registry_contract.as_str())?,
+    };
+    CONFIG.save(deps.storage, &config)?;
+    DISTRIBUTION_COUNT.save(deps.storage, &0)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new().add_attribute("method", "instantiate"))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    Ok(Response::default())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::RegisterDistribution {
+            airdrop_token,
+            stage,
+            merkle_root,
+            snapshot_block,
+            total_amount,
+            claim_deadline,
+        } => register_distribution(
+            deps,
+            info,
+            env,
+            airdrop_token,
+            stage,
+            merkle_root,
+            snapshot_block,
+            total_amount,
+            claim_deadline,
+        ),
+        ExecuteMsg::Claim {
+            distribution_id,
+            amount,
+            proof,
+        } => claim(deps, info, env, distribution_id, amount, proof),
+        ExecuteMsg::SweepUnclaimed {
+            distribution_id,
+            recipient,
+        } => sweep_unclaimed(deps, info, env, distribution_id, recipient),
+        ExecuteMsg::SetManager { manager } => set_manager(deps, info, env, manager),
+        ExecuteMsg::AcceptManager {} => accept_manager(deps, info, env),
+    }
+}
+
+pub fn set_manager(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    manager: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    TMP_MANAGER_STORE.save(
+        deps.storage,
+        &TmpManagerStore {
+            manager: manager.to_lowercase(),
+        },
+    )?;
+
+    Ok(Response::default())
+}
+
+pub fn accept_manager(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+
+    let tmp_manager_store =
+        if let Some(tmp_manager_store) = TMP_MANAGER_STORE.may_load(deps.storage)? {
+            tmp_manager_store
+        } else {
+            return Err(ContractError::TmpManagerStoreEmpty {});
+        };
+
+    let manager = deps.api.addr_validate(tmp_manager_store.manager.as_str())?;
+    if info.sender != manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    config.manager = manager;
+    TMP_MANAGER_STORE.remove(deps.storage);
+
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::default())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn register_distribution(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    airdrop_token_str: String,
+    stage: u8,
+    merkle_root: String,
+    snapshot_block: u64,
+    total_amount: Uint128,
+    claim_deadline: Timestamp,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if decode_merkle_root(merkle_root.as_str()).is_none() {
+        return Err(ContractError::InvalidMerkleRoot {});
+    }
+    if total_amount.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    if claim_deadline <= env.block.time {
+        return Err(ContractError::InvalidClaimDeadline {});
+    }
+
+    // The registry tells us whether the airdrop is a cw20 or a native coin, the same way staking
+    // resolves it when claiming.
+    let airdrop_token = airdrop_token_str.to_lowercase();
+    let asset = get_airdrop_contracts(
+        deps.querier,
+        config.airdrops_registry_contract,
+        airdrop_token.clone(),
+    )?
+    .contracts
+    .ok_or_else(|| ContractError::AirdropNotRegistered(airdrop_token.clone()))?
+    .asset;
+
+    // Balances already promised to other distributions of the same airdrop cannot be reused.
+    let balance = get_airdrop_balance(deps.querier, &asset, &env.contract.address)?;
+    let reserved = RESERVED_AMOUNTS
+        .may_load(deps.storage, airdrop_token.as_str())?
+        .unwrap_or_else(Uint128::zero);
+    let available = balance.saturating_sub(reserved);
+    if available < total_amount {
+        return Err(ContractError::InsufficientAirdropBalance {
+            available,
+            required: total_amount,
+        });
+    }
+    RESERVED_AMOUNTS.save(
+        deps.storage,
+        airdrop_token.as_str(),
+        &(reserved + total_amount),
+    )?;
+
+    let id = DISTRIBUTION_COUNT.load(deps.storage)?;
+    DISTRIBUTION_COUNT.save(deps.storage, &(id + 1))?;
+    DISTRIBUTIONS.save(
+        deps.storage,
+        U64Key::new(id),
+        &Distribution {
+            id,
+            airdrop_token: airdrop_token.clone(),
+            stage,
+            asset,
+            merkle_root,
+            snapshot_block,
+            total_amount,
+            claimed_amount: Uint128::zero(),
+            claim_deadline,
+            swept: false,
+            created_at: env.block.time,
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "register_distribution"),
+        attr("distribution_id", id.to_string()),
+        attr("airdrop_token", airdrop_token),
+        attr("stage", stage.to_string()),
+        attr("total_amount", total_amount),
+    ]))
+}
+
+pub fn claim(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    distribution_id: u64,
+    amount: Uint128,
+    proof: Vec<String>,
+) -> Result<Response, ContractError> {
+    let mut distribution = DISTRIBUTIONS
+        .may_load(deps.storage, U64Key::new(distribution_id))?
+        .ok_or(ContractError::DistributionNotFound(distribution_id))?;
+    if env.block.time >= distribution.claim_deadline {
+        return Err(ContractError::ClaimDeadlinePassed {});
+    }
+    if CLAIMED.has(deps.storage, (U64Key::new(distribution_id), &info.sender)) {
+        return Err(ContractError::AlreadyClaimed {});
+    }
+    if !verify_merkle_proof(
+        distribution.merkle_root.as_str(),
+        &info.sender,
+        amount,
+        &proof,
+    ) {
+        return Err(ContractError::InvalidProof {});
+    }
+
+    // Guards against a root whose leaves add up to more than was registered.
+    distribution.claimed_amount += amount;
+    if distribution.claimed_amount > distribution.total_amount {
+        return Err(ContractError::DistributionExhausted {});
+    }
+    DISTRIBUTIONS.save(deps.storage, U64Key::new(distribution_id), &distribution)?;
+    CLAIMED.save(
+        deps.storage,
+        (U64Key::new(distribution_id), &info.sender),
+        &amount,
+    )?;
+    RESERVED_AMOUNTS.update(
+        deps.storage,
+        distribution.airdrop_token.as_str(),
+        |x| -> StdResult<_> { Ok(x.unwrap_or_else(Uint128::zero).checked_sub(amount)?) },
+    )?;
+
+    Ok(Response::new()
+        .add_message(get_airdrop_transfer_msg(
+            distribution.asset,
+            info.sender.clone(),
+            amount,
+        )?)
+        .add_attributes(vec![
+            attr("action", "claim"),
+            attr("distribution_id", distribution_id.to_string()),
+            attr("address", info.sender),
+            attr("amount", amount),
+        ]))
+}
+
+pub fn sweep_unclaimed(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    distribution_id: u64,
+    recipient: Option<String>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut distribution = DISTRIBUTIONS
+        .may_load(deps.storage, U64Key::new(distribution_id))?
+        .ok_or(ContractError::DistributionNotFound(distribution_id))?;
+    if env.block.time < distribution.claim_deadline {
+        return Err(ContractError::ClaimDeadlineNotPassed {});
+    }
+    if distribution.swept {
+        return Err(ContractError::AlreadySwept {});
+    }
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(recipient.as_str())?,
+        None => config.manager,
+    };
+
+    let unclaimed_amount = distribution.total_amount - distribution.claimed_amount;
+    distribution.swept = true;
+    DISTRIBUTIONS.save(deps.storage, U64Key::new(distribution_id), &distribution)?;
+    RESERVED_AMOUNTS.update(
+        deps.storage,
+        distribution.airdrop_token.as_str(),
+        |x| -> StdResult<_> {
+            Ok(x.unwrap_or_else(Uint128::zero)
+                .checked_sub(unclaimed_amount)?)
+        },
+    )?;
+
+    let mut response = Response::new();
+    if !unclaimed_amount.is_zero() {
+        response = response.add_message(get_airdrop_transfer_msg(
+            distribution.asset,
+            recipient.clone(),
+            unclaimed_amount,
+        )?);
+    }
+
+    Ok(response.add_attributes(vec![
+        attr("action", "sweep_unclaimed"),
+        attr("distribution_id", distribution_id.to_string()),
+        attr("recipient", recipient),
+        attr("amount", unclaimed_amount),
+    ]))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetConfig {} => to_binary(&query_config(deps)?),
+        QueryMsg::Distribution { distribution_id } => {
+            to_binary(&query_distribution(deps, distribution_id)?)
+        }
+        QueryMsg::Distributions { start_after, limit } => {
+            to_binary(&query_distributions(deps, start_after, limit)?)
+        }
+        QueryMsg::Claimed {
+            distribution_id,
+            address,
+        } => to_binary(&query_claimed(deps, distribution_id, address)?),
+        QueryMsg::TmpManagerStore {} => to_binary(&query_tmp_manager_store(deps)?),
+    }
+}
+
+pub fn query_tmp_manager_store(deps: Deps) -> StdResult<TmpManagerStoreResponse> {
+    let tmp_manager_store = TMP_MANAGER_STORE.may_load(deps.storage)?;
+    Ok(TmpManagerStoreResponse { tmp_manager_store })
+}
+
+fn query_config(deps: Deps) -> StdResult<GetConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(GetConfigResponse { config })
+}
+
+fn query_distribution(deps: Deps, distribution_id: u64) -> StdResult<DistributionResponse> {
+    let distribution = DISTRIBUTIONS.may_load(deps.storage, U64Key::new(distribution_id))?;
+    Ok(DistributionResponse { distribution })
+}
+
+fn query_distributions(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<DistributionsResponse> {
+    let limit = limit.unwrap_or(10).min(30) as usize;
+    let start = start_after.map(|id| Bound::exclusive(U64Key::new(id)));
+
+    let distributions = DISTRIBUTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, distribution)| distribution))
+        .collect::<StdResult<Vec<Distribution>>>()?;
+
+    Ok(DistributionsResponse { distributions })
+}
+
+fn query_claimed(deps: Deps, distribution_id: u64, address: String) -> StdResult<ClaimedResponse> {
+    let address = deps.api.addr_validate(address.as_str())?;
+    let amount = CLAIMED.may_load(deps.storage, (U64Key::new(distribution_id), &address))?;
+    Ok(ClaimedResponse { amount })
+}
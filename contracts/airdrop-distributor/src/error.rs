@@ -0,0 +1,53 @@
+use cosmwasm_std::{StdError, Uint128};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContractError {
+    #[error("AirdropDistributor-Contract: {0}")]
+    Std(#[from] StdError),
+
+    #[error("AirdropDistributor-Contract: Unauthorized")]
+    Unauthorized {},
+
+    #[error("AirdropDistributor-Contract: Temp manager store is empty")]
+    TmpManagerStoreEmpty {},
+
+    #[error("AirdropDistributor-Contract: Airdrop is not registered `{0}`")]
+    AirdropNotRegistered(String),
+
+    #[error("AirdropDistributor-Contract: Merkle root must be a hex encoded 32 byte hash")]
+    InvalidMerkleRoot {},
+
+    #[error("AirdropDistributor-Contract: Distribution amount cannot be zero")]
+    ZeroAmount {},
+
+    #[error("AirdropDistributor-Contract: Claim deadline must be in the future")]
+    InvalidClaimDeadline {},
+
+    #[error("AirdropDistributor-Contract: Insufficient unallocated airdrop balance. Available: {available}, required: {required}")]
+    InsufficientAirdropBalance {
+        available: Uint128,
+        required: Uint128,
+    },
+
+    #[error("AirdropDistributor-Contract: Distribution not found `{0}`")]
+    DistributionNotFound(u64),
+
+    #[error("AirdropDistributor-Contract: Claim deadline of the distribution has passed")]
+    ClaimDeadlinePassed {},
+
+    #[error("AirdropDistributor-Contract: Claim deadline of the distribution has not passed yet")]
+    ClaimDeadlineNotPassed {},
+
+    #[error("AirdropDistributor-Contract: Airdrop already claimed")]
+    AlreadyClaimed {},
+
+    #[error("AirdropDistributor-Contract: Merkle proof verification failed")]
+    InvalidProof {},
+
+    #[error("AirdropDistributor-Contract: Claims exceed the distribution total")]
+    DistributionExhausted {},
+
+    #[error("AirdropDistributor-Contract: Distribution has already been swept")]
+    AlreadySwept {},
+}
@@ -0,0 +1,92 @@
+use airdrops_registry::msg::{GetAirdropContractsResponse, QueryMsg as AirdropsQueryMsg};
+use airdrops_registry::state::AirdropAsset;
+use cosmwasm_std::{
+    to_binary, Addr, BankMsg, Coin, CosmosMsg, QuerierWrapper, StdResult, Uint128, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
+use sha2::Digest;
+
+pub fn get_airdrop_contracts(
+    querier_wrapper: QuerierWrapper,
+    airdrops_registry_contract: Addr,
+    token: String,
+) -> StdResult<GetAirdropContractsResponse> {
+    querier_wrapper.query_wasm_smart(
+        airdrops_registry_contract,
+        &AirdropsQueryMsg::GetAirdropContracts { token },
+    )
+}
+
+pub fn get_airdrop_balance(
+    querier_wrapper: QuerierWrapper,
+    asset: &AirdropAsset,
+    address: &Addr,
+) -> StdResult<Uint128> {
+    Ok(match asset {
+        AirdropAsset::Cw20 { contract } => {
+            let res: BalanceResponse = querier_wrapper.query_wasm_smart(
+                contract,
+                &Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )?;
+            res.balance
+        }
+        AirdropAsset::Native { denom } => querier_wrapper.query_balance(address, denom)?.amount,
+    })
+}
+
+pub fn get_airdrop_transfer_msg(
+    asset: AirdropAsset,
+    recipient: Addr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    Ok(match asset {
+        AirdropAsset::Cw20 { contract } => CosmosMsg::from(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+        AirdropAsset::Native { denom } => CosmosMsg::from(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin::new(amount.u128(), denom)],
+        }),
+    })
+}
+
+pub fn decode_merkle_root(merkle_root: &str) -> Option<[u8; 32]> {
+    let mut root_buf = [0_u8; 32];
+    hex::decode_to_slice(merkle_root, &mut root_buf).ok()?;
+    Some(root_buf)
+}
+
+// Leaf is sha256(address + amount). Each level hashes the sorted pair, so proofs carry no
+// left/right flags.
+pub fn verify_merkle_proof(
+    merkle_root: &str,
+    address: &Addr,
+    amount: Uint128,
+    proof: &[String],
+) -> bool {
+    let root = match decode_merkle_root(merkle_root) {
+        Some(root) => root,
+        None => return false,
+    };
+
+    let mut hash: [u8; 32] =
+        sha2::Sha256::digest(format!("{}{}", address, amount).as_bytes()).into();
+    for node in proof {
+        let mut node_buf = [0_u8; 32];
+        if hex::decode_to_slice(node, &mut node_buf).is_err() {
+            return false;
+        }
+        let mut pair = [hash, node_buf];
+        pair.sort_unstable();
+        hash = sha2::Sha256::digest(&pair.concat()).into();
+    }
+
+    hash == root
+}
@@ -0,0 +1,84 @@
+use crate::state::{Config, Distribution, TmpManagerStore};
+use cosmwasm_std::{Timestamp, Uint128};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    pub airdrops_registry_contract: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    // The airdrop must already be held by this contract, i.e. claimed by the staking contract
+    // through ClaimAirdrops with this contract as its airdrop_withdrawal_contract.
+    RegisterDistribution {
+        airdrop_token: String,
+        stage: u8,
+        merkle_root: String,
+        snapshot_block: u64,
+        total_amount: Uint128,
+        claim_deadline: Timestamp,
+    },
+    Claim {
+        distribution_id: u64,
+        amount: Uint128,
+        proof: Vec<String>,
+    },
+    // Sends whatever is unclaimed after the deadline to the recipient, or the manager if not set.
+    SweepUnclaimed {
+        distribution_id: u64,
+        recipient: Option<String>,
+    },
+    SetManager {
+        manager: String,
+    },
+    AcceptManager {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    GetConfig {},
+    Distribution {
+        distribution_id: u64,
+    },
+    Distributions {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    Claimed {
+        distribution_id: u64,
+        address: String,
+    },
+    TmpManagerStore {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetConfigResponse {
+    pub config: Config,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DistributionResponse {
+    pub distribution: Option<Distribution>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DistributionsResponse {
+    pub distributions: Vec<Distribution>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimedResponse {
+    pub amount: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TmpManagerStoreResponse {
+    pub tmp_manager_store: Option<TmpManagerStore>,
+}
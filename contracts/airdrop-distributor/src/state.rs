@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use airdrops_registry::state::AirdropAsset;
+use cosmwasm_std::{Addr, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map, U64Key};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Config {
+    pub manager: Addr,
+    pub airdrops_registry_contract: Addr,
+}
+
+pub const CONFIG: Item<Config> = Item::new("config");
+
+// Payout of one claimed airdrop stage to LunaX holders, by a merkle root over the snapshot of their balances.
+// Leaves are sha256(address + amount), pairs are sorted before hashing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Distribution {
+    pub id: u64,
+    pub airdrop_token: String,
+    pub stage: u8,
+    pub asset: AirdropAsset,
+    pub merkle_root: String,
+    pub snapshot_block: u64,
+    pub total_amount: Uint128,
+    pub claimed_amount: Uint128,
+    pub claim_deadline: Timestamp,
+    pub swept: bool,
+    pub created_at: Timestamp,
+}
+
+pub const DISTRIBUTION_COUNT: Item<u64> = Item::new("distribution_count");
+pub const DISTRIBUTIONS: Map<U64Key, Distribution> = Map::new("distributions");
+
+// Map of (distribution id, holder) to the amount claimed
+pub const CLAIMED: Map<(U64Key, &Addr), Uint128> = Map::new("claimed");
+
+// Map of airdrop token to the balance committed to distributions that is neither claimed nor swept.
+// Only the balance above this can be allocated to new distributions.
+pub const RESERVED_AMOUNTS: Map<&str, Uint128> = Map::new("reserved_amounts");
+
+// this is a tmp store to store the intermediate values of manager updates.
+// manager updates are 2 phase, we set it and then accept it. This is done to
+// add a greater assurance of the update.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TmpManagerStore {
+    pub manager: String,
+}
+
+pub const TMP_MANAGER_STORE: Item<TmpManagerStore> = Item::new("tmp_manager_store");
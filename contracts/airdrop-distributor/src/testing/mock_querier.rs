@@ -0,0 +1,126 @@
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Addr, Binary, Coin, ContractResult, Empty, OwnedDeps,
+    Querier, QuerierResult, QueryRequest, SystemError, SystemResult, Timestamp, Uint128, WasmQuery,
+};
+use std::collections::HashMap;
+
+use airdrops_registry::msg::{GetAirdropContractsResponse, QueryMsg as AirdropsQueryMsg};
+use airdrops_registry::state::{AirdropAsset, AirdropRegistryInfo, ClaimFormat};
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR};
+use cw20::BalanceResponse;
+
+pub fn mock_dependencies(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let custom_querier: WasmMockQuerier =
+        WasmMockQuerier::new(MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]));
+
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: custom_querier,
+    }
+}
+
+// "unreg_token" is not registered and "native_token" is paid out in uluna. Every other token is a
+// cw20 at "<token>_contract".
+pub fn get_airdrop_asset(token: &str) -> AirdropAsset {
+    match token {
+        "native_token" => AirdropAsset::Native {
+            denom: "uluna".to_string(),
+        },
+        _ => AirdropAsset::Cw20 {
+            contract: Addr::unchecked(format!("{}_contract", token)),
+        },
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    // Map of cw20 contract to the balance this contract holds in it
+    cw20_balances: HashMap<String, Uint128>,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match &request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                if contract_addr.eq("airdrops_registry_contract") {
+                    match from_binary(msg).unwrap() {
+                        AirdropsQueryMsg::GetAirdropContracts { token } => {
+                            let res = if token.eq("unreg_token") {
+                                GetAirdropContractsResponse { contracts: None }
+                            } else {
+                                GetAirdropContractsResponse {
+                                    contracts: Some(AirdropRegistryInfo {
+                                        token: token.clone(),
+                                        airdrop_contract: Addr::unchecked(format!(
+                                            "{}_airdrop_contract",
+                                            token
+                                        )),
+                                        asset: get_airdrop_asset(token.as_str()),
+                                        claim_format: ClaimFormat::MerkleWithStage {},
+                                        enabled: true,
+                                        stages: vec![],
+                                        claim_deadline: None,
+                                        added_at: Timestamp::from_seconds(0),
+                                    }),
+                                }
+                            };
+                            SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                        }
+                        _ => {
+                            let out = Binary::default();
+                            SystemResult::Ok(ContractResult::from(to_binary(&out)))
+                        }
+                    }
+                } else {
+                    match from_binary(msg).unwrap() {
+                        cw20::Cw20QueryMsg::Balance { address: _ } => {
+                            let res = BalanceResponse {
+                                balance: *self
+                                    .cw20_balances
+                                    .get(contract_addr)
+                                    .unwrap_or(&Uint128::zero()),
+                            };
+                            SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                        }
+                        _ => {
+                            let out = Binary::default();
+                            SystemResult::Ok(ContractResult::from(to_binary(&out)))
+                        }
+                    }
+                }
+            }
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    pub fn update_cw20_balance(&mut self, contract: &str, balance: Uint128) {
+        self.cw20_balances.insert(contract.to_string(), balance);
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
+        WasmMockQuerier {
+            base,
+            cw20_balances: HashMap::new(),
+        }
+    }
+}
@@ -0,0 +1,428 @@
+use crate::contract::{execute, instantiate, query};
+use crate::msg::{
+    ClaimedResponse, DistributionResponse, DistributionsResponse, ExecuteMsg, GetConfigResponse,
+    InstantiateMsg, QueryMsg,
+};
+use crate::state::{Config, Distribution};
+use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
+use crate::ContractError;
+use airdrops_registry::state::AirdropAsset;
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage};
+use cosmwasm_std::{
+    coins, from_binary, to_binary, Addr, BankMsg, CosmosMsg, Env, MessageInfo, OwnedDeps, Response,
+    SubMsg, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use sha2::Digest;
+
+fn instantiate_contract(
+    deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+    info: &MessageInfo,
+    env: &Env,
+) -> Response {
+    let msg = InstantiateMsg {
+        airdrops_registry_contract: "airdrops_registry_contract".to_string(),
+    };
+
+    instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap()
+}
+
+fn leaf(address: &str, amount: u128) -> [u8; 32] {
+    sha2::Sha256::digest(format!("{}{}", address, amount).as_bytes()).into()
+}
+
+// Root of the tree with the snapshot balances alice: 600 and bob: 400.
+fn get_merkle_root() -> String {
+    let mut pair = [leaf("alice", 600_u128), leaf("bob", 400_u128)];
+    pair.sort_unstable();
+    let root: [u8; 32] = sha2::Sha256::digest(&pair.concat()).into();
+    hex::encode(root)
+}
+
+fn register_msg(airdrop_token: &str, total_amount: u128, env: &Env) -> ExecuteMsg {
+    ExecuteMsg::RegisterDistribution {
+        airdrop_token: airdrop_token.to_string(),
+        stage: 1,
+        merkle_root: get_merkle_root(),
+        snapshot_block: 12345,
+        total_amount: Uint128::new(total_amount),
+        claim_deadline: env.block.time.plus_seconds(1000),
+    }
+}
+
+#[test]
+fn proper_initialization() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("creator", &[]);
+    let env = mock_env();
+
+    instantiate_contract(&mut deps, &info, &env);
+
+    let config_response: GetConfigResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::GetConfig {}).unwrap()).unwrap();
+    assert_eq!(
+        config_response.config,
+        Config {
+            manager: Addr::unchecked("creator"),
+            airdrops_registry_contract: Addr::unchecked("airdrops_registry_contract"),
+        }
+    );
+}
+
+#[test]
+fn test_register_distribution() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("creator", &[]);
+    let env = mock_env();
+
+    instantiate_contract(&mut deps, &info, &env);
+    deps.querier
+        .update_cw20_balance("anc_contract", Uint128::new(1500_u128));
+
+    /*
+       Test - 1. Unauthorized
+    */
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("other", &[]),
+        register_msg("anc", 1000, &env),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    /*
+       Test - 2. Invalid inputs
+    */
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::RegisterDistribution {
+            airdrop_token: "anc".to_string(),
+            stage: 1,
+            merkle_root: "abcd".to_string(),
+            snapshot_block: 12345,
+            total_amount: Uint128::new(1000_u128),
+            claim_deadline: env.block.time.plus_seconds(1000),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidMerkleRoot {}));
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        register_msg("anc", 0, &env),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ZeroAmount {}));
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        ExecuteMsg::RegisterDistribution {
+            airdrop_token: "anc".to_string(),
+            stage: 1,
+            merkle_root: get_merkle_root(),
+            snapshot_block: 12345,
+            total_amount: Uint128::new(1000_u128),
+            claim_deadline: env.block.time,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidClaimDeadline {}));
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        register_msg("unreg_token", 1000, &env),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::AirdropNotRegistered(_)));
+
+    /*
+       Test - 3. Successful registration reserves the balance
+    */
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        register_msg("ANC", 1000, &env),
+    )
+    .unwrap();
+    assert_eq!(res.attributes[1].value, "0");
+
+    let distribution_response: DistributionResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Distribution { distribution_id: 0 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        distribution_response.distribution,
+        Some(Distribution {
+            id: 0,
+            airdrop_token: "anc".to_string(),
+            stage: 1,
+            asset: AirdropAsset::Cw20 {
+                contract: Addr::unchecked("anc_contract")
+            },
+            merkle_root: get_merkle_root(),
+            snapshot_block: 12345,
+            total_amount: Uint128::new(1000_u128),
+            claimed_amount: Uint128::zero(),
+            claim_deadline: env.block.time.plus_seconds(1000),
+            swept: false,
+            created_at: env.block.time,
+        })
+    );
+
+    /*
+       Test - 4. Reserved balance cannot be allocated twice
+    */
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        register_msg("anc", 1000, &env),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ContractError::InsufficientAirdropBalance {
+            available,
+            required
+        } if available.eq(&Uint128::new(500_u128)) && required.eq(&Uint128::new(1000_u128))
+    ));
+
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        register_msg("anc", 500, &env),
+    )
+    .unwrap();
+    let distributions_response: DistributionsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Distributions {
+                start_after: Some(0),
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(distributions_response.distributions.len(), 1);
+    assert_eq!(distributions_response.distributions[0].id, 1);
+}
+
+#[test]
+fn test_claim() {
+    let mut deps = mock_dependencies(&[]);
+    let info = mock_info("creator", &[]);
+    let mut env = mock_env();
+
+    instantiate_contract(&mut deps, &info, &env);
+    deps.querier
+        .update_cw20_balance("anc_contract", Uint128::new(1000_u128));
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info,
+        register_msg("anc", 1000, &env),
+    )
+    .unwrap();
+
+    /*
+       Test - 1. Wrong amount or proof
+    */
+    let bob_proof = vec![hex::encode(leaf("alice", 600_u128))];
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("bob", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 0,
+            amount: Uint128::new(600_u128),
+            proof: bob_proof.clone(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::InvalidProof {}));
+
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("bob", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 1,
+            amount: Uint128::new(400_u128),
+            proof: bob_proof.clone(),
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::DistributionNotFound(1)));
+
+    /*
+       Test - 2. Successful claim
+    */
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("bob", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 0,
+            amount: Uint128::new(400_u128),
+            proof: bob_proof.clone(),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(WasmMsg::Execute {
+            contract_addr: "anc_contract".to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: "bob".to_string(),
+                amount: Uint128::new(400_u128),
+            })
+            .unwrap(),
+            funds: vec![],
+        })]
+    );
+    let claimed_response: ClaimedResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Claimed {
+                distribution_id: 0,
+                address: "bob".to_string(),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert_eq!(claimed_response.amount, Some(Uint128::new(400_u128)));
+
+    /*
+       Test - 3. Double claim
+    */
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("bob", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 0,
+            amount: Uint128::new(400_u128),
+            proof: bob_proof,
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::AlreadyClaimed {}));
+
+    /*
+       Test - 4. Claims close at the deadline
+    */
+    env.block.time = env.block.time.plus_seconds(1000);
+    let err = execute(
+        deps.as_mut(),
+        env,
+        mock_info("alice", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 0,
+            amount: Uint128::new(600_u128),
+            proof: vec![hex::encode(leaf("bob", 400_u128))],
+        },
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::ClaimDeadlinePassed {}));
+}
+
+#[test]
+fn test_sweep_unclaimed() {
+    let mut deps = mock_dependencies(&coins(1000, "uluna"));
+    let info = mock_info("creator", &[]);
+    let mut env = mock_env();
+
+    instantiate_contract(&mut deps, &info, &env);
+    execute(
+        deps.as_mut(),
+        env.clone(),
+        info.clone(),
+        register_msg("native_token", 1000, &env),
+    )
+    .unwrap();
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("alice", &[]),
+        ExecuteMsg::Claim {
+            distribution_id: 0,
+            amount: Uint128::new(600_u128),
+            proof: vec![hex::encode(leaf("bob", 400_u128))],
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "alice".to_string(),
+            amount: coins(600, "uluna"),
+        }))]
+    );
+
+    /*
+       Test - 1. Only the manager can sweep, and only after the deadline
+    */
+    let sweep_msg = ExecuteMsg::SweepUnclaimed {
+        distribution_id: 0,
+        recipient: Some("treasury".to_string()),
+    };
+    let err = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("other", &[]),
+        sweep_msg.clone(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, ContractError::Unauthorized {}));
+
+    let err = execute(deps.as_mut(), env.clone(), info.clone(), sweep_msg.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::ClaimDeadlineNotPassed {}));
+
+    /*
+       Test - 2. Successful sweep of bob's unclaimed share
+    */
+    env.block.time = env.block.time.plus_seconds(1000);
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), sweep_msg.clone()).unwrap();
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "treasury".to_string(),
+            amount: coins(400, "uluna"),
+        }))]
+    );
+    let distribution_response: DistributionResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::Distribution { distribution_id: 0 },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let distribution = distribution_response.distribution.unwrap();
+    assert!(distribution.swept);
+    assert_eq!(distribution.claimed_amount, Uint128::new(600_u128));
+
+    let err = execute(deps.as_mut(), env, info, sweep_msg).unwrap_err();
+    assert!(matches!(err, ContractError::AlreadySwept {}));
+}
@@ -18,5 +18,6 @@ fn main() {
     export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(GetConfigResponse), &out_dir);
     export_schema(&schema_for!(GetAirdropContractsResponse), &out_dir);
+    export_schema(&schema_for!(AllAirdropsResponse), &out_dir);
     export_schema(&schema_for!(TmpManagerStoreResponse), &out_dir);
 }
@@ -1,16 +1,21 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
+use cosmwasm_std::{
+    attr, to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Order, Response, StdError, StdResult,
+    Timestamp,
+};
 
 use crate::error::ContractError;
 use crate::msg::{
-    ExecuteMsg, GetAirdropContractsResponse, GetConfigResponse, InstantiateMsg, MigrateMsg,
-    QueryMsg, TmpManagerStoreResponse,
+    AllAirdropsResponse, ExecuteMsg, GetAirdropContractsResponse, GetConfigResponse,
+    InstantiateMsg, MigrateMsg, QueryMsg, TmpManagerStoreResponse,
 };
 use crate::state::{
-    AirdropRegistryInfo, Config, TmpManagerStore, AIRDROP_REGISTRY, CONFIG, TMP_MANAGER_STORE,
+    AirdropAsset, AirdropRegistryInfo, ClaimFormat, Config, TmpManagerStore, AIRDROP_REGISTRY,
+    CONFIG, LEGACY_AIRDROP_REGISTRY, TMP_MANAGER_STORE,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::Bound;
 
 const CONTRACT_NAME: &str = "airdrops-registry";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -32,14 +37,46 @@ pub fn instantiate(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Both formats share a namespace, so each entry is parsed on its own and current ones are left as is.
+    // Legacy entries are merkle cw20 airdrops. Entries from before the enabled flag and metadata existed
+    // are enabled, with no stages or deadline.
+    let tokens = AIRDROP_REGISTRY
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(String::from_utf8)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(StdError::from)?;
+    for token in tokens {
+        if AIRDROP_REGISTRY.load(deps.storage, token.clone()).is_ok() {
+            continue;
+        }
+
+        let legacy_info = LEGACY_AIRDROP_REGISTRY.load(deps.storage, token)?;
+        AIRDROP_REGISTRY.save(
+            deps.storage,
+            legacy_info.token.clone(),
+            &AirdropRegistryInfo {
+                token: legacy_info.token,
+                airdrop_contract: legacy_info.airdrop_contract,
+                asset: AirdropAsset::Cw20 {
+                    contract: legacy_info.cw20_contract,
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                enabled: legacy_info.enabled.unwrap_or(true),
+                stages: legacy_info.stages.unwrap_or_default(),
+                claim_deadline: legacy_info.claim_deadline,
+                added_at: legacy_info.added_at.unwrap_or(env.block.time),
+            },
+        )?;
+    }
+
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
@@ -47,16 +84,30 @@ pub fn execute(
         ExecuteMsg::UpdateAirdropRegistry {
             airdrop_token: airdrop_token_str,
             airdrop_contract: airdrop_contract_str,
-            cw20_contract: cw20_contract_str,
+            asset,
+            claim_format,
+            stages,
+            claim_deadline,
         } => update_airdrop_registry(
             deps,
             info,
+            env,
             airdrop_token_str,
             airdrop_contract_str,
-            cw20_contract_str,
+            asset,
+            claim_format,
+            stages,
+            claim_deadline,
         ),
-        ExecuteMsg::SetManager { manager } => set_manager(deps, info, _env, manager),
-        ExecuteMsg::AcceptManager {} => accept_manager(deps, info, _env),
+        ExecuteMsg::RemoveAirdrop { airdrop_token } => {
+            remove_airdrop(deps, info, env, airdrop_token)
+        }
+        ExecuteMsg::SetAirdropEnabled {
+            airdrop_token,
+            enabled,
+        } => set_airdrop_enabled(deps, info, env, airdrop_token, enabled),
+        ExecuteMsg::SetManager { manager } => set_manager(deps, info, env, manager),
+        ExecuteMsg::AcceptManager {} => accept_manager(deps, info, env),
     }
 }
 
@@ -108,12 +159,17 @@ pub fn accept_manager(
     Ok(Response::default())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn update_airdrop_registry(
     deps: DepsMut,
     info: MessageInfo,
+    env: Env,
     airdrop_token_str: String,
     airdrop_contract_str: String,
-    cw20_contract_str: String,
+    asset: AirdropAsset,
+    claim_format: ClaimFormat,
+    stages: Vec<u8>,
+    claim_deadline: Option<Timestamp>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
@@ -126,21 +182,108 @@ pub fn update_airdrop_registry(
     }
 
     let airdrop_token = airdrop_token_str.to_lowercase();
-    let airdrop_contract = deps.api.addr_validate(airdrop_contract_str.to_lowercase().as_str())?;
-    let cw20_contract = deps.api.addr_validate(cw20_contract_str.to_lowercase().as_str())?;
+    let airdrop_contract = deps
+        .api
+        .addr_validate(airdrop_contract_str.to_lowercase().as_str())?;
+    let asset = match asset {
+        AirdropAsset::Cw20 { contract } => AirdropAsset::Cw20 {
+            contract: deps
+                .api
+                .addr_validate(contract.as_str().to_lowercase().as_str())?,
+        },
+        AirdropAsset::Native { denom } => {
+            if denom.is_empty() {
+                return Err(ContractError::DenomEmpty {});
+            }
+            AirdropAsset::Native { denom }
+        }
+    };
+    if let ClaimFormat::Template { template } = &claim_format {
+        if template.trim().is_empty() {
+            return Err(ContractError::ClaimTemplateEmpty {});
+        }
+    }
+    // Updating an entry keeps its enabled flag and the time it was first added.
+    let existing_info = AIRDROP_REGISTRY.may_load(deps.storage, airdrop_token.clone())?;
+    let (enabled, added_at) = match existing_info {
+        Some(info) => (info.enabled, info.added_at),
+        None => (true, env.block.time),
+    };
     AIRDROP_REGISTRY.save(
         deps.storage,
         airdrop_token.clone(),
         &AirdropRegistryInfo {
             token: airdrop_token,
             airdrop_contract,
-            cw20_contract,
+            asset,
+            claim_format,
+            enabled,
+            stages,
+            claim_deadline,
+            added_at,
         },
     )?;
 
     Ok(Response::default())
 }
 
+pub fn remove_airdrop(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    airdrop_token_str: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let airdrop_token = airdrop_token_str.to_lowercase();
+    if AIRDROP_REGISTRY
+        .may_load(deps.storage, airdrop_token.clone())?
+        .is_none()
+    {
+        return Err(ContractError::AirdropNotFound(airdrop_token));
+    }
+    AIRDROP_REGISTRY.remove(deps.storage, airdrop_token.clone());
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "remove_airdrop"),
+        attr("airdrop_token", airdrop_token),
+    ]))
+}
+
+pub fn set_airdrop_enabled(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    airdrop_token_str: String,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let airdrop_token = airdrop_token_str.to_lowercase();
+    AIRDROP_REGISTRY.update(
+        deps.storage,
+        airdrop_token.clone(),
+        |x| -> Result<_, ContractError> {
+            let mut airdrop_info =
+                x.ok_or_else(|| ContractError::AirdropNotFound(airdrop_token.clone()))?;
+            airdrop_info.enabled = enabled;
+            Ok(airdrop_info)
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_airdrop_enabled"),
+        attr("airdrop_token", airdrop_token),
+        attr("enabled", enabled.to_string()),
+    ]))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
@@ -148,6 +291,9 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::GetAirdropContracts { token } => {
             to_binary(&query_airdrop_contracts(deps, token)?)
         }
+        QueryMsg::AllAirdrops { start_after, limit } => {
+            to_binary(&query_all_airdrops(deps, start_after, limit)?)
+        }
         QueryMsg::TmpManagerStore {} => to_binary(&query_tmp_manager_store(deps)?),
     }
 }
@@ -166,3 +312,20 @@ fn query_airdrop_contracts(deps: Deps, token: String) -> StdResult<GetAirdropCon
     let contracts = AIRDROP_REGISTRY.may_load(deps.storage, token)?;
     Ok(GetAirdropContractsResponse { contracts })
 }
+
+fn query_all_airdrops(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AllAirdropsResponse> {
+    let limit = limit.unwrap_or(10).min(30) as usize;
+    let start = start_after.map(|token| Bound::exclusive(token.to_lowercase().as_bytes()));
+
+    let airdrops = AIRDROP_REGISTRY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, info)| info))
+        .collect::<StdResult<Vec<AirdropRegistryInfo>>>()?;
+
+    Ok(AllAirdropsResponse { airdrops })
+}
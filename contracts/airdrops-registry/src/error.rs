@@ -14,4 +14,13 @@ pub enum ContractError {
 
     #[error("AirdropRegistry-Contract: Temp manager store is empty")]
     TmpManagerStoreEmpty {},
+
+    #[error("AirdropRegistry-Contract: Airdrop is not registered `{0}`")]
+    AirdropNotFound(String),
+
+    #[error("AirdropRegistry-Contract: Claim template cannot be empty")]
+    ClaimTemplateEmpty {},
+
+    #[error("AirdropRegistry-Contract: Native airdrop denom cannot be empty")]
+    DenomEmpty {},
 }
@@ -1,4 +1,5 @@
-use crate::state::{AirdropRegistryInfo, Config, TmpManagerStore};
+use crate::state::{AirdropAsset, AirdropRegistryInfo, ClaimFormat, Config, TmpManagerStore};
+use cosmwasm_std::Timestamp;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -14,7 +15,17 @@ pub enum ExecuteMsg {
     UpdateAirdropRegistry {
         airdrop_token: String,
         airdrop_contract: String,
-        cw20_contract: String,
+        asset: AirdropAsset,
+        claim_format: ClaimFormat,
+        stages: Vec<u8>,
+        claim_deadline: Option<Timestamp>,
+    },
+    RemoveAirdrop {
+        airdrop_token: String,
+    },
+    SetAirdropEnabled {
+        airdrop_token: String,
+        enabled: bool,
     },
     SetManager {
         manager: String,
@@ -26,7 +37,13 @@ pub enum ExecuteMsg {
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetConfig {},
-    GetAirdropContracts { token: String },
+    GetAirdropContracts {
+        token: String,
+    },
+    AllAirdrops {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
     TmpManagerStore {},
 }
 
@@ -44,3 +61,8 @@ pub struct GetConfigResponse {
 pub struct GetAirdropContractsResponse {
     pub contracts: Option<AirdropRegistryInfo>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllAirdropsResponse {
+    pub airdrops: Vec<AirdropRegistryInfo>,
+}
@@ -1,7 +1,7 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
+use cosmwasm_std::{Addr, Timestamp};
 use cw_storage_plus::{Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -11,16 +11,59 @@ pub struct Config {
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+// Shape of the message sent to the airdrop contract to claim.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimFormat {
+    MerkleWithStage {},    // {"claim":{"stage":..,"amount":..,"proof":[..]}}
+    MerkleWithoutStage {}, // {"claim":{"amount":..,"proof":[..]}}
+    Plain {},              // {"claim":{}}
+    // Raw JSON in which {{stage}}, {{amount}} and {{proof}} are replaced by the JSON encoding of their values
+    Template { template: String },
+}
+
+// Where the claimed airdrop ends up and how it is forwarded to the airdrop withdrawal contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AirdropAsset {
+    Cw20 { contract: Addr },
+    Native { denom: String },
+}
+
 // Move the registered contracts to a central location.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AirdropRegistryInfo {
     pub token: String,
     pub airdrop_contract: Addr,
-    pub cw20_contract: Addr,
+    pub asset: AirdropAsset,
+    pub claim_format: ClaimFormat,
+    pub enabled: bool,   // Disabled airdrops stay registered but are not claimed
+    pub stages: Vec<u8>, // Merkle stages of the airdrop contract that apply to the pool
+    pub claim_deadline: Option<Timestamp>,
+    pub added_at: Timestamp,
 }
 // Map of airdrop token to the token contract
 pub const AIRDROP_REGISTRY: Map<String, AirdropRegistryInfo> = Map::new("airdrop_registry");
 
+// Entries as they were stored before claim formats were added. Only read by migrate.
+// The first release stored none of the optional fields. Later entries also carry the enabled flag and metadata.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyAirdropRegistryInfo {
+    pub token: String,
+    pub airdrop_contract: Addr,
+    pub cw20_contract: Addr,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub stages: Option<Vec<u8>>,
+    #[serde(default)]
+    pub claim_deadline: Option<Timestamp>,
+    #[serde(default)]
+    pub added_at: Option<Timestamp>,
+}
+pub const LEGACY_AIRDROP_REGISTRY: Map<String, LegacyAirdropRegistryInfo> =
+    Map::new("airdrop_registry");
+
 // this is a tmp store to store the intermediate values of manager updates.
 // manager updates are 2 phase, we set it and then accept it. This is done to
 // add a greater assurance of the update.
@@ -1,11 +1,14 @@
 #[cfg(test)]
 mod tests {
-    use crate::contract::{execute, instantiate, query};
+    use crate::contract::{execute, instantiate, migrate, query};
 
     use crate::error::ContractError::TokenEmpty;
-    use crate::msg::{ExecuteMsg, GetConfigResponse, InstantiateMsg, QueryMsg};
+    use crate::msg::{
+        AllAirdropsResponse, ExecuteMsg, GetConfigResponse, InstantiateMsg, MigrateMsg, QueryMsg,
+    };
     use crate::state::{
-        AirdropRegistryInfo, Config, TmpManagerStore, AIRDROP_REGISTRY, CONFIG, TMP_MANAGER_STORE,
+        AirdropAsset, AirdropRegistryInfo, ClaimFormat, Config, LegacyAirdropRegistryInfo,
+        TmpManagerStore, AIRDROP_REGISTRY, CONFIG, LEGACY_AIRDROP_REGISTRY, TMP_MANAGER_STORE,
     };
     use crate::ContractError;
     use cosmwasm_std::testing::{
@@ -13,7 +16,7 @@ mod tests {
     };
     use cosmwasm_std::{
         coins, from_binary, to_binary, Addr, Attribute, BankMsg, Coin, Empty, Env, MessageInfo,
-        OwnedDeps, Response, SubMsg, Uint128, WasmMsg,
+        OwnedDeps, Response, SubMsg, Timestamp, Uint128, WasmMsg,
     };
     use cw20::Cw20ExecuteMsg;
 
@@ -66,7 +69,12 @@ mod tests {
             ExecuteMsg::UpdateAirdropRegistry {
                 airdrop_token: "".to_string(),
                 airdrop_contract: "".to_string(),
-                cw20_contract: "".to_string(),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked(""),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                stages: vec![],
+                claim_deadline: None,
             },
         )
         .unwrap_err();
@@ -82,14 +90,90 @@ mod tests {
             ExecuteMsg::UpdateAirdropRegistry {
                 airdrop_token: "".to_string(),
                 airdrop_contract: "".to_string(),
-                cw20_contract: "".to_string(),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked(""),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                stages: vec![],
+                claim_deadline: None,
             },
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::TokenEmpty {}));
 
         /*
-           Test - 3. Success
+            Test - 3. Empty native denom or claim template
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateAirdropRegistry {
+                airdrop_token: "atom".to_string(),
+                airdrop_contract: "atom_airdrop_contract".to_string(),
+                asset: AirdropAsset::Native {
+                    denom: "".to_string(),
+                },
+                claim_format: ClaimFormat::Plain {},
+                stages: vec![],
+                claim_deadline: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::DenomEmpty {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateAirdropRegistry {
+                airdrop_token: "atom".to_string(),
+                airdrop_contract: "atom_airdrop_contract".to_string(),
+                asset: AirdropAsset::Native {
+                    denom: "uatom".to_string(),
+                },
+                claim_format: ClaimFormat::Template {
+                    template: " ".to_string(),
+                },
+                stages: vec![],
+                claim_deadline: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ClaimTemplateEmpty {}));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateAirdropRegistry {
+                airdrop_token: "atom".to_string(),
+                airdrop_contract: "atom_airdrop_contract".to_string(),
+                asset: AirdropAsset::Native {
+                    denom: "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+                        .to_string(),
+                },
+                claim_format: ClaimFormat::Template {
+                    template: r#"{"claim":{"amount":{{amount}}}}"#.to_string(),
+                },
+                stages: vec![],
+                claim_deadline: None,
+            },
+        )
+        .unwrap();
+        let atom_info = AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "atom".to_string())
+            .unwrap();
+        assert_eq!(
+            atom_info.asset,
+            AirdropAsset::Native {
+                denom: "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2"
+                    .to_string()
+            }
+        );
+
+        /*
+           Test - 4. Success
         */
         let res = execute(
             deps.as_mut(),
@@ -98,7 +182,12 @@ mod tests {
             ExecuteMsg::UpdateAirdropRegistry {
                 airdrop_token: "anc".to_string(),
                 airdrop_contract: "anc_airdrop_contract".to_string(),
-                cw20_contract: "anc_cw20_contract".to_string(),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked("anc_cw20_contract"),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                stages: vec![1, 2],
+                claim_deadline: Some(Timestamp::from_seconds(1000)),
             },
         )
         .unwrap();
@@ -110,9 +199,326 @@ mod tests {
             AirdropRegistryInfo {
                 token: "anc".to_string(),
                 airdrop_contract: Addr::unchecked("anc_airdrop_contract"),
-                cw20_contract: Addr::unchecked("anc_cw20_contract")
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked("anc_cw20_contract"),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                enabled: true,
+                stages: vec![1, 2],
+                claim_deadline: Some(Timestamp::from_seconds(1000)),
+                added_at: env.block.time,
             }
         );
+
+        /*
+           Test - 5. Updating keeps the enabled flag and the time the entry was added
+        */
+        let mut later_env = mock_env();
+        later_env.block.time = env.block.time.plus_seconds(100);
+        execute(
+            deps.as_mut(),
+            later_env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetAirdropEnabled {
+                airdrop_token: "anc".to_string(),
+                enabled: false,
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            later_env,
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateAirdropRegistry {
+                airdrop_token: "anc".to_string(),
+                airdrop_contract: "anc_airdrop_contract_v2".to_string(),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked("anc_cw20_contract"),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                stages: vec![3],
+                claim_deadline: None,
+            },
+        )
+        .unwrap();
+        let anc_info = AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "anc".to_string())
+            .unwrap();
+        assert!(!anc_info.enabled);
+        assert_eq!(anc_info.stages, vec![3]);
+        assert_eq!(anc_info.added_at, env.block.time);
+        assert_eq!(
+            anc_info.airdrop_contract,
+            Addr::unchecked("anc_airdrop_contract_v2")
+        );
+    }
+
+    fn register_airdrop(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, token: &str) {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateAirdropRegistry {
+                airdrop_token: token.to_string(),
+                airdrop_contract: format!("{}_airdrop_contract", token),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked(format!("{}_cw20_contract", token)),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                stages: vec![],
+                claim_deadline: None,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_remove_airdrop() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+        register_airdrop(&mut deps, "anc");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("not-creator", &[]),
+            ExecuteMsg::RemoveAirdrop {
+                airdrop_token: "anc".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::RemoveAirdrop {
+                airdrop_token: "mir".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AirdropNotFound(String { .. })));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::RemoveAirdrop {
+                airdrop_token: "ANC".to_string(),
+            },
+        )
+        .unwrap();
+        let anc_info = AIRDROP_REGISTRY
+            .may_load(deps.as_mut().storage, "anc".to_string())
+            .unwrap();
+        assert_eq!(anc_info, None);
+    }
+
+    #[test]
+    fn test_set_airdrop_enabled() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+        register_airdrop(&mut deps, "anc");
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("not-creator", &[]),
+            ExecuteMsg::SetAirdropEnabled {
+                airdrop_token: "anc".to_string(),
+                enabled: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetAirdropEnabled {
+                airdrop_token: "mir".to_string(),
+                enabled: false,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AirdropNotFound(String { .. })));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetAirdropEnabled {
+                airdrop_token: "anc".to_string(),
+                enabled: false,
+            },
+        )
+        .unwrap();
+        let anc_info = AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "anc".to_string())
+            .unwrap();
+        assert!(!anc_info.enabled);
+    }
+
+    #[test]
+    fn test_all_airdrops() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+        for token in ["mir", "anc", "pylon"] {
+            register_airdrop(&mut deps, token);
+        }
+
+        let res: AllAirdropsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AllAirdrops {
+                    start_after: None,
+                    limit: Some(2),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let tokens: Vec<String> = res.airdrops.into_iter().map(|info| info.token).collect();
+        assert_eq!(tokens, vec!["anc".to_string(), "mir".to_string()]);
+
+        let res: AllAirdropsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AllAirdrops {
+                    start_after: Some("mir".to_string()),
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let tokens: Vec<String> = res.airdrops.into_iter().map(|info| info.token).collect();
+        assert_eq!(tokens, vec!["pylon".to_string()]);
+    }
+
+    #[test]
+    fn test_migrate_legacy_airdrops() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+        LEGACY_AIRDROP_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                "anc".to_string(),
+                &LegacyAirdropRegistryInfo {
+                    token: "anc".to_string(),
+                    airdrop_contract: Addr::unchecked("anc_airdrop_contract"),
+                    cw20_contract: Addr::unchecked("anc_cw20_contract"),
+                    enabled: None,
+                    stages: None,
+                    claim_deadline: None,
+                    added_at: None,
+                },
+            )
+            .unwrap();
+        assert!(AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "anc".to_string())
+            .is_err());
+        // Registered after the enabled flag and metadata were added.
+        LEGACY_AIRDROP_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                "mir".to_string(),
+                &LegacyAirdropRegistryInfo {
+                    token: "mir".to_string(),
+                    airdrop_contract: Addr::unchecked("mir_airdrop_contract"),
+                    cw20_contract: Addr::unchecked("mir_cw20_contract"),
+                    enabled: Some(false),
+                    stages: Some(vec![1, 2]),
+                    claim_deadline: Some(Timestamp::from_seconds(2000)),
+                    added_at: Some(Timestamp::from_seconds(1000)),
+                },
+            )
+            .unwrap();
+        // Already in the current format.
+        let ust_info = AirdropRegistryInfo {
+            token: "ust".to_string(),
+            airdrop_contract: Addr::unchecked("ust_airdrop_contract"),
+            asset: AirdropAsset::Native {
+                denom: "uusd".to_string(),
+            },
+            claim_format: ClaimFormat::MerkleWithoutStage {},
+            enabled: false,
+            stages: vec![3],
+            claim_deadline: None,
+            added_at: Timestamp::from_seconds(500),
+        };
+        AIRDROP_REGISTRY
+            .save(deps.as_mut().storage, "ust".to_string(), &ust_info)
+            .unwrap();
+
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        let anc_info = AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "anc".to_string())
+            .unwrap();
+        assert_eq!(
+            anc_info,
+            AirdropRegistryInfo {
+                token: "anc".to_string(),
+                airdrop_contract: Addr::unchecked("anc_airdrop_contract"),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked("anc_cw20_contract"),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                enabled: true,
+                stages: vec![],
+                claim_deadline: None,
+                added_at: env.block.time,
+            }
+        );
+        let mir_info = AIRDROP_REGISTRY
+            .load(deps.as_mut().storage, "mir".to_string())
+            .unwrap();
+        assert_eq!(
+            mir_info,
+            AirdropRegistryInfo {
+                token: "mir".to_string(),
+                airdrop_contract: Addr::unchecked("mir_airdrop_contract"),
+                asset: AirdropAsset::Cw20 {
+                    contract: Addr::unchecked("mir_cw20_contract"),
+                },
+                claim_format: ClaimFormat::MerkleWithStage {},
+                enabled: false,
+                stages: vec![1, 2],
+                claim_deadline: Some(Timestamp::from_seconds(2000)),
+                added_at: Timestamp::from_seconds(1000),
+            }
+        );
+        assert_eq!(
+            AIRDROP_REGISTRY
+                .load(deps.as_mut().storage, "ust".to_string())
+                .unwrap(),
+            ust_info
+        );
+
+        // A second migrate leaves every entry as it is.
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            AIRDROP_REGISTRY
+                .load(deps.as_mut().storage, "mir".to_string())
+                .unwrap(),
+            mir_info
+        );
     }
 
     #[test]
@@ -4,7 +4,9 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use reward::msg::{
-    ExecuteMsg, GetConfigResponse, InstantiateMsg, QueryMsg, TmpManagerStoreResponse,
+    Cw20HookMsg, Cw20RewardBalancesResponse, Cw20RewardTokensResponse, ExecuteMsg,
+    GetConfigResponse, InstantiateMsg, QueryMsg, RewardDenomsResponse, SwapRoutesResponse,
+    SwapThresholdsResponse, TmpManagerStoreResponse,
 };
 use reward::state::*;
 
@@ -20,4 +22,10 @@ fn main() {
     export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(GetConfigResponse), &out_dir);
     export_schema(&schema_for!(TmpManagerStoreResponse), &out_dir);
+    export_schema(&schema_for!(SwapRoutesResponse), &out_dir);
+    export_schema(&schema_for!(RewardDenomsResponse), &out_dir);
+    export_schema(&schema_for!(Cw20RewardTokensResponse), &out_dir);
+    export_schema(&schema_for!(Cw20RewardBalancesResponse), &out_dir);
+    export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(SwapThresholdsResponse), &out_dir);
 }
@@ -3,18 +3,33 @@ use cosmwasm_std::entry_point;
 
 use crate::error::ContractError;
 use crate::msg::{
-    ExecuteMsg, GetConfigResponse, InstantiateMsg, MigrateMsg, QueryMsg, TmpManagerStoreResponse,
+    AssetInfo, ChainMsg, Cw20HookMsg, Cw20RewardBalancesResponse, Cw20RewardToken,
+    Cw20RewardTokensResponse, DenomSwapRoute, DenomSwapThreshold, ExecuteMsg, GetConfigResponse,
+    InstantiateMsg, MigrateMsg, ProtocolFee, QueryMsg, RewardDenomsResponse, RouterExecuteMsg,
+    RouterQueryMsg, SimulateSwapOperationsResponse, SwapOperation, SwapRoutesResponse,
+    SwapThresholdsResponse, TmpManagerStoreResponse,
 };
 
-use crate::state::{Config, TmpManagerStore, CONFIG, TMP_MANAGER_STORE};
+use crate::state::{
+    Config, Cw20RewardAction, SwapRoute, TmpManagerStore, CONFIG, CW20_REWARD_TOKENS,
+    REWARD_DENOMS, SWAP_ROUTES, SWAP_THRESHOLDS, TMP_MANAGER_STORE,
+};
 use cw2::set_contract_version;
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg};
 
 use cosmwasm_std::{
-    attr, to_binary, Addr, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Response, StdResult,
-    Uint128,
+    attr, from_binary, to_binary, Addr, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
+    MessageInfo, Order, Response, StdError, StdResult, Uint128, WasmMsg,
+};
+#[cfg(feature = "terra")]
+use stader_utils::coin_utils::decimal_division_in_256;
+use stader_utils::coin_utils::{
+    decimal_multiplication_in_256, decimal_subtraction_in_256, get_decimal_from_uint128,
+    uint128_from_decimal,
 };
 use stader_utils::helpers::send_funds_msg;
-use terra_cosmwasm::{create_swap_msg, ExchangeRatesResponse, TerraMsgWrapper, TerraQuerier};
+#[cfg(feature = "terra")]
+use terra_cosmwasm::{create_swap_msg, ExchangeRatesResponse, TerraQuerier};
 
 const CONTRACT_NAME: &str = "reward";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -25,15 +40,22 @@ pub fn instantiate(
     _env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
+    let reward_denom = match msg.reward_denom {
+        Some(reward_denom) => reward_denom,
+        None => deps.querier.query_bonded_denom()?,
+    };
+
     let config = Config {
         manager: info.sender,
-        reward_denom: "uluna".to_string(),
+        reward_denom,
         staking_contract: deps
             .api
             .addr_validate(msg.staking_contract.to_lowercase().as_str())?,
+        max_spread: None,
     };
     CONFIG.save(deps.storage, &config)?;
+    REWARD_DENOMS.save(deps.storage, &get_default_reward_denoms())?;
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     Ok(Response::default())
@@ -41,41 +63,96 @@ pub fn instantiate(
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn migrate(
-    _deps: DepsMut,
+    deps: DepsMut,
     _env: Env,
     _msg: MigrateMsg,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
+    // Contracts instantiated before the whitelist was kept in storage start from the default list
+    if REWARD_DENOMS.may_load(deps.storage)?.is_none() {
+        REWARD_DENOMS.save(deps.storage, &get_default_reward_denoms())?;
+    }
+
     Ok(Response::default())
 }
 
+#[cfg(feature = "terra")]
+fn get_default_reward_denoms() -> Vec<String> {
+    // got this list from https://fcd.terra.dev/v1/txs/gas_prices. These are native Terra
+    vec![
+        "uluna".to_string(),
+        "usdr".to_string(),
+        "uusd".to_string(),
+        "ukrw".to_string(),
+        "umnt".to_string(),
+        "ueur".to_string(),
+        "ucny".to_string(),
+        "ujpy".to_string(),
+        "ugbp".to_string(),
+        "uinr".to_string(),
+        "ucad".to_string(),
+        "uchf".to_string(),
+        "uaud".to_string(),
+        "usgd".to_string(),
+        "uthb".to_string(),
+        "usek".to_string(),
+        "unok".to_string(),
+        "udkk".to_string(),
+        "uidr".to_string(),
+        "uphp".to_string(),
+        "uhkd".to_string(),
+    ]
+}
+
+#[cfg(not(feature = "terra"))]
+fn get_default_reward_denoms() -> Vec<String> {
+    vec![]
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
     match msg {
         ExecuteMsg::Swap {} => swap(deps, info, env),
         ExecuteMsg::Transfer {
             reward_amount,
             reward_withdraw_contract,
-            protocol_fee_amount: protocol_fee,
-            protocol_fee_contract,
+            protocol_fees,
         } => transfer(
             deps,
             info,
             env,
             reward_amount,
             reward_withdraw_contract,
-            protocol_fee,
-            protocol_fee_contract,
+            protocol_fees,
         ),
         ExecuteMsg::UpdateConfig {
             staking_contract: pools_contract,
-        } => update_config(deps, info, env, pools_contract),
+            max_spread,
+            clear_max_spread,
+        } => update_config(
+            deps,
+            info,
+            env,
+            pools_contract,
+            max_spread,
+            clear_max_spread.unwrap_or(false),
+        ),
         ExecuteMsg::SetManager { manager } => set_manager(deps, info, env, manager),
         ExecuteMsg::AcceptManager {} => accept_manager(deps, info, env),
+        ExecuteMsg::SetSwapRoute { denom, route } => set_swap_route(deps, info, env, denom, route),
+        ExecuteMsg::SetSwapThreshold { denom, min_amount } => {
+            set_swap_threshold(deps, info, env, denom, min_amount)
+        }
+        ExecuteMsg::AddRewardDenom { denom } => add_reward_denom(deps, info, env, denom),
+        ExecuteMsg::RemoveRewardDenom { denom } => remove_reward_denom(deps, info, env, denom),
+        ExecuteMsg::SetCw20RewardToken { token, action } => {
+            set_cw20_reward_token(deps, info, env, token, action)
+        }
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
     }
 }
 
@@ -84,7 +161,7 @@ pub fn set_manager(
     info: MessageInfo,
     _env: Env,
     manager: String,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     if info.sender != config.manager {
         return Err(ContractError::Unauthorized {});
@@ -104,7 +181,7 @@ pub fn accept_manager(
     deps: DepsMut,
     info: MessageInfo,
     _env: Env,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
 
     let tmp_manager_store =
@@ -127,71 +204,524 @@ pub fn accept_manager(
     Ok(Response::default())
 }
 
-// Swaps all rewards accrued in this contract to reward denom - luna.
+// Swaps all rewards accrued in this contract to the reward denom.
 pub fn swap(
     deps: DepsMut,
     info: MessageInfo,
     env: Env,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+) -> Result<Response<ChainMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
     if info.sender != config.staking_contract {
         return Err(ContractError::Unauthorized {});
     }
 
-    let mut messages = vec![];
-    let total_rewards = deps.querier.query_all_balances(env.contract.address)?;
+    let plan = get_swap_plan(&deps, &env, &config)?;
 
-    // got this list from https://fcd.terra.dev/v1/txs/gas_prices. These are native Terra
-    let denoms = vec![
-        "uluna".to_string(),
-        "usdr".to_string(),
-        "uusd".to_string(),
-        "ukrw".to_string(),
-        "umnt".to_string(),
-        "ueur".to_string(),
-        "ucny".to_string(),
-        "ujpy".to_string(),
-        "ugbp".to_string(),
-        "uinr".to_string(),
-        "ucad".to_string(),
-        "uchf".to_string(),
-        "uaud".to_string(),
-        "usgd".to_string(),
-        "uthb".to_string(),
-        "usek".to_string(),
-        "unok".to_string(),
-        "udkk".to_string(),
-        "uidr".to_string(),
-        "uphp".to_string(),
-        "uhkd".to_string(),
-    ];
+    let res = Response::new()
+        .add_messages(plan.messages)
+        .add_attributes(vec![
+            attr("action", "swap"),
+            attr("below_threshold", plan.below_threshold.join(",")),
+            attr("above_max_spread", plan.above_max_spread.join(",")),
+        ]);
+
+    Ok(res)
+}
+
+// Swap messages for this round, and the denoms held back from it.
+#[derive(Default)]
+struct SwapPlan {
+    messages: Vec<CosmosMsg<ChainMsg>>,
+    below_threshold: Vec<String>,
+    above_max_spread: Vec<String>,
+}
+
+fn get_swap_plan(deps: &DepsMut, env: &Env, config: &Config) -> StdResult<SwapPlan> {
+    let mut plan = SwapPlan::default();
+    let mut market_rewards = vec![];
+    let total_rewards = deps
+        .querier
+        .query_all_balances(env.contract.address.clone())?;
+
+    for coin in total_rewards {
+        if coin.denom == config.reward_denom || coin.amount.is_zero() {
+            continue;
+        }
+
+        let min_swap_amount = SWAP_THRESHOLDS
+            .may_load(deps.storage, coin.denom.as_str())?
+            .unwrap_or_else(Uint128::zero);
+        if coin.amount.lt(&min_swap_amount) {
+            plan.below_threshold.push(coin.denom);
+            continue;
+        }
+
+        match SWAP_ROUTES.may_load(deps.storage, coin.denom.as_str())? {
+            Some(SwapRoute::DexRouter { router, operations }) => {
+                let mut minimum_receive = None;
+                if let Some(max_spread) = config.max_spread {
+                    match get_router_minimum_receive(
+                        deps,
+                        config,
+                        max_spread,
+                        &router,
+                        &operations,
+                        &coin,
+                    )? {
+                        Some(router_minimum) => minimum_receive = Some(router_minimum),
+                        None => {
+                            plan.above_max_spread.push(coin.denom);
+                            continue;
+                        }
+                    }
+                }
+
+                plan.messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: router.to_string(),
+                    msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                        operations,
+                        minimum_receive,
+                        to: None,
+                    })?,
+                    funds: vec![coin],
+                }));
+            }
+            Some(SwapRoute::Skip {}) => {}
+            Some(SwapRoute::NativeMarket {}) | None => market_rewards.push(coin),
+        }
+    }
+
+    add_market_swaps(deps, config, market_rewards, &mut plan)?;
+    add_cw20_reward_swaps(deps, env, config, &mut plan)?;
+    Ok(plan)
+}
+
+fn simulate_router_swap(
+    deps: &DepsMut,
+    router: &Addr,
+    operations: &[SwapOperation],
+    offer_amount: Uint128,
+) -> StdResult<Uint128> {
+    let simulation: SimulateSwapOperationsResponse = deps.querier.query_wasm_smart(
+        router.to_string(),
+        &RouterQueryMsg::SimulateSwapOperations {
+            offer_amount,
+            operations: operations.to_vec(),
+        },
+    )?;
+    Ok(simulation.amount)
+}
+
+// Least amount of reward denom to accept for a router swap of `coin`: the oracle minimum when the
+// oracle quotes the denom, else the router's own quote less the max spread.
+// None if the router quotes below the oracle minimum.
+fn get_router_minimum_receive(
+    deps: &DepsMut,
+    config: &Config,
+    max_spread: Decimal,
+    router: &Addr,
+    operations: &[SwapOperation],
+    coin: &Coin,
+) -> StdResult<Option<Uint128>> {
+    let simulated_amount = simulate_router_swap(deps, router, operations, coin.amount)?;
+    // The oracle fails for denoms it does not quote, which covers cw20 tokens and most dex-only denoms.
+    match get_oracle_minimum_receive(deps, config, max_spread, coin) {
+        Ok(oracle_minimum) if simulated_amount.lt(&oracle_minimum) => Ok(None),
+        Ok(oracle_minimum) => Ok(Some(oracle_minimum)),
+        Err(_) => Ok(Some(uint128_from_decimal(decimal_multiplication_in_256(
+            get_decimal_from_uint128(simulated_amount),
+            decimal_subtraction_in_256(Decimal::one(), max_spread),
+        )))),
+    }
+}
+
+// Swaps or forwards the balance of every registered cw20 reward token.
+// With a max spread set, swaps get a minimum receive and tokens quoted below the oracle minimum are held back.
+fn add_cw20_reward_swaps(
+    deps: &DepsMut,
+    env: &Env,
+    config: &Config,
+    plan: &mut SwapPlan,
+) -> StdResult<()> {
+    let cw20_reward_tokens = CW20_REWARD_TOKENS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    for (token, action) in cw20_reward_tokens {
+        let token = String::from_utf8(token)?;
+        let balance: BalanceResponse = deps.querier.query_wasm_smart(
+            token.clone(),
+            &Cw20QueryMsg::Balance {
+                address: env.contract.address.to_string(),
+            },
+        )?;
+        if balance.balance.is_zero() {
+            continue;
+        }
+
+        let min_swap_amount = SWAP_THRESHOLDS
+            .may_load(deps.storage, token.as_str())?
+            .unwrap_or_else(Uint128::zero);
+        if balance.balance.lt(&min_swap_amount) {
+            plan.below_threshold.push(token);
+            continue;
+        }
+
+        let cw20_msg = match action {
+            Cw20RewardAction::Swap { router, operations } => {
+                let mut minimum_receive = None;
+                if let Some(max_spread) = config.max_spread {
+                    let coin = Coin::new(balance.balance.u128(), token.clone());
+                    match get_router_minimum_receive(
+                        deps,
+                        config,
+                        max_spread,
+                        &router,
+                        &operations,
+                        &coin,
+                    )? {
+                        Some(router_minimum) => minimum_receive = Some(router_minimum),
+                        None => {
+                            plan.above_max_spread.push(token);
+                            continue;
+                        }
+                    }
+                }
+
+                Cw20ExecuteMsg::Send {
+                    contract: router.to_string(),
+                    amount: balance.balance,
+                    msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                        operations,
+                        minimum_receive,
+                        to: None,
+                    })?,
+                }
+            }
+            Cw20RewardAction::Forward { recipient } => Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount: balance.balance,
+            },
+        };
+        plan.messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token,
+            msg: to_binary(&cw20_msg)?,
+            funds: vec![],
+        }));
+    }
+
+    Ok(())
+}
+
+// Least amount of reward denom to accept for `coin`, from the oracle rate less the max spread.
+// Fails if the oracle cannot quote the denom.
+#[cfg(feature = "terra")]
+fn get_oracle_minimum_receive(
+    deps: &DepsMut,
+    config: &Config,
+    max_spread: Decimal,
+    coin: &Coin,
+) -> StdResult<Uint128> {
+    let rates = query_exchange_rates(deps, config.reward_denom.clone(), vec![coin.denom.clone()])?;
+    let rate = match rates.exchange_rates.first() {
+        Some(rate) if !rate.exchange_rate.is_zero() => rate.exchange_rate,
+        _ => {
+            return Err(StdError::generic_err(format!(
+                "No exchange rate for {}",
+                coin.denom
+            )))
+        }
+    };
+
+    let expected_amount = decimal_division_in_256(get_decimal_from_uint128(coin.amount), rate);
+    Ok(uint128_from_decimal(decimal_multiplication_in_256(
+        expected_amount,
+        decimal_subtraction_in_256(Decimal::one(), max_spread),
+    )))
+}
+
+#[cfg(not(feature = "terra"))]
+fn get_oracle_minimum_receive(
+    _deps: &DepsMut,
+    _config: &Config,
+    _max_spread: Decimal,
+    coin: &Coin,
+) -> StdResult<Uint128> {
+    Err(StdError::generic_err(format!(
+        "No exchange rate for {}",
+        coin.denom
+    )))
+}
+
+#[cfg(feature = "terra")]
+fn add_market_swaps(
+    deps: &DepsMut,
+    config: &Config,
+    rewards: Vec<Coin>,
+    plan: &mut SwapPlan,
+) -> StdResult<()> {
+    let denoms = REWARD_DENOMS.load(deps.storage)?;
 
     let mut is_listed = true;
-    if query_exchange_rates(&deps, config.reward_denom.clone(), denoms.clone()).is_err() {
+    if query_exchange_rates(deps, config.reward_denom.clone(), denoms.clone()).is_err() {
         is_listed = false;
     }
 
-    for coin in total_rewards {
-        // No need to swap uluna and Don't process rewards that aren't native to Terra
-        if coin.denom == config.reward_denom.clone() || !denoms.contains(&coin.denom) {
+    for coin in rewards {
+        // Don't process rewards that aren't whitelisted
+        if !denoms.contains(&coin.denom) {
             continue;
         }
 
-        if is_listed {
-            messages.push(create_swap_msg(coin, config.reward_denom.to_string()));
-        } else if query_exchange_rates(&deps, config.reward_denom.clone(), vec![coin.denom.clone()])
-            .is_ok()
+        if !is_listed
+            && query_exchange_rates(deps, config.reward_denom.clone(), vec![coin.denom.clone()])
+                .is_err()
         {
-            messages.push(create_swap_msg(coin, config.reward_denom.to_string()));
+            continue;
+        }
+
+        // A denom the oracle cannot price right now is held back rather than swapped without a floor.
+        if let Some(max_spread) = config.max_spread {
+            let below_minimum = match get_oracle_minimum_receive(deps, config, max_spread, &coin) {
+                Ok(minimum_receive) => TerraQuerier::new(&deps.querier)
+                    .query_swap(coin.clone(), config.reward_denom.clone())?
+                    .receive
+                    .amount
+                    .lt(&minimum_receive),
+                Err(_) => true,
+            };
+            if below_minimum {
+                plan.above_max_spread.push(coin.denom);
+                continue;
+            }
         }
+
+        plan.messages
+            .push(create_swap_msg(coin, config.reward_denom.to_string()));
     }
 
-    let res = Response::new()
-        .add_messages(messages)
-        .add_attributes(vec![attr("action", "swap")]);
+    Ok(())
+}
 
-    Ok(res)
+// Chains without a market module leave rewards without a swap route in this contract.
+#[cfg(not(feature = "terra"))]
+fn add_market_swaps(
+    _deps: &DepsMut,
+    _config: &Config,
+    _rewards: Vec<Coin>,
+    _plan: &mut SwapPlan,
+) -> StdResult<()> {
+    Ok(())
+}
+
+pub fn add_reward_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    denom: String,
+) -> Result<Response<ChainMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut reward_denoms = REWARD_DENOMS.load(deps.storage)?;
+    if reward_denoms.contains(&denom) {
+        return Err(ContractError::RewardDenomAlreadyAdded {});
+    }
+
+    // check if the oracle can price the denom against the reward denom
+    if !is_listed_on_oracle(&deps, &config, &denom) {
+        return Err(ContractError::RewardDenomNotListed {});
+    }
+
+    reward_denoms.push(denom.clone());
+    REWARD_DENOMS.save(deps.storage, &reward_denoms)?;
+
+    Ok(Response::new().add_attribute("new_reward_denom", denom))
+}
+
+pub fn remove_reward_denom(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    denom: String,
+) -> Result<Response<ChainMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut reward_denoms = REWARD_DENOMS.load(deps.storage)?;
+    if !reward_denoms.contains(&denom) {
+        return Err(ContractError::RewardDenomNotAdded {});
+    }
+
+    reward_denoms.retain(|reward_denom| reward_denom.ne(&denom));
+    REWARD_DENOMS.save(deps.storage, &reward_denoms)?;
+
+    Ok(Response::new().add_attribute("removed_reward_denom", denom))
+}
+
+#[cfg(feature = "terra")]
+fn is_listed_on_oracle(deps: &DepsMut, config: &Config, denom: &str) -> bool {
+    query_exchange_rates(deps, config.reward_denom.clone(), vec![denom.to_string()]).is_ok()
+}
+
+// Without an oracle there is nothing to check the denom against.
+#[cfg(not(feature = "terra"))]
+fn is_listed_on_oracle(_deps: &DepsMut, _config: &Config, _denom: &str) -> bool {
+    true
+}
+
+pub fn set_swap_threshold(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    denom: String,
+    min_amount: Option<Uint128>,
+) -> Result<Response<ChainMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match min_amount {
+        Some(min_amount) => SWAP_THRESHOLDS.save(deps.storage, denom.as_str(), &min_amount)?,
+        None => SWAP_THRESHOLDS.remove(deps.storage, denom.as_str()),
+    }
+
+    Ok(Response::default())
+}
+
+pub fn set_swap_route(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    denom: String,
+    route: Option<SwapRoute>,
+) -> Result<Response<ChainMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if denom == config.reward_denom {
+        return Err(ContractError::InvalidSwapRoute {});
+    }
+
+    let route = match route {
+        Some(route) => route,
+        None => {
+            SWAP_ROUTES.remove(deps.storage, denom.as_str());
+            return Ok(Response::default());
+        }
+    };
+
+    let route = match route {
+        SwapRoute::DexRouter { router, operations } => {
+            if !asks_reward_denom(&config, &operations) {
+                return Err(ContractError::InvalidSwapRoute {});
+            }
+
+            SwapRoute::DexRouter {
+                router: deps.api.addr_validate(router.as_str())?,
+                operations,
+            }
+        }
+        SwapRoute::NativeMarket {} => {
+            if cfg!(not(feature = "terra")) {
+                return Err(ContractError::InvalidSwapRoute {});
+            }
+            SwapRoute::NativeMarket {}
+        }
+        SwapRoute::Skip {} => SwapRoute::Skip {},
+    };
+
+    SWAP_ROUTES.save(deps.storage, denom.as_str(), &route)?;
+    Ok(Response::default())
+}
+
+fn asks_reward_denom(config: &Config, operations: &[SwapOperation]) -> bool {
+    match operations.last() {
+        Some(SwapOperation::NativeSwap { ask_denom, .. }) => ask_denom.eq(&config.reward_denom),
+        Some(SwapOperation::AstroSwap { ask_asset_info, .. }) => {
+            ask_asset_info.eq(&AssetInfo::NativeToken {
+                denom: config.reward_denom.clone(),
+            })
+        }
+        None => false,
+    }
+}
+
+pub fn set_cw20_reward_token(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    token: String,
+    action: Option<Cw20RewardAction>,
+) -> Result<Response<ChainMsg>, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+
+    if info.sender != config.manager {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let token = deps.api.addr_validate(token.to_lowercase().as_str())?;
+    let action = match action {
+        Some(action) => action,
+        None => {
+            CW20_REWARD_TOKENS.remove(deps.storage, &token);
+            return Ok(Response::default());
+        }
+    };
+
+    let action = match action {
+        Cw20RewardAction::Swap { router, operations } => {
+            if !asks_reward_denom(&config, &operations) {
+                return Err(ContractError::InvalidSwapRoute {});
+            }
+
+            Cw20RewardAction::Swap {
+                router: deps.api.addr_validate(router.as_str())?,
+                operations,
+            }
+        }
+        Cw20RewardAction::Forward { recipient } => Cw20RewardAction::Forward {
+            recipient: deps.api.addr_validate(recipient.as_str())?,
+        },
+    };
+
+    CW20_REWARD_TOKENS.save(deps.storage, &token, &action)?;
+    Ok(Response::default())
+}
+
+// Accepts cw20 rewards sent with a hook. Tokens transferred without one are picked up on swap all the same.
+pub fn receive_cw20(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response<ChainMsg>, ContractError> {
+    // only registered token contracts can execute this message
+    if !CW20_REWARD_TOKENS.has(deps.storage, &info.sender) {
+        return Err(ContractError::Cw20TokenNotRegistered {});
+    }
+
+    match from_binary(&cw20_msg.msg) {
+        Ok(Cw20HookMsg::DepositRewards {}) => Ok(Response::new().add_attributes(vec![
+            attr("action", "deposit_rewards"),
+            attr("token", info.sender),
+            attr("sender", cw20_msg.sender),
+            attr("amount", cw20_msg.amount),
+        ])),
+        Err(_err) => Err(ContractError::InvalidCw20HookMsg {}),
+    }
 }
 
 // Transfers luna to SCC at the behest of Pools contract
@@ -201,16 +731,19 @@ pub fn transfer(
     env: Env,
     reward_amount: Uint128,
     reward_withdraw_contract: Addr,
-    protocol_fee: Uint128,
-    protocol_fee_contract: Addr,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+    protocol_fees: Vec<ProtocolFee>,
+) -> Result<Response<ChainMsg>, ContractError> {
     let config = CONFIG.load(deps.storage)?;
 
     if info.sender != config.staking_contract {
         return Err(ContractError::Unauthorized {});
     }
 
-    let total_withdrawal_amount = reward_amount.checked_add(protocol_fee).unwrap();
+    let total_withdrawal_amount = protocol_fees
+        .iter()
+        .fold(reward_amount, |total, protocol_fee| {
+            total.checked_add(protocol_fee.amount).unwrap()
+        });
     if deps
         .querier
         .query_balance(env.contract.address, config.reward_denom.clone())?
@@ -227,15 +760,22 @@ pub fn transfer(
         ));
     }
 
-    if !protocol_fee.is_zero() {
+    for protocol_fee in protocol_fees {
+        if protocol_fee.amount.is_zero() {
+            continue;
+        }
         msgs.push(send_funds_msg(
-            &protocol_fee_contract,
-            &[Coin::new(protocol_fee.u128(), config.reward_denom)],
+            &protocol_fee.recipient,
+            &[Coin::new(
+                protocol_fee.amount.u128(),
+                config.reward_denom.clone(),
+            )],
         ));
     }
     Ok(Response::new().add_messages(msgs))
 }
 
+#[cfg(feature = "terra")]
 pub fn query_exchange_rates(
     deps: &DepsMut,
     base_denom: String,
@@ -251,7 +791,9 @@ pub fn update_config(
     info: MessageInfo,
     _env: Env,
     pools_contract: Option<String>,
-) -> Result<Response<TerraMsgWrapper>, ContractError> {
+    max_spread: Option<Decimal>,
+    clear_max_spread: bool,
+) -> Result<Response<ChainMsg>, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
 
     if info.sender != config.manager {
@@ -262,18 +804,104 @@ pub fn update_config(
         config.staking_contract = deps.api.addr_validate(pools_contract.unwrap().as_str())?;
     }
 
+    if clear_max_spread {
+        if max_spread.is_some() {
+            return Err(ContractError::InvalidMaxSpread {});
+        }
+        config.max_spread = None;
+    }
+
+    if let Some(max_spread) = max_spread {
+        if max_spread.gt(&Decimal::one()) {
+            return Err(ContractError::InvalidMaxSpread {});
+        }
+        config.max_spread = Some(max_spread);
+    }
+
     CONFIG.save(deps.storage, &config)?;
     Ok(Response::default())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::TmpManagerStore {} => to_binary(&query_tmp_manager_store(deps)?),
+        QueryMsg::SwapRoutes {} => to_binary(&query_swap_routes(deps)?),
+        QueryMsg::SwapThresholds {} => to_binary(&query_swap_thresholds(deps)?),
+        QueryMsg::RewardDenoms {} => to_binary(&query_reward_denoms(deps)?),
+        QueryMsg::Cw20RewardTokens {} => to_binary(&query_cw20_reward_tokens(deps)?),
+        QueryMsg::Cw20RewardBalances {} => to_binary(&query_cw20_reward_balances(deps, env)?),
     }
 }
 
+pub fn query_cw20_reward_tokens(deps: Deps) -> StdResult<Cw20RewardTokensResponse> {
+    let cw20_reward_tokens = CW20_REWARD_TOKENS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (token, action) = item?;
+            Ok(Cw20RewardToken {
+                token: Addr::unchecked(String::from_utf8(token)?),
+                action,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(Cw20RewardTokensResponse { cw20_reward_tokens })
+}
+
+pub fn query_cw20_reward_balances(deps: Deps, env: Env) -> StdResult<Cw20RewardBalancesResponse> {
+    let balances = query_cw20_reward_tokens(deps)?
+        .cw20_reward_tokens
+        .into_iter()
+        .map(|cw20_reward_token| {
+            let balance: BalanceResponse = deps.querier.query_wasm_smart(
+                cw20_reward_token.token.to_string(),
+                &Cw20QueryMsg::Balance {
+                    address: env.contract.address.to_string(),
+                },
+            )?;
+            Ok(Cw20Coin {
+                address: cw20_reward_token.token.to_string(),
+                amount: balance.balance,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(Cw20RewardBalancesResponse { balances })
+}
+
+pub fn query_reward_denoms(deps: Deps) -> StdResult<RewardDenomsResponse> {
+    let reward_denoms = REWARD_DENOMS.load(deps.storage)?;
+    Ok(RewardDenomsResponse { reward_denoms })
+}
+
+pub fn query_swap_thresholds(deps: Deps) -> StdResult<SwapThresholdsResponse> {
+    let swap_thresholds = SWAP_THRESHOLDS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, min_amount) = item?;
+            Ok(DenomSwapThreshold {
+                denom: String::from_utf8(denom)?,
+                min_amount,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(SwapThresholdsResponse { swap_thresholds })
+}
+
+pub fn query_swap_routes(deps: Deps) -> StdResult<SwapRoutesResponse> {
+    let swap_routes = SWAP_ROUTES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (denom, route) = item?;
+            Ok(DenomSwapRoute {
+                denom: String::from_utf8(denom)?,
+                route,
+            })
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(SwapRoutesResponse { swap_routes })
+}
+
 pub fn query_tmp_manager_store(deps: Deps) -> StdResult<TmpManagerStoreResponse> {
     let tmp_manager_store = TMP_MANAGER_STORE.may_load(deps.storage)?;
     Ok(TmpManagerStoreResponse { tmp_manager_store })
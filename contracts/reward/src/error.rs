@@ -17,4 +17,25 @@ pub enum ContractError {
 
     #[error("Reward-Contract: Tmp manager store is empty")]
     TmpManagerStoreEmpty {},
+
+    #[error("Reward-Contract: Invalid swap route")]
+    InvalidSwapRoute {},
+
+    #[error("Reward-Contract: Max spread cannot be more than 1")]
+    InvalidMaxSpread {},
+
+    #[error("Reward-Contract: Reward denom already added")]
+    RewardDenomAlreadyAdded {},
+
+    #[error("Reward-Contract: Reward denom not added")]
+    RewardDenomNotAdded {},
+
+    #[error("Reward-Contract: Reward denom has no oracle exchange rate")]
+    RewardDenomNotListed {},
+
+    #[error("Reward-Contract: Cw20 token is not a registered reward token")]
+    Cw20TokenNotRegistered {},
+
+    #[error("Reward-Contract: Invalid cw20 hook message")]
+    InvalidCw20HookMsg {},
 }
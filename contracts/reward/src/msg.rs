@@ -1,33 +1,73 @@
-use crate::state::{Config, TmpManagerStore};
-use cosmwasm_std::{Addr, Uint128};
+use crate::state::{Config, Cw20RewardAction, SwapRoute, TmpManagerStore};
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw20::{Cw20Coin, Cw20ReceiveMsg};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+// Custom message type of the chain the contract is built for.
+#[cfg(feature = "terra")]
+pub type ChainMsg = terra_cosmwasm::TerraMsgWrapper;
+#[cfg(not(feature = "terra"))]
+pub type ChainMsg = cosmwasm_std::Empty;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
     pub staking_contract: String,
+    pub reward_denom: Option<String>, // Defaults to the chain's bonded denom
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct MigrateMsg {}
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFee {
+    pub recipient: Addr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    DepositRewards {},
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    Swap {}, // Swap rewards into reward denom - luna
+    Swap {}, // Swap rewards into the reward denom
     Transfer {
         reward_amount: Uint128,
         reward_withdraw_contract: Addr,
-        protocol_fee_amount: Uint128,
-        protocol_fee_contract: Addr,
+        protocol_fees: Vec<ProtocolFee>, // Protocol fee already split across the fee recipients
     }, // Transfer swapped rewards to SCC.
     UpdateConfig {
         staking_contract: Option<String>,
+        max_spread: Option<Decimal>,
+        clear_max_spread: Option<bool>, // true removes the max spread check
     },
     SetManager {
         manager: String,
     },
     AcceptManager {},
+    SetSwapRoute {
+        denom: String,
+        route: Option<SwapRoute>, // None removes the route
+    },
+    SetSwapThreshold {
+        denom: String,
+        min_amount: Option<Uint128>, // None removes the threshold
+    },
+    AddRewardDenom {
+        denom: String,
+    },
+    RemoveRewardDenom {
+        denom: String,
+    },
+    SetCw20RewardToken {
+        token: String,
+        action: Option<Cw20RewardAction>, // None removes the token
+    },
+    Receive(Cw20ReceiveMsg),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -35,6 +75,11 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     Config {},
     TmpManagerStore {},
+    SwapRoutes {},
+    SwapThresholds {},
+    RewardDenoms {},
+    Cw20RewardTokens {},
+    Cw20RewardBalances {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -46,3 +91,92 @@ pub struct GetConfigResponse {
 pub struct TmpManagerStoreResponse {
     pub tmp_manager_store: Option<TmpManagerStore>,
 }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomSwapRoute {
+    pub denom: String,
+    pub route: SwapRoute,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapRoutesResponse {
+    pub swap_routes: Vec<DenomSwapRoute>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardDenomsResponse {
+    pub reward_denoms: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20RewardToken {
+    pub token: Addr,
+    pub action: Cw20RewardAction,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20RewardTokensResponse {
+    pub cw20_reward_tokens: Vec<Cw20RewardToken>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw20RewardBalancesResponse {
+    pub balances: Vec<Cw20Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DenomSwapThreshold {
+    pub denom: String,
+    pub min_amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapThresholdsResponse {
+    pub swap_thresholds: Vec<DenomSwapThreshold>,
+}
+
+// Subset of the Astroport router interface used to swap rewards.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetInfo {
+    Token { contract_addr: Addr },
+    NativeToken { denom: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapOperation {
+    NativeSwap {
+        offer_denom: String,
+        ask_denom: String,
+    },
+    AstroSwap {
+        offer_asset_info: AssetInfo,
+        ask_asset_info: AssetInfo,
+    },
+}
+
+// Also the router's cw20 hook message, which has the same shape.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouterExecuteMsg {
+    ExecuteSwapOperations {
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RouterQueryMsg {
+    SimulateSwapOperations {
+        offer_amount: Uint128,
+        operations: Vec<SwapOperation>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SimulateSwapOperationsResponse {
+    pub amount: Uint128,
+}
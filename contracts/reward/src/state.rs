@@ -1,18 +1,57 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use crate::msg::SwapOperation;
+use cosmwasm_std::{Addr, Decimal, Uint128};
+use cw_storage_plus::{Item, Map};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
-    pub manager: Addr,          // Expect update config to be called from manager.
-    pub reward_denom: String,   // Reward denom is expected to be Luna
+    pub manager: Addr,               // Expect update config to be called from manager.
+    pub reward_denom: String, // Reward denom is expected to be the vault denom of the staking contract
     pub staking_contract: Addr, // Expect swap and transfer to be called from pools contract
+    pub max_spread: Option<Decimal>, // Max shortfall of a swap against the oracle rate. None skips the check
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+// How rewards in a denom are turned into the reward denom. Denoms without a route use the native market.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapRoute {
+    NativeMarket {},
+    DexRouter {
+        router: Addr,
+        operations: Vec<SwapOperation>, // Last operation must ask for the reward denom
+    },
+    Skip {},
+}
+
+// denom -> SwapRoute
+pub const SWAP_ROUTES: Map<&str, SwapRoute> = Map::new("swap_routes");
+
+// What happens to a CW20 reward token's balance on swap.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20RewardAction {
+    Swap {
+        router: Addr,
+        operations: Vec<SwapOperation>, // Last operation must ask for the reward denom
+    },
+    Forward {
+        recipient: Addr,
+    },
+}
+
+// cw20 token contract -> Cw20RewardAction
+pub const CW20_REWARD_TOKENS: Map<&Addr, Cw20RewardAction> = Map::new("cw20_reward_tokens");
+
+// Denoms swapped through the native market. Rewards in other denoms without a route are left as is
+pub const REWARD_DENOMS: Item<Vec<String>> = Item::new("reward_denoms");
+
+// denom or cw20 token contract -> least balance worth swapping. Smaller balances are held until the next swap
+pub const SWAP_THRESHOLDS: Map<&str, Uint128> = Map::new("swap_thresholds");
+
 // this is a tmp store to store the intermediate values of manager updates.
 // manager updates are 2 phase, we set it and then accept it. This is done to
 // add a greater assurance of the update.
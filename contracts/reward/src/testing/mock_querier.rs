@@ -0,0 +1,175 @@
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, Coin, ContractResult, Decimal, OwnedDeps, Querier,
+    QuerierResult, QueryRequest, SystemError, SystemResult, Uint128, WasmQuery,
+};
+use std::collections::HashMap;
+
+use crate::msg::{RouterQueryMsg, SimulateSwapOperationsResponse};
+use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
+use cw20::{BalanceResponse, Cw20QueryMsg};
+use stader_utils::coin_utils::{decimal_multiplication_in_256, u128_from_decimal};
+use terra_cosmwasm::{
+    ExchangeRateItem, ExchangeRatesResponse, SwapResponse, TerraQuery, TerraQueryWrapper,
+    TerraRoute,
+};
+
+pub fn mock_dependencies() -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: WasmMockQuerier::new(MockQuerier::new(&[])),
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<TerraQueryWrapper>,
+    exchange_rates: HashMap<String, Decimal>, // quote denom -> quote per reward denom
+    swap_rates: HashMap<String, Decimal>,     // offer denom -> reward denom per offer denom
+    router_rates: HashMap<String, Decimal>,   // router -> ask per offer amount
+    cw20_balances: HashMap<String, Uint128>,  // token -> balance of every holder
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<TerraQueryWrapper> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                })
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<TerraQueryWrapper>) -> Self {
+        WasmMockQuerier {
+            base,
+            exchange_rates: HashMap::new(),
+            swap_rates: HashMap::new(),
+            router_rates: HashMap::new(),
+            cw20_balances: HashMap::new(),
+        }
+    }
+
+    pub fn handle_query(&self, request: &QueryRequest<TerraQueryWrapper>) -> QuerierResult {
+        match &request {
+            QueryRequest::Custom(TerraQueryWrapper { route, query_data }) => {
+                match (route, query_data) {
+                    (
+                        TerraRoute::Oracle,
+                        TerraQuery::ExchangeRates {
+                            base_denom,
+                            quote_denoms,
+                        },
+                    ) => {
+                        let mut exchange_rates = vec![];
+                        for quote_denom in quote_denoms {
+                            match self.exchange_rates.get(quote_denom) {
+                                Some(exchange_rate) => exchange_rates.push(ExchangeRateItem {
+                                    quote_denom: quote_denom.clone(),
+                                    exchange_rate: *exchange_rate,
+                                }),
+                                None => return not_found("exchange rate"),
+                            }
+                        }
+                        let res = ExchangeRatesResponse {
+                            base_denom: base_denom.clone(),
+                            exchange_rates,
+                        };
+                        SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                    }
+                    (
+                        TerraRoute::Market,
+                        TerraQuery::Swap {
+                            offer_coin,
+                            ask_denom,
+                        },
+                    ) => match self.swap_rates.get(&offer_coin.denom) {
+                        Some(swap_rate) => {
+                            let res = SwapResponse {
+                                receive: Coin::new(
+                                    apply_rate(offer_coin.amount, *swap_rate),
+                                    ask_denom,
+                                ),
+                            };
+                            SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                        }
+                        None => not_found("swap"),
+                    },
+                    _ => panic!("Terra query not implemented!"),
+                }
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                if let Some(router_rate) = self.router_rates.get(contract_addr) {
+                    match from_binary(msg).unwrap() {
+                        RouterQueryMsg::SimulateSwapOperations { offer_amount, .. } => {
+                            let res = SimulateSwapOperationsResponse {
+                                amount: Uint128::new(apply_rate(offer_amount, *router_rate)),
+                            };
+                            SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                        }
+                    }
+                } else if let Some(balance) = self.cw20_balances.get(contract_addr) {
+                    match from_binary(msg).unwrap() {
+                        Cw20QueryMsg::Balance { .. } => {
+                            let res = BalanceResponse { balance: *balance };
+                            SystemResult::Ok(ContractResult::from(to_binary(&res)))
+                        }
+                        _ => panic!("Cw20 query not implemented!"),
+                    }
+                } else {
+                    not_found("contract")
+                }
+            }
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    pub fn update_balance(&mut self, addr: impl Into<String>, balances: Vec<Coin>) {
+        self.base.update_balance(addr, balances);
+    }
+
+    pub fn update_exchange_rates(&mut self, exchange_rates: &[(&str, Decimal)]) {
+        self.exchange_rates = to_rate_map(exchange_rates);
+    }
+
+    pub fn update_swap_rates(&mut self, swap_rates: &[(&str, Decimal)]) {
+        self.swap_rates = to_rate_map(swap_rates);
+    }
+
+    pub fn update_cw20_balances(&mut self, cw20_balances: &[(&str, Uint128)]) {
+        self.cw20_balances = cw20_balances
+            .iter()
+            .map(|(token, balance)| (token.to_string(), *balance))
+            .collect();
+    }
+
+    pub fn update_router_rates(&mut self, router_rates: &[(&str, Decimal)]) {
+        self.router_rates = to_rate_map(router_rates);
+    }
+}
+
+fn to_rate_map(rates: &[(&str, Decimal)]) -> HashMap<String, Decimal> {
+    rates
+        .iter()
+        .map(|(key, rate)| (key.to_string(), *rate))
+        .collect()
+}
+
+fn apply_rate(amount: Uint128, rate: Decimal) -> u128 {
+    u128_from_decimal(decimal_multiplication_in_256(
+        Decimal::from_ratio(amount, 1_u128),
+        rate,
+    ))
+}
+
+fn not_found(kind: &str) -> QuerierResult {
+    SystemResult::Err(SystemError::InvalidRequest {
+        error: format!("{} not found", kind),
+        request: Default::default(),
+    })
+}
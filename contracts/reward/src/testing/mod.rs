@@ -1 +1,3 @@
+#[cfg(feature = "terra")]
+mod mock_querier;
 mod tests;
@@ -2,24 +2,42 @@
 mod tests {
     use crate::contract::{execute, instantiate, query};
     use crate::error::ContractError;
-    use crate::msg::{ExecuteMsg, GetConfigResponse, InstantiateMsg, QueryMsg};
-    use crate::state::{Config, TmpManagerStore, CONFIG, TMP_MANAGER_STORE};
+    use crate::msg::{
+        AssetInfo, ChainMsg, DenomSwapRoute, DenomSwapThreshold, ExecuteMsg, GetConfigResponse,
+        InstantiateMsg, ProtocolFee, QueryMsg, RouterExecuteMsg, SwapOperation, SwapRoutesResponse,
+        SwapThresholdsResponse,
+    };
+    #[cfg(feature = "terra")]
+    use crate::msg::{
+        Cw20HookMsg, Cw20RewardBalancesResponse, Cw20RewardToken, Cw20RewardTokensResponse,
+        RewardDenomsResponse,
+    };
+    #[cfg(feature = "terra")]
+    use crate::state::Cw20RewardAction;
+    use crate::state::{Config, SwapRoute, TmpManagerStore, CONFIG, TMP_MANAGER_STORE};
+    #[cfg(feature = "terra")]
+    use crate::testing::mock_querier::mock_dependencies as custom_mock_dependencies;
     use cosmwasm_std::testing::{
         mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
     };
     use cosmwasm_std::{
-        from_binary, Addr, BankMsg, Coin, Env, MessageInfo, OwnedDeps, Response, SubMsg, Uint128,
+        attr, from_binary, to_binary, Addr, BankMsg, Coin, Decimal, Env, MessageInfo, OwnedDeps,
+        Response, SubMsg, Uint128, WasmMsg,
     };
-    use terra_cosmwasm::TerraMsgWrapper;
+    #[cfg(feature = "terra")]
+    use cw20::{Cw20Coin, Cw20ExecuteMsg, Cw20ReceiveMsg};
+    #[cfg(feature = "terra")]
+    use terra_cosmwasm::create_swap_msg;
 
     pub fn instantiate_contract(
         deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>,
         info: &MessageInfo,
         env: &Env,
         vault_denom: Option<String>,
-    ) -> Response<TerraMsgWrapper> {
+    ) -> Response<ChainMsg> {
         let instantiate_msg = InstantiateMsg {
             staking_contract: "pools_addr".to_string(),
+            reward_denom: Some(vault_denom.unwrap_or_else(|| "uluna".to_string())),
         };
 
         return instantiate(deps.as_mut(), env.clone(), info.clone(), instantiate_msg).unwrap();
@@ -29,13 +47,17 @@ mod tests {
     fn proper_initialization() {
         let mut deps = mock_dependencies(&[]);
 
+        // The reward denom is taken from the chain's bonded denom when it is not set.
+        deps.querier.update_staking("uluna", &[], &[]);
         let msg = InstantiateMsg {
             staking_contract: "pools_addr".to_string(),
+            reward_denom: None,
         };
         let expected_config = Config {
             manager: Addr::unchecked("creator"),
             reward_denom: "uluna".to_string(),
             staking_contract: Addr::unchecked("pools_addr"),
+            max_spread: None,
         };
         let info = mock_info("creator", &[]);
 
@@ -57,6 +79,7 @@ mod tests {
         instantiate_contract(&mut deps, &info, &env, None);
         let reward_withdraw_contract = Addr::unchecked("reward_withdraw_contract");
         let protocol_fee_contract = Addr::unchecked("protocol_fee_contract");
+        let insurance_fund = Addr::unchecked("insurance_fund");
         let err = execute(
             deps.as_mut(),
             env.clone(),
@@ -64,8 +87,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 reward_amount: Uint128::new(300),
                 reward_withdraw_contract: reward_withdraw_contract.clone(),
-                protocol_fee_amount: Uint128::zero(),
-                protocol_fee_contract: protocol_fee_contract.clone(),
+                protocol_fees: vec![],
             },
         )
         .unwrap_err();
@@ -80,8 +102,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 reward_amount: Uint128::new(300),
                 reward_withdraw_contract: reward_withdraw_contract.clone(),
-                protocol_fee_amount: Uint128::zero(),
-                protocol_fee_contract: protocol_fee_contract.clone(),
+                protocol_fees: vec![],
             },
         )
         .unwrap_err();
@@ -96,8 +117,7 @@ mod tests {
             ExecuteMsg::Transfer {
                 reward_amount: Uint128::zero(),
                 reward_withdraw_contract: reward_withdraw_contract.clone(),
-                protocol_fee_amount: Uint128::zero(),
-                protocol_fee_contract: protocol_fee_contract.clone(),
+                protocol_fees: vec![],
             },
         )
         .unwrap();
@@ -110,12 +130,24 @@ mod tests {
             ExecuteMsg::Transfer {
                 reward_amount: Uint128::new(200),
                 reward_withdraw_contract: reward_withdraw_contract.clone(),
-                protocol_fee_amount: Uint128::new(2),
-                protocol_fee_contract: protocol_fee_contract.clone(),
+                protocol_fees: vec![
+                    ProtocolFee {
+                        recipient: protocol_fee_contract.clone(),
+                        amount: Uint128::new(2),
+                    },
+                    ProtocolFee {
+                        recipient: insurance_fund.clone(),
+                        amount: Uint128::new(1),
+                    },
+                    ProtocolFee {
+                        recipient: Addr::unchecked("keeper_pool"),
+                        amount: Uint128::zero(),
+                    },
+                ],
             },
         )
         .unwrap();
-        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.messages.len(), 3);
         assert_eq!(
             res.messages[0],
             SubMsg::new(BankMsg::Send {
@@ -130,6 +162,13 @@ mod tests {
                 amount: vec![Coin::new(2, "uluna")]
             })
         );
+        assert_eq!(
+            res.messages[2],
+            SubMsg::new(BankMsg::Send {
+                to_address: insurance_fund.to_string(),
+                amount: vec![Coin::new(1, "uluna")]
+            })
+        );
     }
 
     #[test]
@@ -239,6 +278,8 @@ mod tests {
 
         let initial_msg = ExecuteMsg::UpdateConfig {
             staking_contract: None,
+            max_spread: None,
+            clear_max_spread: None,
         };
         let err = execute(
             deps.as_mut(),
@@ -253,6 +294,7 @@ mod tests {
             manager: Addr::unchecked("creator"),
             reward_denom: "uluna".to_string(),
             staking_contract: Addr::unchecked("pools_addr"),
+            max_spread: None,
         };
         let config = CONFIG.load(deps.as_mut().storage).unwrap();
         assert_eq!(config, expected_config);
@@ -272,14 +314,30 @@ mod tests {
             manager: Addr::unchecked("creator"),
             reward_denom: "uluna".to_string(),
             staking_contract: Addr::unchecked("new_pools_addr"),
+            max_spread: Some(Decimal::percent(5)),
         };
 
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                staking_contract: None,
+                max_spread: Some(Decimal::percent(101)),
+                clear_max_spread: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxSpread {}));
+
         let res = execute(
             deps.as_mut(),
             env.clone(),
             mock_info("creator", &[]),
             ExecuteMsg::UpdateConfig {
                 staking_contract: Some("new_pools_addr".to_string()),
+                max_spread: Some(Decimal::percent(5)),
+                clear_max_spread: None,
             }
             .clone(),
         )
@@ -287,5 +345,909 @@ mod tests {
         assert!(res.messages.is_empty());
         let config = CONFIG.load(deps.as_mut().storage).unwrap();
         assert_eq!(config, expected_config);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                staking_contract: None,
+                max_spread: Some(Decimal::percent(5)),
+                clear_max_spread: Some(true),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxSpread {}));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                staking_contract: None,
+                max_spread: None,
+                clear_max_spread: Some(true),
+            },
+        )
+        .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(config.max_spread, None);
+    }
+
+    fn get_dex_route() -> SwapRoute {
+        SwapRoute::DexRouter {
+            router: Addr::unchecked("dex_router"),
+            operations: vec![
+                SwapOperation::AstroSwap {
+                    offer_asset_info: AssetInfo::NativeToken {
+                        denom: "uabc".to_string(),
+                    },
+                    ask_asset_info: AssetInfo::NativeToken {
+                        denom: "uusd".to_string(),
+                    },
+                },
+                SwapOperation::NativeSwap {
+                    offer_denom: "uusd".to_string(),
+                    ask_denom: "uluna".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_set_swap_route() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env, None);
+
+        /*
+           Unauthorized
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "uabc".to_string(),
+                route: Some(get_dex_route()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        /*
+           Route does not end in the reward denom
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "uabc".to_string(),
+                route: Some(SwapRoute::DexRouter {
+                    router: Addr::unchecked("dex_router"),
+                    operations: vec![SwapOperation::NativeSwap {
+                        offer_denom: "uabc".to_string(),
+                        ask_denom: "uusd".to_string(),
+                    }],
+                }),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapRoute {}));
+
+        /*
+           Reward denom cannot be routed
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "uluna".to_string(),
+                route: Some(SwapRoute::Skip {}),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapRoute {}));
+
+        /*
+           Successful
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "uabc".to_string(),
+                route: Some(get_dex_route()),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "ukrw".to_string(),
+                route: Some(SwapRoute::Skip {}),
+            },
+        )
+        .unwrap();
+        let res: SwapRoutesResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::SwapRoutes {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            res.swap_routes,
+            vec![
+                DenomSwapRoute {
+                    denom: "uabc".to_string(),
+                    route: get_dex_route()
+                },
+                DenomSwapRoute {
+                    denom: "ukrw".to_string(),
+                    route: SwapRoute::Skip {}
+                }
+            ]
+        );
+
+        /*
+           Removing a route
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "ukrw".to_string(),
+                route: None,
+            },
+        )
+        .unwrap();
+        let res: SwapRoutesResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::SwapRoutes {}).unwrap())
+                .unwrap();
+        assert_eq!(res.swap_routes.len(), 1);
+    }
+
+    #[test]
+    fn test_swap_with_routes() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env, None);
+        deps.querier.update_balance(
+            env.contract.address.clone(),
+            vec![
+                Coin::new(100, "uluna"),
+                Coin::new(30, "uabc"),
+                Coin::new(20, "ukrw"),
+            ],
+        );
+        for (denom, route) in [("uabc", get_dex_route()), ("ukrw", SwapRoute::Skip {})] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetSwapRoute {
+                    denom: denom.to_string(),
+                    route: Some(route),
+                },
+            )
+            .unwrap();
+        }
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // The mock querier has no oracle, so nothing goes through the native market.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        let operations = match get_dex_route() {
+            SwapRoute::DexRouter { operations, .. } => operations,
+            _ => panic!("expected a dex route"),
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "dex_router".to_string(),
+                msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                    operations,
+                    minimum_receive: None,
+                    to: None,
+                })
+                .unwrap(),
+                funds: vec![Coin::new(30, "uabc")]
+            })]
+        );
+    }
+
+    #[test]
+    fn test_swap_thresholds() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env, None);
+        deps.querier.update_balance(
+            env.contract.address.clone(),
+            vec![Coin::new(30, "uabc"), Coin::new(5, "udef")],
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::SetSwapThreshold {
+                denom: "uabc".to_string(),
+                min_amount: Some(Uint128::new(10_u128)),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        for denom in ["uabc", "udef"] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetSwapRoute {
+                    denom: denom.to_string(),
+                    route: Some(get_dex_route()),
+                },
+            )
+            .unwrap();
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetSwapThreshold {
+                    denom: denom.to_string(),
+                    min_amount: Some(Uint128::new(10_u128)),
+                },
+            )
+            .unwrap();
+        }
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::SwapThresholds {}).unwrap();
+        let value: SwapThresholdsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.swap_thresholds,
+            vec![
+                DenomSwapThreshold {
+                    denom: "uabc".to_string(),
+                    min_amount: Uint128::new(10_u128),
+                },
+                DenomSwapThreshold {
+                    denom: "udef".to_string(),
+                    min_amount: Uint128::new(10_u128),
+                },
+            ]
+        );
+
+        // udef is below its threshold and is held until the next swap.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(res.attributes.contains(&attr("below_threshold", "udef")));
+        assert!(res.attributes.contains(&attr("above_max_spread", "")));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapThreshold {
+                denom: "udef".to_string(),
+                min_amount: None,
+            },
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::SwapThresholds {}).unwrap();
+        let value: SwapThresholdsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.swap_thresholds.len(), 1);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert!(res.attributes.contains(&attr("below_threshold", "")));
+    }
+
+    #[cfg(feature = "terra")]
+    #[test]
+    fn test_swap_max_spread() {
+        let mut deps = custom_mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            InstantiateMsg {
+                staking_contract: "pools_addr".to_string(),
+                reward_denom: Some("uluna".to_string()),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapRoute {
+                denom: "uabc".to_string(),
+                route: Some(get_dex_route()),
+            },
+        )
+        .unwrap();
+        deps.querier.update_balance(
+            env.contract.address.clone(),
+            vec![
+                Coin::new(30, "uabc"),
+                Coin::new(1000, "ukrw"),
+                Coin::new(1000, "usdr"),
+            ],
+        );
+
+        // The oracle quotes every denom at 10 per uluna, so the expected output is a tenth of
+        // the offer. The router and the ukrw market are within 10% of it, usdr is not.
+        let oracle_rates = ["uabc", "uusd", "ukrw", "usdr"]
+            .map(|denom| (denom, Decimal::from_ratio(10_u128, 1_u128)));
+        deps.querier.update_exchange_rates(&oracle_rates);
+        deps.querier.update_swap_rates(&[
+            ("ukrw", Decimal::from_ratio(95_u128, 1000_u128)),
+            ("usdr", Decimal::from_ratio(80_u128, 1000_u128)),
+        ]);
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(1_u128, 10_u128))]);
+
+        let operations = match get_dex_route() {
+            SwapRoute::DexRouter { operations, .. } => operations,
+            _ => panic!("expected a dex route"),
+        };
+        let dex_swap_msg = |minimum_receive: Option<Uint128>| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "dex_router".to_string(),
+                msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                    operations: operations.clone(),
+                    minimum_receive,
+                    to: None,
+                })
+                .unwrap(),
+                funds: vec![Coin::new(30, "uabc")],
+            })
+        };
+        let market_swap_msg =
+            |denom: &str| SubMsg::new(create_swap_msg(Coin::new(1000, denom), "uluna".to_string()));
+
+        // Without a max spread every denom is swapped with no minimum.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                dex_swap_msg(None),
+                market_swap_msg("ukrw"),
+                market_swap_msg("usdr")
+            ]
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                staking_contract: None,
+                max_spread: Some(Decimal::percent(10)),
+                clear_max_spread: None,
+            },
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                dex_swap_msg(Some(Uint128::new(2_u128))),
+                market_swap_msg("ukrw")
+            ]
+        );
+        assert!(res.attributes.contains(&attr("above_max_spread", "usdr")));
+
+        // A router quote below the minimum holds the denom back as well.
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(1_u128, 20_u128))]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![market_swap_msg("ukrw")]);
+        assert!(res
+            .attributes
+            .contains(&attr("above_max_spread", "uabc,usdr")));
+
+        // Without an oracle quote the router swap is floored at the router's own quote less the
+        // max spread, and a denom the oracle cannot price is held back from the market.
+        deps.querier.update_exchange_rates(&[
+            ("uusd", Decimal::from_ratio(10_u128, 1_u128)),
+            ("ukrw", Decimal::from_ratio(10_u128, 1_u128)),
+            ("usdr", Decimal::zero()),
+        ]);
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(1_u128, 5_u128))]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                dex_swap_msg(Some(Uint128::new(5_u128))),
+                market_swap_msg("ukrw")
+            ]
+        );
+        assert!(res.attributes.contains(&attr("above_max_spread", "usdr")));
+    }
+
+    #[cfg(feature = "terra")]
+    #[test]
+    fn test_reward_denoms() {
+        let mut deps = custom_mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            InstantiateMsg {
+                staking_contract: "pools_addr".to_string(),
+                reward_denom: Some("uluna".to_string()),
+            },
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::RewardDenoms {}).unwrap();
+        let value: RewardDenomsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.reward_denoms.len(), 21);
+        assert!(!value.reward_denoms.contains(&"uxyz".to_string()));
+
+        deps.querier
+            .update_balance(env.contract.address.clone(), vec![Coin::new(1000, "uxyz")]);
+        deps.querier
+            .update_exchange_rates(&[("uxyz", Decimal::from_ratio(10_u128, 1_u128))]);
+
+        // uxyz is not whitelisted, so it is not swapped.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::AddRewardDenom {
+                denom: "uxyz".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::AddRewardDenom {
+                denom: "uunlisted".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RewardDenomNotListed {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::AddRewardDenom {
+                denom: "ukrw".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RewardDenomAlreadyAdded {}));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::AddRewardDenom {
+                denom: "uxyz".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.attributes, vec![attr("new_reward_denom", "uxyz")]);
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::RewardDenoms {}).unwrap();
+        let value: RewardDenomsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.reward_denoms.len(), 22);
+        assert_eq!(value.reward_denoms.last(), Some(&"uxyz".to_string()));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(create_swap_msg(
+                Coin::new(1000, "uxyz"),
+                "uluna".to_string()
+            ))]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::RemoveRewardDenom {
+                denom: "uxyz".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::RemoveRewardDenom {
+                denom: "uxyz".to_string(),
+            },
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::RewardDenoms {}).unwrap();
+        let value: RewardDenomsResponse = from_binary(&res).unwrap();
+        assert_eq!(value.reward_denoms.len(), 21);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::RemoveRewardDenom {
+                denom: "uxyz".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::RewardDenomNotAdded {}));
+    }
+
+    #[cfg(feature = "terra")]
+    #[test]
+    fn test_cw20_reward_tokens() {
+        let mut deps = custom_mock_dependencies();
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            InstantiateMsg {
+                staking_contract: "pools_addr".to_string(),
+                reward_denom: Some("uluna".to_string()),
+            },
+        )
+        .unwrap();
+        deps.querier.update_cw20_balances(&[
+            ("astro_token", Uint128::new(500_u128)),
+            ("mine_token", Uint128::new(300_u128)),
+            ("dust_token", Uint128::new(5_u128)),
+        ]);
+
+        let swap_operations = vec![SwapOperation::AstroSwap {
+            offer_asset_info: AssetInfo::Token {
+                contract_addr: Addr::unchecked("astro_token"),
+            },
+            ask_asset_info: AssetInfo::NativeToken {
+                denom: "uluna".to_string(),
+            },
+        }];
+        let swap_action = Cw20RewardAction::Swap {
+            router: Addr::unchecked("dex_router"),
+            operations: swap_operations.clone(),
+        };
+        let forward_action = Cw20RewardAction::Forward {
+            recipient: Addr::unchecked("incentives_addr"),
+        };
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::SetCw20RewardToken {
+                token: "astro_token".to_string(),
+                action: Some(swap_action.clone()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetCw20RewardToken {
+                token: "astro_token".to_string(),
+                action: Some(Cw20RewardAction::Swap {
+                    router: Addr::unchecked("dex_router"),
+                    operations: vec![],
+                }),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapRoute {}));
+
+        for (token, action) in [
+            ("astro_token", swap_action.clone()),
+            ("mine_token", forward_action.clone()),
+            ("dust_token", forward_action.clone()),
+        ] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetCw20RewardToken {
+                    token: token.to_string(),
+                    action: Some(action),
+                },
+            )
+            .unwrap();
+        }
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetSwapThreshold {
+                denom: "dust_token".to_string(),
+                min_amount: Some(Uint128::new(10_u128)),
+            },
+        )
+        .unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Cw20RewardTokens {}).unwrap();
+        let value: Cw20RewardTokensResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.cw20_reward_tokens,
+            vec![
+                Cw20RewardToken {
+                    token: Addr::unchecked("astro_token"),
+                    action: swap_action,
+                },
+                Cw20RewardToken {
+                    token: Addr::unchecked("dust_token"),
+                    action: forward_action.clone(),
+                },
+                Cw20RewardToken {
+                    token: Addr::unchecked("mine_token"),
+                    action: forward_action,
+                },
+            ]
+        );
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Cw20RewardBalances {}).unwrap();
+        let value: Cw20RewardBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            value.balances,
+            vec![
+                Cw20Coin {
+                    address: "astro_token".to_string(),
+                    amount: Uint128::new(500_u128),
+                },
+                Cw20Coin {
+                    address: "dust_token".to_string(),
+                    amount: Uint128::new(5_u128),
+                },
+                Cw20Coin {
+                    address: "mine_token".to_string(),
+                    amount: Uint128::new(300_u128),
+                },
+            ]
+        );
+
+        // Only registered tokens can deposit rewards through the hook.
+        let hook_msg = Cw20ReceiveMsg {
+            sender: "incentives_addr".to_string(),
+            amount: Uint128::new(100_u128),
+            msg: to_binary(&Cw20HookMsg::DepositRewards {}).unwrap(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other_token", &[]),
+            ExecuteMsg::Receive(hook_msg.clone()),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Cw20TokenNotRegistered {}));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("astro_token", &[]),
+            ExecuteMsg::Receive(hook_msg),
+        )
+        .unwrap();
+        assert!(res.attributes.contains(&attr("token", "astro_token")));
+        assert!(res.attributes.contains(&attr("amount", "100")));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "astro_token".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Send {
+                        contract: "dex_router".to_string(),
+                        amount: Uint128::new(500_u128),
+                        msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                            operations: swap_operations.clone(),
+                            minimum_receive: None,
+                            to: None,
+                        })
+                        .unwrap(),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "mine_token".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: "incentives_addr".to_string(),
+                        amount: Uint128::new(300_u128),
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+            ]
+        );
+        assert!(res
+            .attributes
+            .contains(&attr("below_threshold", "dust_token")));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetCw20RewardToken {
+                token: "mine_token".to_string(),
+                action: None,
+            },
+        )
+        .unwrap();
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::Cw20RewardTokens {}).unwrap();
+        let value: Cw20RewardTokensResponse = from_binary(&res).unwrap();
+        assert_eq!(value.cw20_reward_tokens.len(), 2);
+
+        // With a max spread, swaps carry a minimum receive and quotes below it are held back.
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                staking_contract: None,
+                max_spread: Some(Decimal::percent(10)),
+                clear_max_spread: None,
+            },
+        )
+        .unwrap();
+        let astro_swap_msg = |minimum_receive: Option<Uint128>| {
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "astro_token".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: "dex_router".to_string(),
+                    amount: Uint128::new(500_u128),
+                    msg: to_binary(&RouterExecuteMsg::ExecuteSwapOperations {
+                        operations: swap_operations.clone(),
+                        minimum_receive,
+                        to: None,
+                    })
+                    .unwrap(),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        };
+
+        // No oracle rate for the token, so the minimum is the router quote less the max spread.
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(1_u128, 10_u128))]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![astro_swap_msg(Some(Uint128::new(45_u128)))]
+        );
+
+        // The oracle expects 50 for 500 astro_token, so the minimum is 45.
+        deps.querier
+            .update_exchange_rates(&[("astro_token", Decimal::from_ratio(10_u128, 1_u128))]);
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(95_u128, 1000_u128))]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![astro_swap_msg(Some(Uint128::new(45_u128)))]
+        );
+
+        deps.querier
+            .update_router_rates(&[("dex_router", Decimal::from_ratio(80_u128, 1000_u128))]);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("pools_addr", &[]),
+            ExecuteMsg::Swap {},
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert!(res
+            .attributes
+            .contains(&attr("above_max_spread", "astro_token")));
     }
 }
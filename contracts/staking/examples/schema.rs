@@ -15,7 +15,10 @@ fn main() {
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
     export_schema(&schema_for!(Cw20HookMsg), &out_dir);
+    export_schema(&schema_for!(Cw721HookMsg), &out_dir);
     export_schema(&schema_for!(MerkleAirdropMsg), &out_dir);
+    export_schema(&schema_for!(MerkleAirdropWithoutStageMsg), &out_dir);
+    export_schema(&schema_for!(PlainAirdropMsg), &out_dir);
     export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(QueryConfigResponse), &out_dir);
     export_schema(&schema_for!(QueryStateResponse), &out_dir);
@@ -24,4 +27,16 @@ fn main() {
     export_schema(&schema_for!(GetFundsClaimRecord), &out_dir);
     export_schema(&schema_for!(UserInfoResponse), &out_dir);
     export_schema(&schema_for!(TmpManagerStoreResponse), &out_dir);
+    export_schema(&schema_for!(SharesInvariantResponse), &out_dir);
+    export_schema(&schema_for!(RoundingDustResponse), &out_dir);
+    export_schema(&schema_for!(ReferralCodeResponse), &out_dir);
+    export_schema(&schema_for!(AirdropsClaimedResponse), &out_dir);
+    export_schema(&schema_for!(AirdropClaimHistoryResponse), &out_dir);
+    export_schema(&schema_for!(UnbondingNftResponse), &out_dir);
+    export_schema(&schema_for!(AutoClaimResponse), &out_dir);
+    export_schema(&schema_for!(UnwindStateResponse), &out_dir);
+    export_schema(&schema_for!(CircuitBreakerResponse), &out_dir);
+    export_schema(&schema_for!(PauseInfoResponse), &out_dir);
+    export_schema(&schema_for!(AdminActionsResponse), &out_dir);
+    export_schema(&schema_for!(ComputeUnwindClaimResponse), &out_dir);
 }
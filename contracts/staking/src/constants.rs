@@ -1,4 +1,4 @@
-use cosmwasm_std::Decimal;
+use cosmwasm_std::{Decimal, Uint128};
 
 pub fn get_deposit_fee_cap() -> Decimal {
     Decimal::from_ratio(5_u128, 100_u128)
@@ -11,3 +11,15 @@ pub fn get_withdraw_fee_cap() -> Decimal {
 pub fn get_reward_fee_cap() -> Decimal {
     Decimal::from_ratio(10_u128, 100_u128)
 }
+
+pub fn get_locked_shares_on_first_deposit() -> Uint128 {
+    Uint128::new(1000_u128)
+}
+
+pub fn get_circuit_breaker_window() -> u64 {
+    24 * 3600
+}
+
+pub fn get_max_referral_code_length() -> usize {
+    32
+}
@@ -1,37 +1,54 @@
-use crate::constants::{get_deposit_fee_cap, get_reward_fee_cap, get_withdraw_fee_cap};
+use crate::constants::{
+    get_circuit_breaker_window, get_deposit_fee_cap, get_locked_shares_on_first_deposit,
+    get_reward_fee_cap, get_withdraw_fee_cap,
+};
 use crate::helpers::{
-    burn_minted_tokens, calculate_exchange_rate, create_mint_message,
+    add_change, burn_minted_tokens, calculate_exchange_rate, create_mint_message,
     create_new_undelegation_batch, decrease_tracked_stake, get_active_validators_sorted_by_stake,
-    get_airdrop_contracts, get_total_token_supply, get_user_balance, get_validator_for_deposit,
-    increase_tracked_stake, validate, Verify,
+    get_airdrop_balance, get_airdrop_claim_msg, get_airdrop_contracts, get_airdrop_transfer_msg,
+    get_config_diff, get_deposit_allocations, get_fee_shares, get_operation_controls_diff,
+    get_pending_rewards, get_protocol_fee_msgs, get_rounding_dust, get_total_token_supply,
+    get_undelegation_allocations, get_user_balance, get_validator_for_deposit,
+    increase_tracked_stake, record_admin_action, split_protocol_fee, update_pause_info, validate,
+    validate_protocol_fee_recipients, validate_referral_code, Verify,
 };
 use crate::msg::{
-    Cw20HookMsg, ExecuteMsg, GetFundsClaimRecord, GetFundsDepositRecord, GetValMetaResponse,
-    InstantiateMsg, MerkleAirdropMsg, MigrateMsg, QueryBatchUndelegationResponse,
-    QueryConfigResponse, QueryMsg, QueryStateResponse, TmpManagerStoreResponse, UserInfoResponse,
-    UserQueryInfo,
+    AdminActionsResponse, AirdropClaimHistoryResponse, AirdropsClaimedResponse, AutoClaimResponse,
+    CircuitBreakerResponse, ComputeUnwindClaimResponse, Cw20HookMsg, Cw721ExecuteMsg, Cw721HookMsg,
+    Cw721MintMsg, Cw721ReceiveMsg, ExecuteMsg, GetFundsClaimRecord, GetFundsDepositRecord,
+    GetValMetaResponse, InstantiateMsg, MigrateMsg, PauseInfoResponse,
+    QueryBatchUndelegationResponse, QueryConfigResponse, QueryMsg, QueryStateResponse,
+    ReferralCodeResponse, RoundingDustResponse, SharesInvariantResponse, TmpManagerStoreResponse,
+    UnbondingNftResponse, UnwindStateResponse, UserInfoResponse, UserQueryInfo,
 };
 use crate::state::{
-    AirdropRate, Config, ConfigUpdateRequest, OperationControls, OperationControlsUpdateRequest,
-    State, TmpManagerStore, UndelegationInfo, VMeta, BATCH_UNDELEGATION_REGISTRY, CONFIG,
-    OPERATION_CONTROLS, STATE, TMP_MANAGER_STORE, USERS, VALIDATOR_META,
+    AdminAction, AirdropRate, CircuitBreakerPolicy, CircuitBreakerState, CircuitBreakerTrip,
+    ClaimedAirdropStage, Config, ConfigUpdateRequest, FeeDestination, FeeDestinationPolicy,
+    LegacyConfig, OperationControls, OperationControlsUpdateRequest, PendingAirdropClaim,
+    ProtocolFeeRecipient, ReferralInfo, RewardSnipingPolicy, SplitPolicy, State, TmpManagerStore,
+    UndelegationInfo, UnwindInfo, UnwindPhase, UnwindStep, VMeta, ADMIN_ACTIONS, AIRDROPS_CLAIMED,
+    AIRDROP_CLAIM_HISTORY, AUTO_CLAIM_QUEUE, AUTO_CLAIM_USERS, BATCH_UNDELEGATION_REGISTRY,
+    CIRCUIT_BREAKER, CONFIG, LEGACY_CONFIG, LEGACY_STATE, OPERATION_CONTROLS, PAUSE_INFO,
+    PENDING_AIRDROP_CLAIMS, REFERRAL_CODES, REFERRED_SHARES, STATE, TMP_MANAGER_STORE,
+    UNBONDING_NFTS, UNBONDING_NFT_COUNT, UNWIND, USERS, VALIDATOR_META,
 };
 use crate::ContractError;
 use airdrops_registry::msg::GetAirdropContractsResponse;
+use cosmwasm_bignumber::Decimal256;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    from_binary, to_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, DistributionMsg,
-    Env, MessageInfo, Order, Response, StakingMsg, StdError, StdResult, Storage, SubMsg, Uint128,
-    WasmMsg,
+    attr, from_binary, to_binary, Addr, BankMsg, Binary, Coin, ContractResult, Decimal, Deps,
+    DepsMut, DistributionMsg, Env, Event, MessageInfo, Order, Reply, Response, StakingMsg,
+    StdError, StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
-use cw20::Cw20ReceiveMsg;
-use cw20_base::msg::ExecuteMsg as Cw20ExecuteMsg;
-use cw_storage_plus::{Bound, U64Key};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw_storage_plus::{Bound, U64Key, U8Key};
 use reward::msg::ExecuteMsg as RewardExecuteMsg;
 use stader_utils::coin_utils::{
-    decimal_division_in_256, decimal_multiplication_in_256, get_decimal_from_uint128,
-    multiply_u128_with_decimal, uint128_from_decimal,
+    decimal256_from_ratio, decimal_multiplication_in_256, decimal_summation_in_256,
+    divide_uint128_by_decimal256, get_decimal256_from_uint128, get_decimal_from_uint128,
+    multiply_uint128_with_decimal256, uint128_from_decimal, Rounding,
 };
 use std::ops::{Deref, Mul};
 
@@ -49,9 +66,14 @@ pub fn instantiate(
         return Err(ContractError::ProtocolFeeAboveLimit {});
     }
 
+    let vault_denom = match msg.vault_denom {
+        Some(vault_denom) => vault_denom,
+        None => deps.querier.query_bonded_denom()?,
+    };
+
     let config = Config {
         manager: info.sender.clone(),
-        vault_denom: "uluna".to_string(),
+        vault_denom,
         min_deposit: msg.min_deposit,
         max_deposit: msg.max_deposit,
         active: true,
@@ -59,29 +81,62 @@ pub fn instantiate(
         airdrop_registry_contract: deps
             .api
             .addr_validate(msg.airdrops_registry_contract.as_str())?,
+        unbonding_nft_contract: None,
         airdrop_withdrawal_contract: deps
             .api
             .addr_validate(msg.airdrop_withdrawal_contract.as_str())?,
         reward_contract: deps.api.addr_validate(msg.reward_contract.as_str())?,
         cw20_token_contract: Addr::unchecked("0"),
 
-        protocol_fee_contract: deps.api.addr_validate(msg.protocol_fee_contract.as_str())?,
+        protocol_fee_recipients: validate_protocol_fee_recipients(
+            deps.api,
+            msg.protocol_fee_recipients,
+        )?,
         protocol_reward_fee: msg.protocol_reward_fee,
         protocol_deposit_fee: msg.protocol_deposit_fee,
         protocol_withdraw_fee: msg.protocol_withdraw_fee,
+        reward_fee_as_shares: false,
+        fee_destination_policy: FeeDestinationPolicy {
+            deposit_fee: FeeDestination::ProtocolFeeRecipients,
+            withdraw_fee: FeeDestination::ProtocolFeeRecipients,
+        },
+        referral_fee_share: Decimal::zero(),
 
         undelegation_cooldown: msg.undelegation_cooldown,
         swap_cooldown: msg.swap_cooldown,
         unbonding_period: msg.unbonding_period,
         reinvest_cooldown: msg.reinvest_cooldown,
+
+        split_policy: SplitPolicy {
+            deposit_threshold: None,
+            undelegation_threshold: None,
+        },
+
+        epoch_delegation: false,
+        delegate_pending_cooldown: 0,
+
+        reward_sniping_policy: RewardSnipingPolicy {
+            include_pending_rewards: false,
+            reinvest_window: 0,
+            reinvest_window_fee: None,
+        },
+
+        guardian: None,
+        circuit_breaker_policy: CircuitBreakerPolicy {
+            max_rate_drop_per_call: None,
+            max_rate_rise_per_call: None,
+            max_rate_drop_per_day: None,
+            max_rate_rise_per_day: None,
+        },
     };
 
     CONFIG.save(deps.storage, &config)?;
 
-    let initial_er = Decimal::one();
+    let initial_er = Decimal256::one();
 
     let state = State {
         total_staked: Uint128::zero(),
+        total_shares: Uint128::zero(),
         exchange_rate: initial_er,
         last_reconciled_batch_id: 0,
         current_undelegation_batch_id: 0,
@@ -90,6 +145,11 @@ pub fn instantiate(
         last_reinvest_time: env.block.time.minus_seconds(msg.reinvest_cooldown),
         validators: vec![],
         reconciled_funds_to_withdraw: Uint128::zero(),
+        pending_delegation: Uint128::zero(),
+        last_delegate_pending_time: env.block.time,
+        rounding_dust: Decimal256::zero(),
+        fee_shares_minted: Uint128::zero(),
+        unclaimed_referral_fees: Uint128::zero(),
     };
     STATE.save(deps.storage, &state)?;
 
@@ -104,6 +164,7 @@ pub fn instantiate(
         redeem_rewards_paused: false,
         swap_paused: false,
         reimburse_slashing_paused: false,
+        delegate_pending_paused: false,
     };
     OPERATION_CONTROLS.save(deps.storage, &operation_controls)?;
 
@@ -118,26 +179,127 @@ pub fn instantiate(
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
-    OPERATION_CONTROLS.save(
-        deps.storage,
-        &OperationControls {
-            deposit_paused: false,
-            queue_undelegate_paused: false,
-            undelegate_paused: false,
-            withdraw_paused: false,
-            reinvest_paused: false,
-            reconcile_paused: false,
-            claim_airdrops_paused: false,
-            redeem_rewards_paused: false,
-            swap_paused: false,
-            reimburse_slashing_paused: false,
-        },
-    )?;
+pub fn migrate(deps: DepsMut, env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    // Pauses are left untouched, so a tripped circuit breaker or an unwind stays in force and the
+    // pause records and admin log still describe every flag.
+    if OPERATION_CONTROLS.may_load(deps.storage)?.is_none() {
+        OPERATION_CONTROLS.save(
+            deps.storage,
+            &OperationControls {
+                deposit_paused: false,
+                queue_undelegate_paused: false,
+                undelegate_paused: false,
+                withdraw_paused: false,
+                reinvest_paused: false,
+                reconcile_paused: false,
+                claim_airdrops_paused: false,
+                redeem_rewards_paused: false,
+                swap_paused: false,
+                reimburse_slashing_paused: false,
+                delegate_pending_paused: false,
+            },
+        )?;
+    }
+
+    // Config and State still in the shape of the first release get every later setting at its instantiate default.
+    if CONFIG.load(deps.storage).is_err() {
+        let legacy_config = LEGACY_CONFIG.load(deps.storage)?;
+        CONFIG.save(deps.storage, &get_migrated_config(legacy_config))?;
+    }
+
+    if STATE.load(deps.storage).is_err() {
+        let legacy_state = LEGACY_STATE.load(deps.storage)?;
+        let config = CONFIG.load(deps.storage)?;
+
+        // Seed the internal share accounting from the token contract.
+        let total_shares = if config.cw20_token_contract.ne(&Addr::unchecked("0")) {
+            get_total_token_supply(deps.querier, config.cw20_token_contract)?
+        } else {
+            Uint128::zero()
+        };
+        STATE.save(
+            deps.storage,
+            &State {
+                total_staked: legacy_state.total_staked,
+                total_shares,
+                exchange_rate: Decimal256::from(legacy_state.exchange_rate),
+                last_reconciled_batch_id: legacy_state.last_reconciled_batch_id,
+                current_undelegation_batch_id: legacy_state.current_undelegation_batch_id,
+                last_undelegation_time: legacy_state.last_undelegation_time,
+                last_swap_time: legacy_state.last_swap_time,
+                last_reinvest_time: legacy_state.last_reinvest_time,
+                validators: legacy_state.validators,
+                reconciled_funds_to_withdraw: legacy_state.reconciled_funds_to_withdraw,
+                pending_delegation: Uint128::zero(),
+                last_delegate_pending_time: env.block.time,
+                rounding_dust: Decimal256::zero(),
+                fee_shares_minted: Uint128::zero(),
+                unclaimed_referral_fees: Uint128::zero(),
+            },
+        )?;
+    }
 
     Ok(Response::default())
 }
 
+fn get_migrated_config(legacy_config: LegacyConfig) -> Config {
+    Config {
+        manager: legacy_config.manager,
+        vault_denom: legacy_config.vault_denom,
+        min_deposit: legacy_config.min_deposit,
+        max_deposit: legacy_config.max_deposit,
+        active: legacy_config.active,
+
+        reward_contract: legacy_config.reward_contract,
+        cw20_token_contract: legacy_config.cw20_token_contract,
+        airdrop_registry_contract: legacy_config.airdrop_registry_contract,
+        airdrop_withdrawal_contract: legacy_config.airdrop_withdrawal_contract,
+        unbonding_nft_contract: None,
+
+        // The single fee contract keeps receiving every protocol fee.
+        protocol_fee_recipients: vec![ProtocolFeeRecipient {
+            recipient: legacy_config.protocol_fee_contract,
+            weight: Decimal::one(),
+        }],
+        protocol_reward_fee: legacy_config.protocol_reward_fee,
+        protocol_deposit_fee: legacy_config.protocol_deposit_fee,
+        protocol_withdraw_fee: legacy_config.protocol_withdraw_fee,
+        reward_fee_as_shares: false,
+        fee_destination_policy: FeeDestinationPolicy {
+            deposit_fee: FeeDestination::ProtocolFeeRecipients,
+            withdraw_fee: FeeDestination::ProtocolFeeRecipients,
+        },
+        referral_fee_share: Decimal::zero(),
+
+        unbonding_period: legacy_config.unbonding_period,
+        undelegation_cooldown: legacy_config.undelegation_cooldown,
+        swap_cooldown: legacy_config.swap_cooldown,
+        reinvest_cooldown: legacy_config.reinvest_cooldown,
+
+        split_policy: SplitPolicy {
+            deposit_threshold: None,
+            undelegation_threshold: None,
+        },
+
+        epoch_delegation: false,
+        delegate_pending_cooldown: 0,
+
+        reward_sniping_policy: RewardSnipingPolicy {
+            include_pending_rewards: false,
+            reinvest_window: 0,
+            reinvest_window_fee: None,
+        },
+
+        guardian: None,
+        circuit_breaker_policy: CircuitBreakerPolicy {
+            max_rate_drop_per_call: None,
+            max_rate_rise_per_call: None,
+            max_rate_drop_per_day: None,
+            max_rate_rise_per_day: None,
+        },
+    }
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
@@ -156,17 +318,26 @@ pub fn execute(
             val_addr,
             redel_addr,
         } => rebalance_pool(deps, info, env, amount, val_addr, redel_addr),
-        ExecuteMsg::Deposit {} => deposit(deps, info, env),
+        ExecuteMsg::Deposit { referral_code } => deposit(deps, info, env, referral_code),
+        ExecuteMsg::DelegatePending {} => delegate_pending(deps, info, env),
         ExecuteMsg::RedeemRewards {} => redeem_rewards(deps, info, env),
         ExecuteMsg::Swap {} => swap_rewards(deps, info, env),
         ExecuteMsg::Reinvest {} => reinvest(deps, info, env),
         ExecuteMsg::ReimburseSlashing { val_addr } => reimburse_slashing(deps, info, env, val_addr),
         ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
+        ExecuteMsg::ReceiveNft(msg) => receive_nft(deps, env, info, msg),
         ExecuteMsg::Undelegate {} => undelegate_stake(deps, info, env),
         ExecuteMsg::ReconcileFunds {} => reconcile_funds(deps, info, env),
         ExecuteMsg::WithdrawFundsToWallet { batch_id } => {
             withdraw_funds_to_wallet(deps, info, env, batch_id)
         }
+        ExecuteMsg::WithdrawFor { user, batch_id } => withdraw_for(deps, info, env, user, batch_id),
+        ExecuteMsg::PushBatchPayouts { batch_id, limit } => {
+            push_batch_payouts(deps, info, env, batch_id, limit)
+        }
+        ExecuteMsg::SetAutoClaim { enabled } => set_auto_claim(deps, info, env, enabled),
+        ExecuteMsg::EmergencyUnwind {} => emergency_unwind(deps, info, env),
+        ExecuteMsg::ResumeFromCircuitBreaker {} => resume_from_circuit_breaker(deps, info, env),
         ExecuteMsg::ClaimAirdrops { rates } => claim_airdrops(deps, info, env, rates),
         ExecuteMsg::UpdateConfig { config_request } => {
             update_config(deps, info, env, config_request)
@@ -176,7 +347,115 @@ pub fn execute(
         } => update_operation_flags(deps, info, env, operation_controls_update_request),
         ExecuteMsg::SetManager { manager } => set_manager(deps, info, env, manager),
         ExecuteMsg::AcceptManager {} => accept_manager(deps, info, env),
+        ExecuteMsg::SetProtocolFeeRecipients { recipients } => {
+            set_protocol_fee_recipients(deps, info, env, recipients)
+        }
+        ExecuteMsg::RegisterReferralCode { code } => register_referral_code(deps, info, env, code),
+        ExecuteMsg::ClaimReferralFees { code } => claim_referral_fees(deps, info, env, code),
+    }
+}
+
+// Any address can register an unused code. The registering address owns the code's fees.
+pub fn register_referral_code(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    code: String,
+) -> Result<Response, ContractError> {
+    validate_referral_code(code.as_str())?;
+    if REFERRAL_CODES.has(deps.storage, code.as_str()) {
+        return Err(ContractError::ReferralCodeAlreadyRegistered {});
+    }
+
+    REFERRAL_CODES.save(
+        deps.storage,
+        code.as_str(),
+        &ReferralInfo {
+            owner: info.sender.clone(),
+            total_deposits: Uint128::zero(),
+            outstanding_shares: Uint128::zero(),
+            unclaimed_fees: Uint128::zero(),
+            claimed_fees: Uint128::zero(),
+        },
+    )?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "register_referral_code"),
+        attr("code", code),
+        attr("owner", info.sender),
+    ]))
+}
+
+pub fn claim_referral_fees(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    code: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut referral_info = REFERRAL_CODES
+        .may_load(deps.storage, code.as_str())?
+        .ok_or(ContractError::ReferralCodeNotFound {})?;
+    if info.sender.ne(&referral_info.owner) {
+        return Err(ContractError::Unauthorized {});
     }
+
+    let fees = referral_info.unclaimed_fees;
+    if fees.is_zero() {
+        return Err(ContractError::ZeroAmount {});
+    }
+    referral_info.unclaimed_fees = Uint128::zero();
+    referral_info.claimed_fees = referral_info.claimed_fees.checked_add(fees).unwrap();
+    REFERRAL_CODES.save(deps.storage, code.as_str(), &referral_info)?;
+    STATE.update(deps.storage, |mut state| -> StdResult<_> {
+        state.unclaimed_referral_fees = state
+            .unclaimed_referral_fees
+            .checked_sub(fees)
+            .unwrap_or(Uint128::zero());
+        Ok(state)
+    })?;
+
+    Ok(Response::new()
+        .add_message(BankMsg::Send {
+            to_address: referral_info.owner.to_string(),
+            amount: vec![Coin::new(fees.u128(), config.vault_denom)],
+        })
+        .add_attributes(vec![
+            attr("action", "claim_referral_fees"),
+            attr("code", code),
+            attr("amount", fees),
+        ]))
+}
+
+pub fn set_protocol_fee_recipients(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    recipients: Vec<ProtocolFeeRecipient>,
+) -> Result<Response, ContractError> {
+    let mut config = CONFIG.load(deps.storage)?;
+    validate(&config, &info, &env, vec![Verify::SenderManager])?;
+    let old_config = config.clone();
+
+    config.protocol_fee_recipients = validate_protocol_fee_recipients(deps.api, recipients)?;
+    CONFIG.save(deps.storage, &config)?;
+    record_admin_action(
+        deps.storage,
+        &env,
+        &info.sender,
+        "set_protocol_fee_recipients",
+        get_config_diff(&old_config, &config)?,
+    )?;
+
+    let event = Event::new("protocol_fee_recipients_updated").add_attributes(
+        config.protocol_fee_recipients.iter().map(|recipient| {
+            attr(
+                recipient.recipient.to_string(),
+                recipient.weight.to_string(),
+            )
+        }),
+    );
+    Ok(Response::new().add_event(event))
 }
 
 pub fn set_manager(
@@ -188,12 +467,20 @@ pub fn set_manager(
     let config = CONFIG.load(deps.storage)?;
     validate(&config, &info, &env, vec![Verify::SenderManager])?;
 
-    TMP_MANAGER_STORE.save(
-        deps.storage,
-        &TmpManagerStore {
-            manager: manager.to_lowercase(),
-        },
+    let old_tmp_manager_store = TMP_MANAGER_STORE.may_load(deps.storage)?;
+    let tmp_manager_store = TmpManagerStore {
+        manager: manager.to_lowercase(),
+    };
+    TMP_MANAGER_STORE.save(deps.storage, &tmp_manager_store)?;
+
+    let mut diff = vec![];
+    add_change(
+        &mut diff,
+        "pending_manager",
+        &old_tmp_manager_store.map(|x| x.manager),
+        &Some(tmp_manager_store.manager),
     )?;
+    record_admin_action(deps.storage, &env, &info.sender, "set_manager", diff)?;
 
     Ok(Response::default())
 }
@@ -201,9 +488,10 @@ pub fn set_manager(
 pub fn accept_manager(
     deps: DepsMut,
     info: MessageInfo,
-    _env: Env,
+    env: Env,
 ) -> Result<Response, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
+    let old_config = config.clone();
 
     let tmp_manager_store =
         if let Some(tmp_manager_store) = TMP_MANAGER_STORE.may_load(deps.storage)? {
@@ -221,6 +509,13 @@ pub fn accept_manager(
     TMP_MANAGER_STORE.remove(deps.storage);
 
     CONFIG.save(deps.storage, &config)?;
+    record_admin_action(
+        deps.storage,
+        &env,
+        &info.sender,
+        "accept_manager",
+        get_config_diff(&old_config, &config)?,
+    )?;
 
     Ok(Response::default())
 }
@@ -234,6 +529,7 @@ pub fn update_operation_flags(
     let config = CONFIG.load(deps.storage)?;
     validate(&config, &info, &env, vec![Verify::SenderManager])?;
     let mut operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    let old_operation_controls = operation_controls.clone();
 
     operation_controls.deposit_paused = operation_controls_update_request
         .deposit_paused
@@ -265,8 +561,36 @@ pub fn update_operation_flags(
     operation_controls.reimburse_slashing_paused = operation_controls_update_request
         .reimburse_slashing_paused
         .unwrap_or(operation_controls.reimburse_slashing_paused);
+    operation_controls.delegate_pending_paused = operation_controls_update_request
+        .delegate_pending_paused
+        .unwrap_or(operation_controls.delegate_pending_paused);
+
+    if UNWIND.may_load(deps.storage)?.is_some()
+        && !(operation_controls.deposit_paused
+            && operation_controls.queue_undelegate_paused
+            && operation_controls.undelegate_paused
+            && operation_controls.reinvest_paused
+            && operation_controls.delegate_pending_paused)
+    {
+        return Err(ContractError::UnwindInProgress {});
+    }
 
     OPERATION_CONTROLS.save(deps.storage, &operation_controls)?;
+    update_pause_info(
+        deps.storage,
+        &old_operation_controls,
+        &operation_controls,
+        &info.sender,
+        env.block.time,
+        operation_controls_update_request.reason,
+    )?;
+    record_admin_action(
+        deps.storage,
+        &env,
+        &info.sender,
+        "update_operation_flags",
+        get_operation_controls_diff(&old_operation_controls, &operation_controls)?,
+    )?;
 
     Ok(Response::default())
 }
@@ -279,6 +603,7 @@ pub fn update_config(
 ) -> Result<Response, ContractError> {
     let mut config = CONFIG.load(deps.storage)?;
     validate(&config, &info, &env, vec![Verify::SenderManager])?;
+    let old_config = config.clone();
 
     if let Some(cw20_contract) = update_config.cw20_token_contract {
         if config.cw20_token_contract == Addr::unchecked("0") {
@@ -292,6 +617,10 @@ pub fn update_config(
         config.airdrop_registry_contract = deps.api.addr_validate(arc.as_str())?;
     }
 
+    if let Some(nft_contract) = update_config.unbonding_nft_contract {
+        config.unbonding_nft_contract = Some(deps.api.addr_validate(nft_contract.as_str())?);
+    }
+
     config.min_deposit = update_config.min_deposit.unwrap_or(config.min_deposit);
     config.max_deposit = update_config.max_deposit.unwrap_or(config.max_deposit);
 
@@ -326,8 +655,73 @@ pub fn update_config(
     config.reinvest_cooldown = update_config
         .reinvest_cooldown
         .unwrap_or(config.reinvest_cooldown);
+    config.split_policy = update_config.split_policy.unwrap_or(config.split_policy);
+    config.epoch_delegation = update_config
+        .epoch_delegation
+        .unwrap_or(config.epoch_delegation);
+    config.delegate_pending_cooldown = update_config
+        .delegate_pending_cooldown
+        .unwrap_or(config.delegate_pending_cooldown);
+    config.reward_fee_as_shares = update_config
+        .reward_fee_as_shares
+        .unwrap_or(config.reward_fee_as_shares);
+    config.fee_destination_policy = update_config
+        .fee_destination_policy
+        .unwrap_or(config.fee_destination_policy);
+
+    if let Some(rfs) = update_config.referral_fee_share {
+        if rfs.gt(&Decimal::one()) {
+            return Err(ContractError::InvalidReferralFeeShare {});
+        }
+        config.referral_fee_share = rfs;
+    }
+
+    if let Some(rsp) = update_config.reward_sniping_policy {
+        if let Some(window_fee) = rsp.reinvest_window_fee {
+            if window_fee.gt(&get_deposit_fee_cap()) {
+                return Err(ContractError::ProtocolFeeAboveLimit {});
+            }
+        }
+        config.reward_sniping_policy = rsp;
+    }
+
+    // The window fee is charged on top of the deposit fee, so their sum has to stay under the cap
+    // whichever of the two was updated last.
+    if let Some(window_fee) = config.reward_sniping_policy.reinvest_window_fee {
+        if decimal_summation_in_256(config.protocol_deposit_fee, window_fee)
+            .gt(&get_deposit_fee_cap())
+        {
+            return Err(ContractError::ProtocolFeeAboveLimit {});
+        }
+    }
+
+    if let Some(guardian) = update_config.guardian {
+        config.guardian = Some(deps.api.addr_validate(guardian.as_str())?);
+    }
+
+    if let Some(cbp) = update_config.circuit_breaker_policy {
+        if cbp
+            .max_rate_drop_per_call
+            .unwrap_or_default()
+            .gt(&Decimal::one())
+            || cbp
+                .max_rate_drop_per_day
+                .unwrap_or_default()
+                .gt(&Decimal::one())
+        {
+            return Err(ContractError::InvalidCircuitBreakerPolicy {});
+        }
+        config.circuit_breaker_policy = cbp;
+    }
 
     CONFIG.save(deps.storage, &config)?;
+    record_admin_action(
+        deps.storage,
+        &env,
+        &info.sender,
+        "update_config",
+        get_config_diff(&old_config, &config)?,
+    )?;
 
     Ok(Response::default())
 }
@@ -354,13 +748,22 @@ pub fn add_validator(
         return Err(ContractError::ValidatorNotDiscoverable {});
     }
 
-    STATE.update(deps.storage, |mut state| -> StdResult<_> {
+    let new_state = STATE.update(deps.storage, |mut state| -> StdResult<_> {
         state.validators.push(val_addr.clone());
         Ok(state)
     })?;
 
     VALIDATOR_META.save(deps.storage, &val_addr, &VMeta::new())?;
 
+    let mut diff = vec![];
+    add_change(
+        &mut diff,
+        "validators",
+        &state.validators,
+        &new_state.validators,
+    )?;
+    record_admin_action(deps.storage, &env, &info.sender, "add_validator", diff)?;
+
     Ok(Response::new().add_attribute("new_validator", val_addr.to_string()))
 }
 
@@ -377,7 +780,12 @@ pub fn remove_validator_from_pool(
     let val_addr = Addr::unchecked(val_addr.to_string().to_lowercase());
     let redel_addr = Addr::unchecked(redel_addr.to_string().to_lowercase());
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(get_circuit_breaker_tripped_response(
+            "remove_validator",
+            reason,
+        ));
+    }
 
     let mut state = STATE.load(deps.storage)?;
 
@@ -389,6 +797,7 @@ pub fn remove_validator_from_pool(
         return Err(ContractError::ValidatorNotAdded {});
     }
 
+    let old_validators = state.validators.clone();
     state.validators = state
         .validators
         .into_iter()
@@ -398,7 +807,7 @@ pub fn remove_validator_from_pool(
     // Update validator tracking amounts
     let val_delegation = deps
         .querier
-        .query_delegation(env.contract.address, val_addr.clone())?;
+        .query_delegation(env.contract.address.clone(), val_addr.clone())?;
     let mut msgs = vec![];
     if val_delegation.is_some() {
         let full_delegation = val_delegation.unwrap();
@@ -420,6 +829,10 @@ pub fn remove_validator_from_pool(
     STATE.save(deps.storage, &state)?;
     VALIDATOR_META.remove(deps.storage, &val_addr);
 
+    let mut diff = vec![];
+    add_change(&mut diff, "validators", &old_validators, &state.validators)?;
+    record_admin_action(deps.storage, &env, &info.sender, "remove_validator", diff)?;
+
     Ok(Response::new().add_messages(msgs))
 }
 
@@ -442,7 +855,12 @@ pub fn rebalance_pool(
     let val_addr = Addr::unchecked(val_addr.to_string().to_lowercase());
     let redel_addr = Addr::unchecked(redel_addr.to_string().to_lowercase());
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(get_circuit_breaker_tripped_response(
+            "rebalance_pool",
+            reason,
+        ));
+    }
 
     let state = STATE.load(deps.storage)?;
     if val_addr.eq(&redel_addr) {
@@ -455,7 +873,7 @@ pub fn rebalance_pool(
 
     let src_val_delegation_opt = deps
         .querier
-        .query_delegation(env.contract.address, val_addr.clone())?;
+        .query_delegation(env.contract.address.clone(), val_addr.clone())?;
     if let Some(src_val_delegation) = src_val_delegation_opt {
         if src_val_delegation.amount.amount.lt(&amount) {
             return Err(ContractError::InSufficientFunds {});
@@ -473,9 +891,34 @@ pub fn rebalance_pool(
     };
 
     // Update validator tracking amounts
+    let old_src_staked = VALIDATOR_META
+        .may_load(deps.storage, &val_addr)?
+        .map(|x| x.staked);
+    let old_dst_staked = VALIDATOR_META
+        .may_load(deps.storage, &redel_addr)?
+        .map(|x| x.staked);
     decrease_tracked_stake(&mut deps, &val_addr, amount)?;
     increase_tracked_stake(&mut deps, &redel_addr, amount)?;
 
+    let mut diff = vec![];
+    add_change(
+        &mut diff,
+        format!("{}.staked", val_addr).as_str(),
+        &old_src_staked,
+        &VALIDATOR_META
+            .may_load(deps.storage, &val_addr)?
+            .map(|x| x.staked),
+    )?;
+    add_change(
+        &mut diff,
+        format!("{}.staked", redel_addr).as_str(),
+        &old_dst_staked,
+        &VALIDATOR_META
+            .may_load(deps.storage, &redel_addr)?
+            .map(|x| x.staked),
+    )?;
+    record_admin_action(deps.storage, &env, &info.sender, "rebalance_pool", diff)?;
+
     Ok(Response::new().add_message(StakingMsg::Redelegate {
         src_validator: val_addr.to_string(),
         dst_validator: redel_addr.to_string(),
@@ -483,8 +926,16 @@ pub fn rebalance_pool(
     }))
 }
 
-pub fn check_slashing(deps: &mut DepsMut, env: &Env) -> Result<Response, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
+// Returns the reason when the recomputed exchange rate trips the circuit breaker.
+// The caller must then return successfully without acting on the rate, or the pause would be reverted with it.
+pub fn check_slashing(deps: &mut DepsMut, env: &Env) -> Result<Option<String>, ContractError> {
+    let previous_rate = STATE.load(deps.storage)?.exchange_rate;
+    let new_rate = record_slashing(deps, env)?;
+    check_circuit_breaker(deps.storage, env, previous_rate, new_rate)
+}
+
+// Moves slashed stake out of the tracked stake and returns the recomputed exchange rate.
+fn record_slashing(deps: &mut DepsMut, env: &Env) -> Result<Decimal256, ContractError> {
     let mut state = STATE.load(deps.storage)?;
     let mut total_staked_on_chain = Uint128::zero();
 
@@ -518,17 +969,202 @@ pub fn check_slashing(deps: &mut DepsMut, env: &Env) -> Result<Response, Contrac
         })?;
     }
 
-    let total_tokens = get_total_token_supply(deps.querier, config.cw20_token_contract)?;
-
-    state.total_staked = total_staked_on_chain;
-    state.exchange_rate = calculate_exchange_rate(state.total_staked, total_tokens);
+    // Deposits waiting for the next epoch are part of the pool even though they are not delegated yet.
+    state.total_staked = total_staked_on_chain
+        .checked_add(state.pending_delegation)
+        .unwrap();
+    state.exchange_rate = calculate_exchange_rate(state.total_staked, state.total_shares);
     STATE.save(deps.storage, &state)?;
 
-    Ok(Response::default())
+    Ok(state.exchange_rate)
+}
+
+fn check_circuit_breaker(
+    storage: &mut dyn Storage,
+    env: &Env,
+    previous_rate: Decimal256,
+    new_rate: Decimal256,
+) -> Result<Option<String>, ContractError> {
+    // The unwind pauses the pool on its own and has to be able to run through any rate move.
+    if UNWIND.may_load(storage)?.is_some() {
+        return Ok(None);
+    }
+
+    let policy = CONFIG.load(storage)?.circuit_breaker_policy;
+    let mut circuit_breaker = match CIRCUIT_BREAKER.may_load(storage)? {
+        Some(circuit_breaker)
+            if env.block.time.lt(&circuit_breaker
+                .window_start
+                .plus_seconds(get_circuit_breaker_window())) =>
+        {
+            circuit_breaker
+        }
+        circuit_breaker => CircuitBreakerState {
+            window_start: env.block.time,
+            window_start_rate: previous_rate,
+            trip: circuit_breaker.and_then(|x| x.trip),
+        },
+    };
+
+    let reason = if rate_dropped_beyond(previous_rate, new_rate, policy.max_rate_drop_per_call) {
+        Some("rate_drop_per_call")
+    } else if rate_rose_beyond(previous_rate, new_rate, policy.max_rate_rise_per_call) {
+        Some("rate_rise_per_call")
+    } else if rate_dropped_beyond(
+        circuit_breaker.window_start_rate,
+        new_rate,
+        policy.max_rate_drop_per_day,
+    ) {
+        Some("rate_drop_per_day")
+    } else if rate_rose_beyond(
+        circuit_breaker.window_start_rate,
+        new_rate,
+        policy.max_rate_rise_per_day,
+    ) {
+        Some("rate_rise_per_day")
+    } else {
+        None
+    };
+
+    if let Some(reason) = reason {
+        let mut paused_operations = vec![];
+        let old_operation_controls = OPERATION_CONTROLS.load(storage)?;
+        let mut operation_controls = old_operation_controls.clone();
+        for (name, paused) in [
+            ("deposit", &mut operation_controls.deposit_paused),
+            (
+                "queue_undelegate",
+                &mut operation_controls.queue_undelegate_paused,
+            ),
+            ("undelegate", &mut operation_controls.undelegate_paused),
+            ("reinvest", &mut operation_controls.reinvest_paused),
+            (
+                "delegate_pending",
+                &mut operation_controls.delegate_pending_paused,
+            ),
+        ] {
+            if !*paused {
+                *paused = true;
+                paused_operations.push(name.to_string());
+            }
+        }
+        OPERATION_CONTROLS.save(storage, &operation_controls)?;
+        update_pause_info(
+            storage,
+            &old_operation_controls,
+            &operation_controls,
+            &env.contract.address,
+            env.block.time,
+            Some(format!("circuit_breaker: {}", reason)),
+        )?;
+
+        // A second trip before resuming keeps the operations paused by the first one.
+        if let Some(trip) = circuit_breaker.trip.take() {
+            paused_operations.extend(trip.paused_operations);
+        }
+        circuit_breaker.trip = Some(CircuitBreakerTrip {
+            reason: reason.to_string(),
+            tripped_at: env.block.time,
+            previous_rate,
+            new_rate,
+            paused_operations,
+        });
+    }
+    CIRCUIT_BREAKER.save(storage, &circuit_breaker)?;
+
+    Ok(reason.map(String::from))
+}
+
+fn rate_dropped_beyond(from: Decimal256, to: Decimal256, max_drop: Option<Decimal>) -> bool {
+    match max_drop {
+        Some(max_drop) => to.lt(&(from * (Decimal256::one() - Decimal256::from(max_drop)))),
+        None => false,
+    }
+}
+
+fn rate_rose_beyond(from: Decimal256, to: Decimal256, max_rise: Option<Decimal>) -> bool {
+    match max_rise {
+        Some(max_rise) => to.gt(&(from * (Decimal256::one() + Decimal256::from(max_rise)))),
+        None => false,
+    }
+}
+
+fn get_circuit_breaker_tripped_response(action: &str, reason: String) -> Response {
+    Response::new().add_attributes(vec![
+        attr("action", action),
+        attr("circuit_breaker_tripped", reason),
+    ])
+}
+
+pub fn resume_from_circuit_breaker(
+    deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if info.sender.ne(&config.manager) && Some(&info.sender).ne(&config.guardian.as_ref()) {
+        return Err(ContractError::Unauthorized {});
+    }
+    if UNWIND.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::UnwindInProgress {});
+    }
+
+    let mut circuit_breaker = CIRCUIT_BREAKER
+        .may_load(deps.storage)?
+        .ok_or(ContractError::CircuitBreakerNotTripped {})?;
+    let trip = circuit_breaker
+        .trip
+        .take()
+        .ok_or(ContractError::CircuitBreakerNotTripped {})?;
+
+    let old_operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    let mut operation_controls = old_operation_controls.clone();
+    for name in trip.paused_operations.iter() {
+        match name.as_str() {
+            "deposit" => operation_controls.deposit_paused = false,
+            "queue_undelegate" => operation_controls.queue_undelegate_paused = false,
+            "undelegate" => operation_controls.undelegate_paused = false,
+            "reinvest" => operation_controls.reinvest_paused = false,
+            "delegate_pending" => operation_controls.delegate_pending_paused = false,
+            _ => {}
+        }
+    }
+    OPERATION_CONTROLS.save(deps.storage, &operation_controls)?;
+    update_pause_info(
+        deps.storage,
+        &old_operation_controls,
+        &operation_controls,
+        &info.sender,
+        env.block.time,
+        None,
+    )?;
+    record_admin_action(
+        deps.storage,
+        &env,
+        &info.sender,
+        "resume_from_circuit_breaker",
+        get_operation_controls_diff(&old_operation_controls, &operation_controls)?,
+    )?;
+
+    // The accepted rate becomes the new baseline, or the day limit would trip again on the next call.
+    circuit_breaker.window_start = env.block.time;
+    circuit_breaker.window_start_rate = STATE.load(deps.storage)?.exchange_rate;
+    CIRCUIT_BREAKER.save(deps.storage, &circuit_breaker)?;
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "resume_from_circuit_breaker"),
+        attr("reason", trip.reason),
+        attr("resumed_by", info.sender),
+    ]))
 }
 
 // Any address can call this.
-pub fn deposit(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Response, ContractError> {
+pub fn deposit(
+    mut deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+    referral_code: Option<String>,
+) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
     let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
 
@@ -538,7 +1174,14 @@ pub fn deposit(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respons
 
     validate(&config, &info, &env, vec![Verify::NonZeroSingleInfoFund])?;
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(
+            get_circuit_breaker_tripped_response("deposit", reason).add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: info.funds,
+            }),
+        );
+    }
 
     let amount = info.funds.first().unwrap().amount;
     if amount.gt(&config.max_deposit) {
@@ -547,47 +1190,118 @@ pub fn deposit(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respons
     if amount.lt(&config.min_deposit) {
         return Err(ContractError::MinDeposit {});
     }
+    if let Some(code) = referral_code.as_deref() {
+        // Owners would otherwise get a part of their own deposit fee back.
+        if let Some(referral_info) = REFERRAL_CODES.may_load(deps.storage, code)? {
+            if referral_info.owner.eq(&info.sender) {
+                return Err(ContractError::SelfReferral {});
+            }
+        }
+    }
     let sender = info.sender;
     let mut state = STATE.load(deps.storage)?;
 
     let mut msgs = vec![];
-    let deposit_breakdown = compute_deposit_breakdown(deps.storage.deref(), amount)?;
-
-    if !deposit_breakdown.protocol_fee.is_zero() {
-        msgs.push(SubMsg::new(BankMsg::Send {
-            to_address: config.protocol_fee_contract.to_string(),
-            amount: vec![Coin::new(
-                deposit_breakdown.protocol_fee.u128(),
-                config.vault_denom.clone(),
-            )],
-        }));
+    let deposit_breakdown =
+        compute_deposit_breakdown(deps.as_ref(), &env, amount, referral_code.as_deref())?;
+    if deposit_breakdown.tokens_to_mint.is_zero() {
+        return Err(ContractError::ZeroMint {});
     }
 
-    if !deposit_breakdown.staked_amount.is_zero() {
-        let val_addr = get_validator_for_deposit(
+    msgs.extend(
+        get_protocol_fee_msgs(&config, deposit_breakdown.protocol_fee)
+            .into_iter()
+            .map(SubMsg::new),
+    );
+
+    if !deposit_breakdown.staked_amount.is_zero() && config.epoch_delegation {
+        state.total_staked = state
+            .total_staked
+            .checked_add(deposit_breakdown.staked_amount)
+            .unwrap();
+        state.pending_delegation = state
+            .pending_delegation
+            .checked_add(deposit_breakdown.staked_amount)
+            .unwrap();
+    } else if !deposit_breakdown.staked_amount.is_zero() {
+        let allocations = get_deposit_allocations(
             deps.querier,
-            env.contract.address,
+            env.contract.address.clone(),
             state.validators.clone(),
+            deposit_breakdown.staked_amount,
+            config.split_policy.deposit_threshold,
         )?;
 
         state.total_staked = state
             .total_staked
             .checked_add(deposit_breakdown.staked_amount)
             .unwrap();
-        increase_tracked_stake(&mut deps, &val_addr, deposit_breakdown.staked_amount)?;
+        for (val_addr, val_amount) in allocations {
+            increase_tracked_stake(&mut deps, &val_addr, val_amount)?;
 
-        msgs.push(SubMsg::new(StakingMsg::Delegate {
-            validator: val_addr.to_string(),
-            amount: Coin::new(deposit_breakdown.staked_amount.u128(), config.vault_denom),
-        }));
+            msgs.push(SubMsg::new(StakingMsg::Delegate {
+                validator: val_addr.to_string(),
+                amount: Coin::new(val_amount.u128(), config.vault_denom.clone()),
+            }));
+        }
     }
 
-    let mut mint_messages = vec![];
-    if !deposit_breakdown.tokens_to_mint.is_zero() {
+    state.total_shares = state
+        .total_shares
+        .checked_add(deposit_breakdown.tokens_to_mint)
+        .unwrap()
+        .checked_add(deposit_breakdown.locked_tokens)
+        .unwrap();
+    state.rounding_dust += deposit_breakdown.rounding_dust;
+    if let Some(code) = referral_code {
+        // The referral fee stays in the contract until the code's owner claims it.
+        state.unclaimed_referral_fees = state
+            .unclaimed_referral_fees
+            .checked_add(deposit_breakdown.referral_fee)
+            .unwrap();
+        REFERRAL_CODES.update(
+            deps.storage,
+            code.as_str(),
+            |x| -> Result<_, ContractError> {
+                let mut referral_info = x.ok_or(ContractError::ReferralCodeNotFound {})?;
+                referral_info.total_deposits =
+                    referral_info.total_deposits.checked_add(amount).unwrap();
+                referral_info.outstanding_shares = referral_info
+                    .outstanding_shares
+                    .checked_add(deposit_breakdown.tokens_to_mint)
+                    .unwrap();
+                referral_info.unclaimed_fees = referral_info
+                    .unclaimed_fees
+                    .checked_add(deposit_breakdown.referral_fee)
+                    .unwrap();
+                Ok(referral_info)
+            },
+        )?;
+        REFERRED_SHARES.update(
+            deps.storage,
+            (&sender, code.as_str()),
+            |x| -> StdResult<_> {
+                Ok(x.unwrap_or_default()
+                    .checked_add(deposit_breakdown.tokens_to_mint)
+                    .unwrap())
+            },
+        )?;
+    }
+    if !deposit_breakdown.pool_fee.is_zero() {
+        // The fee was staked without minting shares for it.
+        state.exchange_rate = calculate_exchange_rate(state.total_staked, state.total_shares);
+    }
+    let mut mint_messages = vec![create_mint_message(
+        config.cw20_token_contract.clone(),
+        deposit_breakdown.tokens_to_mint,
+        sender,
+    )?];
+    if !deposit_breakdown.locked_tokens.is_zero() {
+        // Held by this contract and never undelegated.
         mint_messages.push(create_mint_message(
             config.cw20_token_contract,
-            deposit_breakdown.tokens_to_mint,
-            sender,
+            deposit_breakdown.locked_tokens,
+            env.contract.address,
         )?);
     }
 
@@ -598,56 +1312,194 @@ pub fn deposit(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respons
         .add_messages(mint_messages))
 }
 
-pub fn compute_deposit_breakdown(
-    storage: &dyn Storage,
-    user_amount: Uint128, // funds sent by user.
-) -> Result<GetFundsDepositRecord, ContractError> {
-    let config = CONFIG.load(storage)?;
-    let state = STATE.load(storage)?;
-
-    let mut amount_to_stake = user_amount;
-    let mut protocol_deposit_fee = Uint128::zero();
-
-    if !config.protocol_deposit_fee.is_zero() {
-        protocol_deposit_fee = config.protocol_deposit_fee.mul(user_amount);
-        amount_to_stake = user_amount
-            .checked_sub(protocol_deposit_fee)
-            .unwrap_or(Uint128::zero());
-    }
-    let mint_tokens = uint128_from_decimal(decimal_division_in_256(
-        get_decimal_from_uint128(amount_to_stake),
-        state.exchange_rate, // exchange rate will never be 0
-    ));
-    Ok(GetFundsDepositRecord {
-        user_deposit_amount: user_amount,
-        protocol_fee: protocol_deposit_fee,
-        staked_amount: amount_to_stake,
-        tokens_to_mint: mint_tokens,
-    })
-}
-
-pub fn redeem_rewards(
+// Delegates every deposit held in the pending pool. Anyone can call this once the cooldown has passed.
+pub fn delegate_pending(
     mut deps: DepsMut,
-    _info: MessageInfo,
+    info: MessageInfo,
     env: Env,
 ) -> Result<Response, ContractError> {
-    check_slashing(&mut deps, &env)?;
-    let state = STATE.load(deps.storage)?;
+    let config = CONFIG.load(deps.storage)?;
     let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
-    if operation_controls.redeem_rewards_paused {
-        return Err(ContractError::OperationPaused("redeem_rewards".to_string()));
+    if operation_controls.delegate_pending_paused {
+        return Err(ContractError::OperationPaused(
+            "delegate_pending".to_string(),
+        ));
     }
 
-    let mut messages = vec![];
-    let mut failed_vals: Vec<String> = vec![];
-    for val_addr in state.validators {
-        // Skip validators that are currently jailed.
-        if deps
-            .querier
-            .query_validator(val_addr.to_string())?
-            .is_none()
-            || deps
-                .querier
+    let mut state = STATE.load(deps.storage)?;
+
+    if info.sender.ne(&config.manager)
+        && env.block.time.lt(&state
+            .last_delegate_pending_time
+            .plus_seconds(config.delegate_pending_cooldown))
+    {
+        return Err(ContractError::DelegatePendingInCooldown {});
+    }
+
+    if state.pending_delegation.is_zero() {
+        return Ok(Response::default());
+    }
+
+    let allocations = get_deposit_allocations(
+        deps.querier,
+        env.contract.address.clone(),
+        state.validators.clone(),
+        state.pending_delegation,
+        config.split_policy.deposit_threshold,
+    )?;
+
+    let mut msgs = vec![];
+    for (val_addr, val_amount) in allocations {
+        increase_tracked_stake(&mut deps, &val_addr, val_amount)?;
+
+        msgs.push(StakingMsg::Delegate {
+            validator: val_addr.to_string(),
+            amount: Coin::new(val_amount.u128(), config.vault_denom.clone()),
+        });
+    }
+
+    let delegated_amount = state.pending_delegation;
+    state.pending_delegation = Uint128::zero();
+    state.last_delegate_pending_time = env.block.time;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new()
+        .add_messages(msgs)
+        .add_attribute("delegated_amount", delegated_amount.to_string()))
+}
+
+pub fn compute_deposit_breakdown(
+    deps: Deps,
+    env: &Env,
+    user_amount: Uint128, // funds sent by user.
+    referral_code: Option<&str>,
+) -> Result<GetFundsDepositRecord, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+    let reward_sniping_policy = &config.reward_sniping_policy;
+
+    let mut deposit_fee = config.protocol_deposit_fee;
+    if reward_sniping_policy.reinvest_window > 0
+        && env.block.time.ge(&state.last_reinvest_time.plus_seconds(
+            config
+                .reinvest_cooldown
+                .saturating_sub(reward_sniping_policy.reinvest_window),
+        ))
+    {
+        match reward_sniping_policy.reinvest_window_fee {
+            Some(window_fee) => deposit_fee = decimal_summation_in_256(deposit_fee, window_fee),
+            None => return Err(ContractError::DepositInReinvestWindow {}),
+        }
+    }
+
+    let mut exchange_rate = state.exchange_rate;
+    if reward_sniping_policy.include_pending_rewards {
+        let pending_rewards = get_pending_rewards(
+            deps.querier,
+            env.contract.address.clone(),
+            &state.validators,
+            &config,
+        )?;
+        exchange_rate = calculate_exchange_rate(
+            state.total_staked.checked_add(pending_rewards).unwrap(),
+            state.total_shares,
+        );
+    }
+
+    if let Some(code) = referral_code {
+        if !REFERRAL_CODES.has(deps.storage, code) {
+            return Err(ContractError::ReferralCodeNotFound {});
+        }
+    }
+
+    let mut amount_to_mint = user_amount;
+    let mut protocol_deposit_fee = Uint128::zero();
+    let mut pool_deposit_fee = Uint128::zero();
+    let mut referral_fee = Uint128::zero();
+
+    if !deposit_fee.is_zero() {
+        // Fees round up so that the pool never gives away a fraction of a luna.
+        let mut fee = multiply_uint128_with_decimal256(
+            user_amount,
+            Decimal256::from(deposit_fee),
+            Rounding::Ceil,
+        );
+        amount_to_mint = user_amount.checked_sub(fee).unwrap_or(Uint128::zero());
+        // The referral code takes its share first. The rest follows the fee destination policy.
+        if referral_code.is_some() {
+            referral_fee = config.referral_fee_share.mul(fee);
+            fee = fee.checked_sub(referral_fee).unwrap_or(Uint128::zero());
+        }
+        match config.fee_destination_policy.deposit_fee {
+            FeeDestination::ProtocolFeeRecipients => protocol_deposit_fee = fee,
+            FeeDestination::Pool => pool_deposit_fee = fee,
+        }
+    }
+    let amount_to_stake = user_amount
+        .checked_sub(protocol_deposit_fee)
+        .unwrap_or(Uint128::zero())
+        .checked_sub(referral_fee)
+        .unwrap_or(Uint128::zero());
+    // Mints round down so that new shares are never worth more than the luna backing them.
+    let mut mint_tokens = divide_uint128_by_decimal256(
+        amount_to_mint,
+        exchange_rate, // exchange rate will never be 0
+        Rounding::Floor,
+    );
+    let rounding_dust = get_rounding_dust(
+        get_decimal256_from_uint128(amount_to_mint) / exchange_rate,
+        mint_tokens,
+    ) * exchange_rate;
+
+    // The first deposit locks a few shares away for good. Otherwise a lone first depositor could
+    // inflate the exchange rate through ReimburseSlashing until later deposits mint nothing.
+    let mut locked_tokens = Uint128::zero();
+    if state.total_shares.is_zero() {
+        locked_tokens = get_locked_shares_on_first_deposit();
+        mint_tokens = mint_tokens
+            .checked_sub(locked_tokens)
+            .unwrap_or_else(|_| Uint128::zero());
+    }
+
+    Ok(GetFundsDepositRecord {
+        user_deposit_amount: user_amount,
+        protocol_fee: protocol_deposit_fee,
+        pool_fee: pool_deposit_fee,
+        referral_fee,
+        staked_amount: amount_to_stake,
+        tokens_to_mint: mint_tokens,
+        locked_tokens,
+        rounding_dust,
+    })
+}
+
+pub fn redeem_rewards(
+    mut deps: DepsMut,
+    _info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(get_circuit_breaker_tripped_response(
+            "redeem_rewards",
+            reason,
+        ));
+    }
+    let state = STATE.load(deps.storage)?;
+    let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    if operation_controls.redeem_rewards_paused {
+        return Err(ContractError::OperationPaused("redeem_rewards".to_string()));
+    }
+
+    let mut messages = vec![];
+    let mut failed_vals: Vec<String> = vec![];
+    for val_addr in state.validators {
+        // Skip validators that are currently jailed.
+        if deps
+            .querier
+            .query_validator(val_addr.to_string())?
+            .is_none()
+            || deps
+                .querier
                 .query_delegation(env.contract.address.clone(), val_addr.to_string())?
                 .is_none()
         {
@@ -700,7 +1552,9 @@ pub fn reinvest(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respon
         return Err(ContractError::OperationPaused("reinvest".to_string()));
     }
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(get_circuit_breaker_tripped_response("reinvest", reason));
+    }
 
     let mut state = STATE.load(deps.storage)?;
 
@@ -717,10 +1571,16 @@ pub fn reinvest(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respon
         config.vault_denom.clone(),
     )?;
 
-    let protocol_fee_amount = uint128_from_decimal(decimal_multiplication_in_256(
+    let reward_fee = uint128_from_decimal(decimal_multiplication_in_256(
         get_decimal_from_uint128(balance.amount),
         config.protocol_reward_fee,
     ));
+    // When the fee is taken as shares all of the rewards are staked.
+    let protocol_fee_amount = if config.reward_fee_as_shares {
+        Uint128::zero()
+    } else {
+        reward_fee
+    };
     let transfer_amount = balance
         .amount
         .checked_sub(protocol_fee_amount)
@@ -733,10 +1593,21 @@ pub fn reinvest(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respon
     )?;
     state.total_staked = state.total_staked.checked_add(transfer_amount).unwrap();
     increase_tracked_stake(&mut deps, &val_addr, transfer_amount)?;
-    state.exchange_rate = calculate_exchange_rate(
-        state.total_staked,
-        get_total_token_supply(deps.querier, config.cw20_token_contract)?,
-    );
+
+    let mut mint_messages = vec![];
+    if config.reward_fee_as_shares {
+        let fee_shares = get_fee_shares(state.total_staked, state.total_shares, reward_fee);
+        for fee_share in split_protocol_fee(&config.protocol_fee_recipients, fee_shares) {
+            mint_messages.push(create_mint_message(
+                config.cw20_token_contract.clone(),
+                fee_share.amount,
+                fee_share.recipient,
+            )?);
+        }
+        state.total_shares = state.total_shares.checked_add(fee_shares).unwrap();
+        state.fee_shares_minted = state.fee_shares_minted.checked_add(fee_shares).unwrap();
+    }
+    state.exchange_rate = calculate_exchange_rate(state.total_staked, state.total_shares);
 
     state.last_reinvest_time = env.block.time;
     STATE.save(deps.storage, &state)?;
@@ -747,8 +1618,7 @@ pub fn reinvest(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respon
         msg: to_binary(&RewardExecuteMsg::Transfer {
             reward_amount: transfer_amount,
             reward_withdraw_contract: env.contract.address,
-            protocol_fee_amount,
-            protocol_fee_contract: config.protocol_fee_contract,
+            protocol_fees: split_protocol_fee(&config.protocol_fee_recipients, protocol_fee_amount),
         })?,
         funds: vec![],
     }));
@@ -761,7 +1631,9 @@ pub fn reinvest(mut deps: DepsMut, info: MessageInfo, env: Env) -> Result<Respon
     }
 
     // Reward contract throws an error if transfer_amount is not available to be sent over.
-    Ok(Response::new().add_submessages(msgs))
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_messages(mint_messages))
 }
 
 // Useful for staking to a validator as a mechanism for filling lost slashing funds.
@@ -816,7 +1688,7 @@ pub fn receive_cw20(
     let config = CONFIG.load(deps.storage)?;
 
     match from_binary(&cw20_msg.msg) {
-        Ok(Cw20HookMsg::QueueUndelegate {}) => {
+        Ok(Cw20HookMsg::QueueUndelegate { mint_nft }) => {
             // only token contract can execute this message
             if contract_addr != config.cw20_token_contract {
                 return Err(ContractError::Unauthorized {});
@@ -828,6 +1700,19 @@ pub fn receive_cw20(
                 info,
                 cw20_msg.amount,
                 cw20_msg.sender,
+                mint_nft.unwrap_or(false),
+            )?)
+        }
+        Ok(Cw20HookMsg::UnwindClaim {}) => {
+            if contract_addr != config.cw20_token_contract {
+                return Err(ContractError::Unauthorized {});
+            }
+            Ok(unwind_claim(
+                deps,
+                env,
+                info,
+                cw20_msg.amount,
+                cw20_msg.sender,
             )?)
         }
         Err(_err) => Err(ContractError::NoOp {}),
@@ -841,6 +1726,7 @@ pub fn queue_undelegation(
     _info: MessageInfo,
     amount_to_burn: Uint128,
     user_addr_str: String,
+    mint_nft: bool,
 ) -> Result<Response, ContractError> {
     let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
     if operation_controls.queue_undelegate_paused {
@@ -849,28 +1735,110 @@ pub fn queue_undelegation(
         ));
     }
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        let config = CONFIG.load(deps.storage)?;
+        return Ok(
+            get_circuit_breaker_tripped_response("queue_undelegate", reason).add_message(
+                WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: user_addr_str,
+                        amount: amount_to_burn,
+                    })?,
+                    funds: vec![],
+                },
+            ),
+        );
+    }
 
     let state = STATE.load(deps.storage)?;
 
     let batch_key = U64Key::new(state.current_undelegation_batch_id);
     let user_addr = deps.api.addr_validate(user_addr_str.as_str())?;
 
-    USERS.update(
-        deps.storage,
-        (&user_addr, batch_key.clone()),
-        |x| -> StdResult<_> {
-            let mut user_current_batch_undelegations = x.unwrap_or(UndelegationInfo {
+    // The undelegated shares are taken from the user's referred shares, code by code. Shares the
+    // user got any other way are not attributed to a code.
+    let referred_shares = REFERRED_SHARES
+        .prefix(&user_addr)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    let mut unattributed_amount = amount_to_burn;
+    for (code, shares) in referred_shares {
+        if unattributed_amount.is_zero() {
+            break;
+        }
+        let code = String::from_utf8(code).map_err(StdError::from)?;
+        let attributed_amount = shares.min(unattributed_amount);
+        unattributed_amount = unattributed_amount.checked_sub(attributed_amount).unwrap();
+        if attributed_amount.eq(&shares) {
+            REFERRED_SHARES.remove(deps.storage, (&user_addr, code.as_str()));
+        } else {
+            REFERRED_SHARES.save(
+                deps.storage,
+                (&user_addr, code.as_str()),
+                &shares.checked_sub(attributed_amount).unwrap(),
+            )?;
+        }
+        REFERRAL_CODES.update(deps.storage, code.as_str(), |x| -> StdResult<_> {
+            let mut referral_info = x.unwrap();
+            referral_info.outstanding_shares = referral_info
+                .outstanding_shares
+                .checked_sub(attributed_amount)
+                .unwrap_or(Uint128::zero());
+            Ok(referral_info)
+        })?;
+    }
+
+    let mut msgs = vec![];
+    let mut attrs = vec![];
+    if mint_nft {
+        // Each NFT is its own position, so it is never merged with other undelegations in the batch.
+        let config = CONFIG.load(deps.storage)?;
+        let unbonding_nft_contract = config
+            .unbonding_nft_contract
+            .ok_or(ContractError::UnbondingNftNotConfigured {})?;
+        let nft_count = UNBONDING_NFT_COUNT.may_load(deps.storage)?.unwrap_or(0);
+        let token_id = nft_count.to_string();
+        UNBONDING_NFT_COUNT.save(deps.storage, &(nft_count + 1))?;
+        UNBONDING_NFTS.save(
+            deps.storage,
+            token_id.as_str(),
+            &UndelegationInfo {
                 batch_id: state.current_undelegation_batch_id,
-                token_amount: Uint128::zero(),
-            });
-            user_current_batch_undelegations.token_amount = user_current_batch_undelegations
-                .token_amount
-                .checked_add(amount_to_burn)
-                .unwrap();
-            Ok(user_current_batch_undelegations)
-        },
-    )?;
+                token_amount: amount_to_burn,
+            },
+        )?;
+        msgs.push(WasmMsg::Execute {
+            contract_addr: unbonding_nft_contract.to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::Mint(Cw721MintMsg {
+                token_id: token_id.clone(),
+                owner: user_addr.to_string(),
+                token_uri: None,
+                extension: None,
+            }))?,
+            funds: vec![],
+        });
+        attrs.push(attr("unbonding_nft", token_id));
+    } else {
+        USERS.update(
+            deps.storage,
+            (&user_addr, batch_key.clone()),
+            |x| -> StdResult<_> {
+                let mut user_current_batch_undelegations = x.unwrap_or(UndelegationInfo {
+                    batch_id: state.current_undelegation_batch_id,
+                    token_amount: Uint128::zero(),
+                });
+                user_current_batch_undelegations.token_amount = user_current_batch_undelegations
+                    .token_amount
+                    .checked_add(amount_to_burn)
+                    .unwrap();
+                Ok(user_current_batch_undelegations)
+            },
+        )?;
+        if AUTO_CLAIM_USERS.has(deps.storage, &user_addr) {
+            AUTO_CLAIM_QUEUE.save(deps.storage, (batch_key.clone(), &user_addr), &true)?;
+        }
+    }
     BATCH_UNDELEGATION_REGISTRY.update(deps.storage, batch_key, |x| -> StdResult<_> {
         let mut batch_undelegation = x.unwrap();
         batch_undelegation.undelegated_tokens = batch_undelegation
@@ -883,7 +1851,7 @@ pub fn queue_undelegation(
     })?;
     STATE.save(deps.storage, &state)?;
 
-    Ok(Response::default())
+    Ok(Response::new().add_messages(msgs).add_attributes(attrs))
 }
 
 pub fn undelegate_stake(
@@ -899,7 +1867,12 @@ pub fn undelegate_stake(
         ));
     }
 
-    check_slashing(&mut deps, &env)?;
+    if let Some(reason) = check_slashing(&mut deps, &env)? {
+        return Ok(get_circuit_breaker_tripped_response(
+            "undelegate_stake",
+            reason,
+        ));
+    }
 
     let mut state = STATE.load(deps.storage)?;
 
@@ -917,6 +1890,8 @@ pub fn undelegate_stake(
     let undelegate_batch_id = state.current_undelegation_batch_id;
     let batch_key = U64Key::new(undelegate_batch_id);
     let mut undel_amount = Uint128::zero(); // Amount to actually undelegate from blockchain
+    let mut burnt_tokens = Uint128::zero();
+    let mut rounding_dust = Decimal256::zero();
     BATCH_UNDELEGATION_REGISTRY.update(
         deps.storage,
         batch_key,
@@ -933,43 +1908,40 @@ pub fn undelegate_stake(
 
             batch_undel.est_release_time =
                 Some(env.block.time.plus_seconds(config.unbonding_period));
-            batch_undel.undelegated_stake = Uint128::new(multiply_u128_with_decimal(
-                batch_undel.undelegated_tokens.u128(),
+            batch_undel.undelegated_stake = multiply_uint128_with_decimal256(
+                batch_undel.undelegated_tokens,
                 state.exchange_rate,
-            ));
+                Rounding::Floor,
+            );
+            rounding_dust = get_rounding_dust(
+                get_decimal256_from_uint128(batch_undel.undelegated_tokens) * state.exchange_rate,
+                batch_undel.undelegated_stake,
+            );
             batch_undel.undelegation_er = state.exchange_rate;
             undel_amount = batch_undel.undelegated_stake;
+            burnt_tokens = batch_undel.undelegated_tokens;
             Ok(batch_undel)
         },
     )?;
     let validators = state.validators.clone();
-    let mut to_undelegate = undel_amount;
     let stake_tuples = get_active_validators_sorted_by_stake(
         deps.querier,
         env.contract.address.clone(),
         validators,
     )?;
+    let allocations = get_undelegation_allocations(
+        &stake_tuples,
+        undel_amount,
+        config.split_policy.undelegation_threshold,
+    )?;
 
-    for index in (0..stake_tuples.len()).rev() {
-        let tuple_val = stake_tuples.get(index).unwrap().clone();
-        if to_undelegate.is_zero() {
-            break;
-        }
-        let val_addr = Addr::unchecked(tuple_val.1);
-        let amount = std::cmp::min(to_undelegate, tuple_val.0);
+    for (val_addr, amount) in allocations {
         undelegate_message.push(StakingMsg::Undelegate {
             validator: val_addr.to_string(),
             amount: Coin::new(amount.u128(), config.vault_denom.clone()),
         });
 
         decrease_tracked_stake(&mut deps, &val_addr, amount)?;
-        to_undelegate = to_undelegate
-            .checked_sub(amount)
-            .unwrap_or_else(|_| Uint128::zero());
-    }
-
-    if !to_undelegate.is_zero() {
-        return Err(ContractError::InSufficientFunds {});
     }
 
     state.last_undelegation_time = env.block.time;
@@ -977,6 +1949,11 @@ pub fn undelegate_stake(
         .total_staked
         .checked_sub(undel_amount)
         .unwrap_or(Uint128::zero());
+    state.total_shares = state
+        .total_shares
+        .checked_sub(burnt_tokens)
+        .unwrap_or(Uint128::zero());
+    state.rounding_dust += rounding_dust;
     STATE.save(deps.storage, &state)?;
 
     // Loads the saved state.
@@ -1035,9 +2012,21 @@ pub fn reconcile_funds(
         .querier
         .query_balance(env.contract.address.to_string(), config.vault_denom)?;
 
+    // Deposits waiting to be delegated, unclaimed referral fees and released unwind funds sit in the same balance
+    // and are not unbonded batch funds.
+    let unwind_funds = UNWIND
+        .may_load(deps.storage)?
+        .map(|unwind| unwind.held_funds(env.block.time))
+        .unwrap_or_else(Uint128::zero);
     let unaccounted_funds = contract_balance
         .amount
         .checked_sub(state.reconciled_funds_to_withdraw)
+        .unwrap_or(Uint128::zero())
+        .checked_sub(state.pending_delegation)
+        .unwrap_or(Uint128::zero())
+        .checked_sub(state.unclaimed_referral_fees)
+        .unwrap_or(Uint128::zero())
+        .checked_sub(unwind_funds)
         .unwrap_or(Uint128::zero());
     if unaccounted_funds.is_zero() {
         return Err(ContractError::ZeroAmount {});
@@ -1073,6 +2062,284 @@ pub fn reconcile_funds(
     Ok(Response::default())
 }
 
+pub fn emergency_unwind(
+    mut deps: DepsMut,
+    info: MessageInfo,
+    env: Env,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    validate(&config, &info, &env, vec![Verify::SenderManager])?;
+
+    let old_operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    let previous_unwind = UNWIND.may_load(deps.storage)?;
+    let old_phase = previous_unwind.as_ref().map(|x| x.phase.clone());
+    let (mut unwind, started) = match previous_unwind {
+        Some(unwind) => (unwind, false),
+        None => (start_unwind(deps.storage, &env, &info.sender)?, true),
+    };
+    let mut response = Response::new().add_attribute("action", "emergency_unwind");
+    match unwind.phase {
+        UnwindPhase::Undelegating => {
+            // The unwind has to run through any rate move, and on the first call UNWIND is not saved
+            // yet, so the circuit breaker is skipped rather than left to trip.
+            record_slashing(&mut deps, &env)?;
+            let mut state = STATE.load(deps.storage)?;
+
+            let mut delegations = vec![];
+            for val_addr in state.validators.iter() {
+                if let Some(delegation) = deps
+                    .querier
+                    .query_delegation(env.contract.address.clone(), val_addr)?
+                {
+                    if !delegation.amount.amount.is_zero() {
+                        delegations.push((val_addr.clone(), delegation.amount.amount));
+                    }
+                }
+            }
+
+            if delegations.is_empty() {
+                unwind.phase = UnwindPhase::Unbonding;
+            } else if env.block.time.lt(&state
+                .last_undelegation_time
+                .plus_seconds(config.undelegation_cooldown))
+            {
+                // The first call still stops the pool if the stake cannot be undelegated yet.
+                if !started {
+                    return Err(ContractError::UndelegationInCooldown {});
+                }
+            } else {
+                // Each step undelegates a single validator in full, so the unwind adds one unbonding entry per
+                // validator and the steps are spaced by the batch cooldown like regular undelegations.
+                let (val_addr, amount) = delegations[0].clone();
+                response = response.add_message(StakingMsg::Undelegate {
+                    validator: val_addr.to_string(),
+                    amount: Coin::new(amount.u128(), config.vault_denom.clone()),
+                });
+                decrease_tracked_stake(&mut deps, &val_addr, amount)?;
+
+                state.last_undelegation_time = env.block.time;
+                state.total_staked = state
+                    .total_staked
+                    .checked_sub(amount)
+                    .unwrap_or(Uint128::zero());
+                STATE.save(deps.storage, &state)?;
+                unwind.steps.push(UnwindStep {
+                    amount,
+                    undelegated_at: env.block.time,
+                    release_time: env.block.time.plus_seconds(config.unbonding_period),
+                });
+                if delegations.len() == 1 {
+                    unwind.phase = UnwindPhase::Unbonding;
+                }
+                response = response
+                    .add_attribute("validator", val_addr)
+                    .add_attribute("undelegated_amount", amount);
+            }
+        }
+        UnwindPhase::Unbonding => {
+            response =
+                response.add_message(finalize_unwind(deps.branch(), &env, &config, &mut unwind)?);
+        }
+        UnwindPhase::Claimable => return Err(ContractError::UnwindAlreadyFinalized {}),
+    }
+    UNWIND.save(deps.storage, &unwind)?;
+
+    let mut diff = get_operation_controls_diff(
+        &old_operation_controls,
+        &OPERATION_CONTROLS.load(deps.storage)?,
+    )?;
+    add_change(
+        &mut diff,
+        "unwind_phase",
+        &old_phase,
+        &Some(unwind.phase.clone()),
+    )?;
+    record_admin_action(deps.storage, &env, &info.sender, "emergency_unwind", diff)?;
+
+    Ok(response.add_attribute("phase", get_unwind_phase_name(&unwind.phase)))
+}
+
+fn start_unwind(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+) -> Result<UnwindInfo, ContractError> {
+    // LunaX queued in the open batch is still counted in the pool, so it has to be undelegated the usual way first.
+    let state = STATE.load(storage)?;
+    let open_batch = BATCH_UNDELEGATION_REGISTRY
+        .load(storage, U64Key::new(state.current_undelegation_batch_id))?;
+    if !open_batch.undelegated_tokens.is_zero() {
+        return Err(ContractError::UnwindOpenBatchNotEmpty {});
+    }
+
+    let old_operation_controls = OPERATION_CONTROLS.load(storage)?;
+    let mut operation_controls = old_operation_controls.clone();
+    pause_for_unwind(&mut operation_controls);
+    OPERATION_CONTROLS.save(storage, &operation_controls)?;
+    update_pause_info(
+        storage,
+        &old_operation_controls,
+        &operation_controls,
+        sender,
+        env.block.time,
+        Some("emergency_unwind".to_string()),
+    )?;
+
+    Ok(UnwindInfo {
+        phase: UnwindPhase::Undelegating,
+        started_at: env.block.time,
+        steps: vec![],
+        claimable_amount: Uint128::zero(),
+        claimable_shares: Uint128::zero(),
+        claim_rate: Decimal256::zero(),
+        claimed_shares: Uint128::zero(),
+        claimed_amount: Uint128::zero(),
+    })
+}
+
+// Everything that moves stake into or out of the pool stays paused for the rest of the unwind.
+fn pause_for_unwind(operation_controls: &mut OperationControls) {
+    operation_controls.deposit_paused = true;
+    operation_controls.queue_undelegate_paused = true;
+    operation_controls.undelegate_paused = true;
+    operation_controls.reinvest_paused = true;
+    operation_controls.delegate_pending_paused = true;
+}
+
+// Sets the luna per LunaX once every undelegated step has been released and every batch has taken its share.
+// Rewards withdrawn to the reward contract by the undelegations are pulled in, less the protocol fee.
+fn finalize_unwind(
+    deps: DepsMut,
+    env: &Env,
+    config: &Config,
+    unwind: &mut UnwindInfo,
+) -> Result<WasmMsg, ContractError> {
+    if let Some(last_step) = unwind.steps.last() {
+        if env.block.time.lt(&last_step.release_time) {
+            return Err(ContractError::UnwindFundsNotReleased {});
+        }
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    if state.last_reconciled_batch_id + 1 < state.current_undelegation_batch_id {
+        return Err(ContractError::UnwindBatchesNotReconciled {});
+    }
+
+    // Nothing is delegated anymore, so there are no rewards left to redeem. The fee is taken in luna
+    // because minting fee shares now would dilute the claims.
+    let reward_balance = deps.querier.query_balance(
+        config.reward_contract.to_string(),
+        config.vault_denom.clone(),
+    )?;
+    let reward_fee = uint128_from_decimal(decimal_multiplication_in_256(
+        get_decimal_from_uint128(reward_balance.amount),
+        config.protocol_reward_fee,
+    ));
+    let reward_amount = reward_balance
+        .amount
+        .checked_sub(reward_fee)
+        .unwrap_or(Uint128::zero());
+    let transfer_msg = WasmMsg::Execute {
+        contract_addr: config.reward_contract.to_string(),
+        msg: to_binary(&RewardExecuteMsg::Transfer {
+            reward_amount,
+            reward_withdraw_contract: env.contract.address.clone(),
+            protocol_fees: split_protocol_fee(&config.protocol_fee_recipients, reward_fee),
+        })?,
+        funds: vec![],
+    };
+
+    let contract_balance = deps
+        .querier
+        .query_balance(env.contract.address.to_string(), config.vault_denom.clone())?;
+    let claimable_amount = contract_balance
+        .amount
+        .checked_add(reward_amount)
+        .unwrap()
+        .checked_sub(state.reconciled_funds_to_withdraw)
+        .unwrap_or(Uint128::zero())
+        .checked_sub(state.unclaimed_referral_fees)
+        .unwrap_or(Uint128::zero());
+    // Shares locked in the contract on the first deposit are never claimed.
+    let contract_shares = get_user_balance(
+        deps.querier,
+        config.cw20_token_contract.clone(),
+        env.contract.address.clone(),
+    )?;
+    let claimable_shares = state
+        .total_shares
+        .checked_sub(contract_shares)
+        .unwrap_or(Uint128::zero());
+
+    unwind.phase = UnwindPhase::Claimable;
+    unwind.claimable_amount = claimable_amount;
+    unwind.claimable_shares = claimable_shares;
+    unwind.claim_rate = if claimable_shares.is_zero() {
+        Decimal256::zero()
+    } else {
+        decimal256_from_ratio(claimable_amount, claimable_shares)
+    };
+
+    // Pending deposits are now part of the claimable amount.
+    state.pending_delegation = Uint128::zero();
+    state.total_staked = Uint128::zero();
+    state.exchange_rate = unwind.claim_rate;
+    STATE.save(deps.storage, &state)?;
+
+    Ok(transfer_msg)
+}
+
+fn get_unwind_phase_name(phase: &UnwindPhase) -> &'static str {
+    match phase {
+        UnwindPhase::Undelegating => "undelegating",
+        UnwindPhase::Unbonding => "unbonding",
+        UnwindPhase::Claimable => "claimable",
+    }
+}
+
+pub fn unwind_claim(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    shares: Uint128,
+    user_addr_str: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut unwind = UNWIND
+        .may_load(deps.storage)?
+        .filter(|unwind| unwind.phase == UnwindPhase::Claimable)
+        .ok_or(ContractError::UnwindNotClaimable {})?;
+    let user_addr = deps.api.addr_validate(user_addr_str.as_str())?;
+
+    let amount = multiply_uint128_with_decimal256(shares, unwind.claim_rate, Rounding::Floor);
+    unwind.claimed_shares = unwind.claimed_shares.checked_add(shares).unwrap();
+    unwind.claimed_amount = unwind.claimed_amount.checked_add(amount).unwrap();
+    UNWIND.save(deps.storage, &unwind)?;
+
+    let mut state = STATE.load(deps.storage)?;
+    state.total_shares = state
+        .total_shares
+        .checked_sub(shares)
+        .unwrap_or(Uint128::zero());
+    STATE.save(deps.storage, &state)?;
+
+    let mut response =
+        Response::new().add_message(burn_minted_tokens(config.cw20_token_contract, shares)?);
+    if !amount.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: user_addr.to_string(),
+            amount: vec![Coin::new(amount.u128(), config.vault_denom)],
+        });
+    }
+
+    Ok(response.add_attributes(vec![
+        attr("action", "unwind_claim"),
+        attr("user", user_addr),
+        attr("shares", shares),
+        attr("amount", amount),
+    ]))
+}
+
 // Slashing check not required
 pub fn withdraw_funds_to_wallet(
     deps: DepsMut,
@@ -1089,7 +2356,197 @@ pub fn withdraw_funds_to_wallet(
     let mut state = STATE.load(deps.storage)?;
     let user_addr = deps.api.addr_validate(info.sender.as_str())?;
     let funds_record = compute_withdrawable_funds(deps.storage.deref(), batch_id, &user_addr)?;
+    let msgs = get_withdrawal_msgs(&config, &mut state, &funds_record, &user_addr);
+
+    STATE.save(deps.storage, &state)?;
+    USERS.remove(deps.storage, (&user_addr, U64Key::new(batch_id)));
+    Ok(Response::new().add_messages(msgs))
+}
+
+// Unbonding NFTs are withdrawn by sending them here with SendNft. Staking then owns the NFT and can
+// burn it, so the holder doesn't need to approve staking first.
+pub fn receive_nft(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    cw721_msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let unbonding_nft_contract = config
+        .unbonding_nft_contract
+        .clone()
+        .ok_or(ContractError::UnbondingNftNotConfigured {})?;
+    // only the unbonding NFT contract can execute this message
+    if info.sender.ne(&unbonding_nft_contract) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    match from_binary(&cw721_msg.msg) {
+        Ok(Cw721HookMsg::WithdrawFundsToWallet {}) => {
+            let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+            if operation_controls.withdraw_paused {
+                return Err(ContractError::OperationPaused("withdraw".to_string()));
+            }
+
+            let mut state = STATE.load(deps.storage)?;
+            let user_addr = deps.api.addr_validate(cw721_msg.sender.as_str())?;
+            let token_id = cw721_msg.token_id;
+            let position = UNBONDING_NFTS
+                .may_load(deps.storage, token_id.as_str())?
+                .ok_or(ContractError::UndelegationEntryNotFound {})?;
+            let batch_id = position.batch_id;
+            let funds_record =
+                compute_undelegation_funds(deps.storage.deref(), batch_id, Some(position))?;
+            let msgs = get_withdrawal_msgs(&config, &mut state, &funds_record, &user_addr);
+
+            STATE.save(deps.storage, &state)?;
+            UNBONDING_NFTS.remove(deps.storage, token_id.as_str());
+            Ok(Response::new()
+                .add_messages(msgs)
+                .add_message(WasmMsg::Execute {
+                    contract_addr: unbonding_nft_contract.to_string(),
+                    msg: to_binary(&Cw721ExecuteMsg::Burn {
+                        token_id: token_id.clone(),
+                    })?,
+                    funds: vec![],
+                })
+                .add_attributes(vec![
+                    attr("action", "withdraw_unbonding_nft"),
+                    attr("user", user_addr),
+                    attr("batch_id", batch_id.to_string()),
+                    attr("unbonding_nft", token_id),
+                ]))
+        }
+        Err(_err) => Err(ContractError::NoOp {}),
+    }
+}
+
+pub fn withdraw_for(
+    deps: DepsMut,
+    _info: MessageInfo,
+    _env: Env,
+    user_addr_str: String,
+    batch_id: u64,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    if operation_controls.withdraw_paused {
+        return Err(ContractError::OperationPaused("withdraw".to_string()));
+    }
+
+    let mut state = STATE.load(deps.storage)?;
+    let user_addr = deps
+        .api
+        .addr_validate(user_addr_str.to_lowercase().as_str())?;
+    let funds_record = compute_withdrawable_funds(deps.storage.deref(), batch_id, &user_addr)?;
+    let msgs = get_withdrawal_msgs(&config, &mut state, &funds_record, &user_addr);
+
+    STATE.save(deps.storage, &state)?;
+    USERS.remove(deps.storage, (&user_addr, U64Key::new(batch_id)));
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "withdraw_for"),
+        attr("user", user_addr),
+        attr("batch_id", batch_id.to_string()),
+    ]))
+}
+
+pub fn push_batch_payouts(
+    deps: DepsMut,
+    _info: MessageInfo,
+    _env: Env,
+    batch_id: u64,
+    limit: Option<u64>,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let operation_controls = OPERATION_CONTROLS.load(deps.storage)?;
+    if operation_controls.withdraw_paused {
+        return Err(ContractError::OperationPaused("withdraw".to_string()));
+    }
+
+    let batch = BATCH_UNDELEGATION_REGISTRY
+        .may_load(deps.storage, U64Key::new(batch_id))?
+        .ok_or(ContractError::UndelegationBatchNotFound {})?;
+    if !batch.reconciled {
+        return Err(ContractError::UndelegationBatchNotReconciled {});
+    }
+
+    let limit = limit.unwrap_or(10).min(20) as usize;
+    let queued_users = AUTO_CLAIM_QUEUE
+        .prefix(U64Key::new(batch_id))
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(limit)
+        .map(|key| String::from_utf8(key).map(Addr::unchecked))
+        .collect::<Result<Vec<Addr>, _>>()
+        .map_err(StdError::invalid_utf8)?;
+
+    let mut state = STATE.load(deps.storage)?;
     let mut msgs = vec![];
+    let mut paid_users = 0_u64;
+    for user_addr in queued_users {
+        AUTO_CLAIM_QUEUE.remove(deps.storage, (U64Key::new(batch_id), &user_addr));
+        // Users who withdrew on their own or turned auto-claim off since queueing are skipped.
+        if !AUTO_CLAIM_USERS.has(deps.storage, &user_addr)
+            || !USERS.has(deps.storage, (&user_addr, U64Key::new(batch_id)))
+        {
+            continue;
+        }
+
+        let funds_record = compute_withdrawable_funds(deps.storage.deref(), batch_id, &user_addr)?;
+        msgs.extend(get_withdrawal_msgs(
+            &config,
+            &mut state,
+            &funds_record,
+            &user_addr,
+        ));
+        USERS.remove(deps.storage, (&user_addr, U64Key::new(batch_id)));
+        paid_users += 1;
+    }
+    STATE.save(deps.storage, &state)?;
+
+    Ok(Response::new().add_messages(msgs).add_attributes(vec![
+        attr("action", "push_batch_payouts"),
+        attr("batch_id", batch_id.to_string()),
+        attr("paid_users", paid_users.to_string()),
+    ]))
+}
+
+pub fn set_auto_claim(
+    deps: DepsMut,
+    info: MessageInfo,
+    _env: Env,
+    enabled: bool,
+) -> Result<Response, ContractError> {
+    if enabled {
+        AUTO_CLAIM_USERS.save(deps.storage, &info.sender, &true)?;
+        // Undelegations queued before opting in are picked up by PushBatchPayouts as well.
+        let batch_ids = USERS
+            .prefix(&info.sender)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, undelegation_info)| undelegation_info.batch_id))
+            .collect::<StdResult<Vec<u64>>>()?;
+        for batch_id in batch_ids {
+            AUTO_CLAIM_QUEUE.save(deps.storage, (U64Key::new(batch_id), &info.sender), &true)?;
+        }
+    } else {
+        AUTO_CLAIM_USERS.remove(deps.storage, &info.sender);
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        attr("action", "set_auto_claim"),
+        attr("user", info.sender),
+        attr("enabled", enabled.to_string()),
+    ]))
+}
+
+// Moves the claim out of the reconciled funds and pays the user, the fee recipients or the pool.
+fn get_withdrawal_msgs(
+    config: &Config,
+    state: &mut State,
+    funds_record: &GetFundsClaimRecord,
+    user_addr: &Addr,
+) -> Vec<BankMsg> {
+    let mut msgs = vec![];
+    state.rounding_dust += funds_record.rounding_dust;
 
     if !funds_record.user_withdrawal_amount.is_zero() {
         state.reconciled_funds_to_withdraw = state
@@ -1109,18 +2566,26 @@ pub fn withdraw_funds_to_wallet(
             .reconciled_funds_to_withdraw
             .checked_sub(funds_record.protocol_fee)
             .unwrap_or(Uint128::zero());
-        msgs.push(BankMsg::Send {
-            to_address: config.protocol_fee_contract.to_string(),
-            amount: vec![Coin::new(
-                funds_record.protocol_fee.u128(),
-                config.vault_denom,
-            )],
-        });
+        msgs.extend(get_protocol_fee_msgs(config, funds_record.protocol_fee));
+    }
+    if !funds_record.pool_fee.is_zero() {
+        // The fee goes back into the pool and is delegated with the next DelegatePending.
+        state.reconciled_funds_to_withdraw = state
+            .reconciled_funds_to_withdraw
+            .checked_sub(funds_record.pool_fee)
+            .unwrap_or(Uint128::zero());
+        state.pending_delegation = state
+            .pending_delegation
+            .checked_add(funds_record.pool_fee)
+            .unwrap();
+        state.total_staked = state
+            .total_staked
+            .checked_add(funds_record.pool_fee)
+            .unwrap();
+        state.exchange_rate = calculate_exchange_rate(state.total_staked, state.total_shares);
     }
 
-    STATE.save(deps.storage, &state)?;
-    USERS.remove(deps.storage, (&user_addr, U64Key::new(batch_id)));
-    Ok(Response::new().add_messages(msgs))
+    msgs
 }
 
 // Does not change any state. Used for both messages & queries
@@ -1128,6 +2593,16 @@ pub fn compute_withdrawable_funds(
     storage: &dyn Storage,
     batch_id: u64,
     user_addr: &Addr,
+) -> Result<GetFundsClaimRecord, ContractError> {
+    let key = (user_addr, U64Key::from(batch_id));
+    compute_undelegation_funds(storage, batch_id, USERS.may_load(storage, key)?)
+}
+
+// Shared by undelegations recorded for a wallet and those held as unbonding NFTs.
+pub fn compute_undelegation_funds(
+    storage: &dyn Storage,
+    batch_id: u64,
+    user_undelegated_tokens_opt: Option<UndelegationInfo>,
 ) -> Result<GetFundsClaimRecord, ContractError> {
     let config = CONFIG.load(storage)?;
 
@@ -1141,27 +2616,47 @@ pub fn compute_withdrawable_funds(
         return Err(ContractError::UndelegationBatchNotReconciled {});
     }
 
-    let key = (user_addr, U64Key::from(batch_id));
-    let user_undelegated_tokens_opt = USERS.may_load(storage, key)?;
     if user_undelegated_tokens_opt.is_none() {
         return Err(ContractError::UndelegationEntryNotFound {});
     }
     let user_undelegation = user_undelegated_tokens_opt.unwrap();
-    let user_undelegated_amount = multiply_u128_with_decimal(
-        user_undelegation.token_amount.u128(),
+    let user_undelegated_amount = multiply_uint128_with_decimal256(
+        user_undelegation.token_amount,
         und_batch.undelegation_er,
+        Rounding::Floor,
     );
+    let rounding_dust = get_rounding_dust(
+        get_decimal256_from_uint128(user_undelegation.token_amount) * und_batch.undelegation_er,
+        user_undelegated_amount,
+    );
+    let user_undelegated_amount = user_undelegated_amount.u128();
+
+    // Luna owed to the user rounds down and the withdraw fee rounds up, so both favour the pool.
+    let claimable_amount = multiply_uint128_with_decimal256(
+        Uint128::new(user_undelegated_amount),
+        Decimal256::from(und_batch.unbonding_slashing_ratio),
+        Rounding::Floor,
+    )
+    .u128();
+
+    let withdraw_fee = multiply_uint128_with_decimal256(
+        Uint128::new(claimable_amount),
+        Decimal256::from(config.protocol_withdraw_fee),
+        Rounding::Ceil,
+    )
+    .u128();
+    let (protocol_fee, pool_fee) = match config.fee_destination_policy.withdraw_fee {
+        FeeDestination::ProtocolFeeRecipients => (withdraw_fee, 0_u128),
+        FeeDestination::Pool => (0_u128, withdraw_fee),
+    };
 
-    let claimable_amount =
-        multiply_u128_with_decimal(user_undelegated_amount, und_batch.unbonding_slashing_ratio);
-
-    let protocol_fee = multiply_u128_with_decimal(claimable_amount, config.protocol_withdraw_fee);
-
-    let user_withdrawal_amount = claimable_amount.checked_sub(protocol_fee).unwrap_or(0_u128);
+    let user_withdrawal_amount = claimable_amount.checked_sub(withdraw_fee).unwrap_or(0_u128);
     Ok(GetFundsClaimRecord {
         user_withdrawal_amount: Uint128::new(user_withdrawal_amount),
         protocol_fee: Uint128::new(protocol_fee),
+        pool_fee: Uint128::new(pool_fee),
         undelegated_tokens: user_undelegation.token_amount,
+        rounding_dust,
     })
 }
 
@@ -1169,7 +2664,7 @@ pub fn compute_withdrawable_funds(
 pub fn claim_airdrops(
     deps: DepsMut,
     _info: MessageInfo,
-    _env: Env,
+    env: Env,
     airdrop_rates: Vec<AirdropRate>,
 ) -> Result<Response, ContractError> {
     let config = CONFIG.load(deps.storage)?;
@@ -1179,17 +2674,26 @@ pub fn claim_airdrops(
     }
 
     let mut msgs = vec![];
-    let airdrop_withdrawal_contract = config.airdrop_withdrawal_contract;
+    let mut claimed_stages: Vec<(String, u8)> = vec![];
     let airdrops_registry_contract = config.airdrop_registry_contract;
     for rate in airdrop_rates {
         if rate.amount.is_zero() {
             continue;
         }
 
+        // Stages claimed before, or twice in this call, would only fail at the airdrop contract.
+        let denom = rate.denom.to_lowercase();
+        if claimed_stages.contains(&(denom.clone(), rate.stage))
+            || AIRDROP_CLAIM_HISTORY.has(deps.storage, (denom.as_str(), U8Key::new(rate.stage)))
+        {
+            return Err(ContractError::AirdropStageAlreadyClaimed(denom, rate.stage));
+        }
+        claimed_stages.push((denom.clone(), rate.stage));
+
         let contract_response: GetAirdropContractsResponse = get_airdrop_contracts(
             deps.querier,
             airdrops_registry_contract.clone(),
-            rate.denom.to_lowercase().clone(),
+            denom.clone(),
         )?;
 
         let contracts = if let Some(contracts) = contract_response.contracts {
@@ -1197,32 +2701,103 @@ pub fn claim_airdrops(
         } else {
             return Err(ContractError::AirdropNotRegistered(rate.denom));
         };
+        if !contracts.enabled {
+            return Err(ContractError::AirdropDisabled(rate.denom));
+        }
 
-        let claim_msg = to_binary(&MerkleAirdropMsg::Claim {
-            stage: rate.stage,
-            amount: rate.amount,
-            proof: rate.proof,
-        })?;
-        msgs.push(WasmMsg::Execute {
-            contract_addr: contracts.airdrop_contract.to_string(),
-            msg: claim_msg,
-            funds: vec![],
-        });
-        msgs.push(WasmMsg::Execute {
-            contract_addr: contracts.cw20_contract.to_string(),
-            msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                recipient: airdrop_withdrawal_contract.to_string(),
-                amount: rate.amount,
-            })?,
-            funds: vec![],
-        })
+        // The reply forwards what the claim actually paid out, so the balance is taken before it runs.
+        // Claims that fail are reported by the reply instead of reverting the other claims.
+        let reply_id = msgs.len() as u64;
+        PENDING_AIRDROP_CLAIMS.save(
+            deps.storage,
+            U64Key::new(reply_id),
+            &PendingAirdropClaim {
+                denom,
+                stage: rate.stage,
+                asset: contracts.asset.clone(),
+                balance_before: get_airdrop_balance(
+                    deps.querier,
+                    &contracts.asset,
+                    env.contract.address.clone(),
+                )?,
+            },
+        )?;
+        msgs.push(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: contracts.airdrop_contract.to_string(),
+                msg: get_airdrop_claim_msg(&contracts.claim_format, &rate)?,
+                funds: vec![],
+            },
+            reply_id,
+        ));
     }
 
-    Ok(Response::new().add_messages(msgs))
+    Ok(Response::new().add_submessages(msgs))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    let reply_key = U64Key::new(msg.id);
+    let pending_claim = PENDING_AIRDROP_CLAIMS
+        .may_load(deps.storage, reply_key.clone())?
+        .ok_or(ContractError::UnknownReplyId(msg.id))?;
+    PENDING_AIRDROP_CLAIMS.remove(deps.storage, reply_key);
+
+    if let ContractResult::Err(err) = msg.result {
+        return Ok(Response::new().add_attributes(vec![
+            attr("action", "airdrop_claim_failed"),
+            attr("denom", pending_claim.denom),
+            attr("stage", pending_claim.stage.to_string()),
+            attr("error", err),
+        ]));
+    }
+
+    let balance_after = get_airdrop_balance(
+        deps.querier,
+        &pending_claim.asset,
+        env.contract.address.clone(),
+    )?;
+    let claimed_amount = balance_after
+        .checked_sub(pending_claim.balance_before)
+        .unwrap_or(Uint128::zero());
+    AIRDROP_CLAIM_HISTORY.save(
+        deps.storage,
+        (
+            pending_claim.denom.as_str(),
+            U8Key::new(pending_claim.stage),
+        ),
+        &ClaimedAirdropStage {
+            stage: pending_claim.stage,
+            amount: claimed_amount,
+            claimed_at: env.block.time,
+        },
+    )?;
+    let mut response = Response::new().add_attributes(vec![
+        attr("action", "airdrop_claimed"),
+        attr("denom", pending_claim.denom.clone()),
+        attr("stage", pending_claim.stage.to_string()),
+        attr("amount", claimed_amount),
+    ]);
+    if claimed_amount.is_zero() {
+        return Ok(response);
+    }
+
+    AIRDROPS_CLAIMED.update(
+        deps.storage,
+        pending_claim.denom.as_str(),
+        |x| -> StdResult<_> { Ok(x.unwrap_or_default().checked_add(claimed_amount)?) },
+    )?;
+    let config = CONFIG.load(deps.storage)?;
+    response = response.add_message(get_airdrop_transfer_msg(
+        pending_claim.asset,
+        config.airdrop_withdrawal_contract,
+        claimed_amount,
+    )?);
+    Ok(response)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::State {} => to_binary(&query_state(deps)?),
@@ -1242,17 +2817,125 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         )?),
         QueryMsg::GetValMeta { val_addr } => to_binary(&query_val_meta(deps, val_addr)?),
         QueryMsg::GetUserInfo { user_addr } => to_binary(&query_user_info(deps, user_addr)?),
-        QueryMsg::ComputeDepositBreakdown { amount } => {
-            to_binary(&query_compute_deposit_breakdown(deps, amount)?)
+        QueryMsg::ComputeDepositBreakdown {
+            amount,
+            referral_code,
+        } => to_binary(&query_compute_deposit_breakdown(
+            deps,
+            env,
+            amount,
+            referral_code,
+        )?),
+        QueryMsg::ReferralCode { code } => to_binary(&query_referral_code(deps, code)?),
+        QueryMsg::AirdropsClaimed { denom } => to_binary(&query_airdrops_claimed(deps, denom)?),
+        QueryMsg::AirdropClaimHistory { denom } => {
+            to_binary(&query_airdrop_claim_history(deps, denom)?)
         }
         QueryMsg::GetUserUndelegationInfo {
             user_addr,
             batch_id,
         } => to_binary(&query_user_undelegation_info(deps, user_addr, batch_id)?),
         QueryMsg::TmpManagerStore {} => to_binary(&query_manager_tmp_store(deps)?),
+        QueryMsg::UnbondingNft { token_id } => to_binary(&query_unbonding_nft(deps, token_id)?),
+        QueryMsg::AutoClaim { user_addr } => to_binary(&query_auto_claim(deps, user_addr)?),
+        QueryMsg::UnwindState {} => to_binary(&query_unwind_state(deps)?),
+        QueryMsg::CircuitBreaker {} => to_binary(&query_circuit_breaker(deps)?),
+        QueryMsg::PauseInfo {} => to_binary(&query_pause_info(deps)?),
+        QueryMsg::AdminActions { start_after, limit } => {
+            to_binary(&query_admin_actions(deps, start_after, limit)?)
+        }
+        QueryMsg::ComputeUnwindClaim { shares } => {
+            to_binary(&query_compute_unwind_claim(deps, shares)?)
+        }
+        QueryMsg::SharesInvariant {} => to_binary(&query_shares_invariant(deps)?),
+        QueryMsg::RoundingDust {} => to_binary(&query_rounding_dust(deps)?),
     }
 }
 
+pub fn query_unbonding_nft(deps: Deps, token_id: String) -> StdResult<UnbondingNftResponse> {
+    let position = UNBONDING_NFTS.may_load(deps.storage, token_id.as_str())?;
+    Ok(UnbondingNftResponse { position })
+}
+
+pub fn query_auto_claim(deps: Deps, user_addr: String) -> StdResult<AutoClaimResponse> {
+    let user_addr = deps.api.addr_validate(user_addr.to_lowercase().as_str())?;
+    Ok(AutoClaimResponse {
+        enabled: AUTO_CLAIM_USERS.has(deps.storage, &user_addr),
+    })
+}
+
+pub fn query_pause_info(deps: Deps) -> StdResult<PauseInfoResponse> {
+    let pauses = PAUSE_INFO
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, pause_info)| pause_info))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(PauseInfoResponse { pauses })
+}
+
+pub fn query_admin_actions(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u64>,
+) -> StdResult<AdminActionsResponse> {
+    let limit = limit.unwrap_or(10).min(20) as usize;
+    let start = start_after.map(|id| Bound::exclusive(U64Key::new(id)));
+
+    let actions = ADMIN_ACTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, action)| action))
+        .collect::<StdResult<Vec<AdminAction>>>()?;
+    Ok(AdminActionsResponse { actions })
+}
+
+pub fn query_circuit_breaker(deps: Deps) -> StdResult<CircuitBreakerResponse> {
+    let circuit_breaker = CIRCUIT_BREAKER.may_load(deps.storage)?;
+    Ok(CircuitBreakerResponse { circuit_breaker })
+}
+
+pub fn query_unwind_state(deps: Deps) -> StdResult<UnwindStateResponse> {
+    let unwind = UNWIND.may_load(deps.storage)?;
+    Ok(UnwindStateResponse { unwind })
+}
+
+pub fn query_compute_unwind_claim(
+    deps: Deps,
+    shares: Uint128,
+) -> StdResult<ComputeUnwindClaimResponse> {
+    let unwind = UNWIND
+        .may_load(deps.storage)?
+        .filter(|unwind| unwind.phase == UnwindPhase::Claimable)
+        .ok_or_else(|| StdError::generic_err("Unwind has not been finalized"))?;
+    Ok(ComputeUnwindClaimResponse {
+        amount: multiply_uint128_with_decimal256(shares, unwind.claim_rate, Rounding::Floor),
+    })
+}
+
+pub fn query_rounding_dust(deps: Deps) -> StdResult<RoundingDustResponse> {
+    let state = STATE.load(deps.storage)?;
+    Ok(RoundingDustResponse {
+        rounding_dust: state.rounding_dust,
+        rounding_dust_amount: multiply_uint128_with_decimal256(
+            Uint128::new(1_u128),
+            state.rounding_dust,
+            Rounding::Floor,
+        ),
+    })
+}
+
+// Compares the internally tracked shares against the cw20 total supply.
+pub fn query_shares_invariant(deps: Deps) -> StdResult<SharesInvariantResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let state = STATE.load(deps.storage)?;
+
+    let cw20_total_supply = get_total_token_supply(deps.querier, config.cw20_token_contract)?;
+    Ok(SharesInvariantResponse {
+        total_shares: state.total_shares,
+        cw20_total_supply,
+        is_consistent: state.total_shares.eq(&cw20_total_supply),
+    })
+}
+
 pub fn query_manager_tmp_store(deps: Deps) -> StdResult<TmpManagerStoreResponse> {
     let tmp_manager_store = TMP_MANAGER_STORE.may_load(deps.storage)?;
     Ok(TmpManagerStoreResponse { tmp_manager_store })
@@ -1269,12 +2952,13 @@ pub fn query_user_info(deps: Deps, user_addr: String) -> StdResult<UserInfoRespo
     let state = STATE.load(deps.storage)?;
 
     let user_token_balance = get_user_balance(deps.querier, config.cw20_token_contract, user_addr)?;
-    let user_amount = state.exchange_rate.mul(user_token_balance);
+    let user_amount =
+        multiply_uint128_with_decimal256(user_token_balance, state.exchange_rate, Rounding::Floor);
 
     Ok(UserInfoResponse {
         user_info: UserQueryInfo {
             total_tokens: user_token_balance,
-            total_amount: Coin::new(user_amount.u128(), "uluna".to_string()),
+            total_amount: Coin::new(user_amount.u128(), config.vault_denom),
         },
     })
 }
@@ -1344,11 +3028,42 @@ pub fn query_user_undelegation_info(
     Ok(funds_record)
 }
 
+pub fn query_airdrops_claimed(deps: Deps, denom: String) -> StdResult<AirdropsClaimedResponse> {
+    let amount = AIRDROPS_CLAIMED
+        .may_load(deps.storage, denom.as_str())?
+        .unwrap_or_default();
+    Ok(AirdropsClaimedResponse { denom, amount })
+}
+
+pub fn query_airdrop_claim_history(
+    deps: Deps,
+    denom: String,
+) -> StdResult<AirdropClaimHistoryResponse> {
+    let denom = denom.to_lowercase();
+    let claimed_stages = AIRDROP_CLAIM_HISTORY
+        .prefix(denom.as_str())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, claimed_stage)| claimed_stage))
+        .collect::<StdResult<Vec<ClaimedAirdropStage>>>()?;
+    Ok(AirdropClaimHistoryResponse {
+        denom,
+        claimed_stages,
+    })
+}
+
+pub fn query_referral_code(deps: Deps, code: String) -> StdResult<ReferralCodeResponse> {
+    Ok(ReferralCodeResponse {
+        referral_info: REFERRAL_CODES.may_load(deps.storage, code.as_str())?,
+    })
+}
+
 pub fn query_compute_deposit_breakdown(
     deps: Deps,
+    env: Env,
     amount: Uint128,
+    referral_code: Option<String>,
 ) -> StdResult<GetFundsDepositRecord> {
-    let res = compute_deposit_breakdown(deps.storage, amount);
+    let res = compute_deposit_breakdown(deps, &env, amount, referral_code.as_deref());
     if res.is_err() {
         return Err(StdError::GenericErr {
             msg: "Error in computing the deposit breakdown".to_string(),
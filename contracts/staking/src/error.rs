@@ -33,6 +33,15 @@ pub enum ContractError {
     #[error("Staking-Contract: Airdrop is not registered `{0}`")]
     AirdropNotRegistered(String),
 
+    #[error("Staking-Contract: Airdrop is disabled in the registry `{0}`")]
+    AirdropDisabled(String),
+
+    #[error("Staking-Contract: Airdrop `{0}` has already been claimed for stage {1}")]
+    AirdropStageAlreadyClaimed(String, u8),
+
+    #[error("Staking-Contract: No pending airdrop claim for reply id `{0}`")]
+    UnknownReplyId(u64),
+
     #[error("Staking-Contract: Amount cannot be zero")]
     ZeroAmount {},
 
@@ -75,6 +84,12 @@ pub enum ContractError {
     #[error("Staking-Contract: Deposit amount cannot be less than min deposit amount")]
     MinDeposit {},
 
+    #[error("Staking-Contract: Deposit is too small to mint any tokens")]
+    ZeroMint {},
+
+    #[error("Staking-Contract: Deposits are closed until rewards are reinvested")]
+    DepositInReinvestWindow {},
+
     #[error("Staking-Contract: All validators in the pool are inactive/jailed")]
     AllValidatorsJailed {},
 
@@ -99,6 +114,54 @@ pub enum ContractError {
     #[error("Staking-Contract: Reinvest is in cooldown")]
     ReinvestInCooldown {},
 
+    #[error("Staking-Contract: Delegate pending is in cooldown")]
+    DelegatePendingInCooldown {},
+
     #[error("Staking-Contract: Tmp manager store is empty")]
     TmpManagerStoreEmpty {},
+
+    #[error("Staking-Contract: Protocol fee recipients must be distinct and their weights must add up to 100%")]
+    InvalidProtocolFeeRecipients {},
+
+    #[error("Staking-Contract: Referral fee share cannot be more than 100%")]
+    InvalidReferralFeeShare {},
+
+    #[error("Staking-Contract: Referral codes are 1-32 characters of letters, digits, '-' or '_'")]
+    InvalidReferralCode {},
+
+    #[error("Staking-Contract: Referral code is already registered")]
+    ReferralCodeAlreadyRegistered {},
+
+    #[error("Staking-Contract: Referral code not found")]
+    ReferralCodeNotFound {},
+
+    #[error("Staking-Contract: Cannot deposit with a referral code you own")]
+    SelfReferral {},
+
+    #[error("Staking-Contract: Unbonding NFT contract is not configured")]
+    UnbondingNftNotConfigured {},
+
+    #[error("Staking-Contract: Deposits and undelegations cannot be resumed while the pool is unwinding")]
+    UnwindInProgress {},
+
+    #[error("Staking-Contract: Undelegate the open undelegation batch before starting the unwind")]
+    UnwindOpenBatchNotEmpty {},
+
+    #[error("Staking-Contract: Undelegated stake has not been released yet")]
+    UnwindFundsNotReleased {},
+
+    #[error("Staking-Contract: Reconcile all undelegation batches before finalizing the unwind")]
+    UnwindBatchesNotReconciled {},
+
+    #[error("Staking-Contract: Unwind has not been finalized")]
+    UnwindNotClaimable {},
+
+    #[error("Staking-Contract: Unwind has already been finalized")]
+    UnwindAlreadyFinalized {},
+
+    #[error("Staking-Contract: Circuit breaker rate drop limits cannot exceed 1")]
+    InvalidCircuitBreakerPolicy {},
+
+    #[error("Staking-Contract: Circuit breaker has not tripped")]
+    CircuitBreakerNotTripped {},
 }
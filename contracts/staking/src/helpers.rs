@@ -1,19 +1,30 @@
 #![allow(dead_code)]
 
+use crate::constants::get_max_referral_code_length;
+use crate::msg::{MerkleAirdropMsg, MerkleAirdropWithoutStageMsg, PlainAirdropMsg};
 use crate::state::{
-    BatchUndelegationRecord, Config, VMeta, BATCH_UNDELEGATION_REGISTRY, STATE, VALIDATOR_META,
+    AdminAction, AdminActionChange, AirdropRate, BatchUndelegationRecord, Config,
+    OperationControls, PauseInfo, ProtocolFeeRecipient, VMeta, ADMIN_ACTIONS, ADMIN_ACTION_COUNT,
+    BATCH_UNDELEGATION_REGISTRY, PAUSE_INFO, STATE, VALIDATOR_META,
 };
 use crate::ContractError;
 use airdrops_registry::msg::GetAirdropContractsResponse;
 use airdrops_registry::msg::QueryMsg as AirdropsQueryMsg;
+use airdrops_registry::state::{AirdropAsset, ClaimFormat};
+use cosmwasm_bignumber::Decimal256;
 use cosmwasm_std::{
-    to_binary, Addr, Decimal, DepsMut, Env, MessageInfo, QuerierWrapper, StdResult, Storage,
-    Uint128, WasmMsg,
+    to_binary, to_vec, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, DepsMut, Env,
+    MessageInfo, QuerierWrapper, StdError, StdResult, Storage, Timestamp, Uint128, WasmMsg,
 };
 use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse};
 use cw_storage_plus::U64Key;
+use reward::msg::ProtocolFee;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use stader_utils::coin_utils::{
+    decimal256_from_ratio, decimal_multiplication_in_256, decimal_summation_in_256,
+    get_decimal256_from_uint128, get_decimal_from_uint128, uint128_from_decimal,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub enum Verify {
@@ -128,6 +139,104 @@ pub fn get_active_validators_sorted_by_stake(
     Ok(stake_tuples)
 }
 
+// Deposits at or below the threshold go to the least staked validator. Larger deposits are spread over
+// the active validators in proportion to how far each one is below an even share of the new total stake.
+pub fn get_deposit_allocations(
+    querier: QuerierWrapper,
+    validator_contract: Addr,
+    validators: Vec<Addr>,
+    amount: Uint128,
+    split_threshold: Option<Uint128>,
+) -> Result<Vec<(Addr, Uint128)>, ContractError> {
+    if split_threshold.is_none() || amount.le(&split_threshold.unwrap()) {
+        let val_addr = get_validator_for_deposit(querier, validator_contract, validators)?;
+        return Ok(vec![(val_addr, amount)]);
+    }
+
+    let stake_tuples =
+        get_active_validators_sorted_by_stake(querier, validator_contract, validators)?;
+    let total_stake = stake_tuples
+        .iter()
+        .fold(amount, |acc, x| acc.checked_add(x.0).unwrap());
+    let target_stake = total_stake.multiply_ratio(1_u128, stake_tuples.len() as u128);
+
+    let mut weights = stake_tuples
+        .iter()
+        .map(|x| {
+            target_stake
+                .checked_sub(x.0)
+                .unwrap_or_else(|_| Uint128::zero())
+        })
+        .collect::<Vec<Uint128>>();
+    let mut total_weight = weights
+        .iter()
+        .fold(Uint128::zero(), |acc, x| acc.checked_add(*x).unwrap());
+    if total_weight.is_zero() {
+        // All validators are already at the target. Spread evenly.
+        weights = vec![Uint128::new(1_u128); stake_tuples.len()];
+        total_weight = Uint128::new(stake_tuples.len() as u128);
+    }
+
+    let mut allocations = vec![];
+    let mut allocated = Uint128::zero();
+    for (index, tuple_val) in stake_tuples.iter().enumerate() {
+        let val_amount = amount.multiply_ratio(weights[index], total_weight);
+        allocated = allocated.checked_add(val_amount).unwrap();
+        allocations.push((Addr::unchecked(tuple_val.1.clone()), val_amount));
+    }
+    // Rounding leftovers go to the least staked validator.
+    allocations[0].1 = allocations[0]
+        .1
+        .checked_add(amount.checked_sub(allocated).unwrap())
+        .unwrap();
+
+    Ok(allocations.into_iter().filter(|x| !x.1.is_zero()).collect())
+}
+
+// Undelegations at or below the threshold drain the largest delegations first. Larger undelegations are
+// taken from every active validator in proportion to its current stake.
+// stake_tuples are expected to be sorted in ascending order of stake.
+pub fn get_undelegation_allocations(
+    stake_tuples: &[(Uint128, String)],
+    amount: Uint128,
+    split_threshold: Option<Uint128>,
+) -> Result<Vec<(Addr, Uint128)>, ContractError> {
+    let total_stake = stake_tuples
+        .iter()
+        .fold(Uint128::zero(), |acc, x| acc.checked_add(x.0).unwrap());
+    if total_stake.lt(&amount) {
+        return Err(ContractError::InSufficientFunds {});
+    }
+
+    let mut amounts = vec![Uint128::zero(); stake_tuples.len()];
+    let mut to_undelegate = amount;
+    if split_threshold.is_some() && amount.gt(&split_threshold.unwrap()) {
+        for (index, tuple_val) in stake_tuples.iter().enumerate() {
+            amounts[index] = amount.multiply_ratio(tuple_val.0, total_stake);
+            to_undelegate = to_undelegate.checked_sub(amounts[index]).unwrap();
+        }
+    }
+
+    // Whatever is left is drained from the largest delegations first.
+    for index in (0..stake_tuples.len()).rev() {
+        if to_undelegate.is_zero() {
+            break;
+        }
+        let available = stake_tuples[index].0.checked_sub(amounts[index]).unwrap();
+        let val_amount = std::cmp::min(to_undelegate, available);
+        amounts[index] = amounts[index].checked_add(val_amount).unwrap();
+        to_undelegate = to_undelegate.checked_sub(val_amount).unwrap();
+    }
+
+    Ok(stake_tuples
+        .iter()
+        .zip(amounts)
+        .rev()
+        .filter(|x| !x.1.is_zero())
+        .map(|x| (Addr::unchecked(x.0 .1.clone()), x.1))
+        .collect())
+}
+
 pub fn create_new_undelegation_batch(
     storage: &mut dyn Storage,
     env: Env,
@@ -152,6 +261,264 @@ pub fn create_new_undelegation_batch(
     Ok(())
 }
 
+// Every pausable operation with its current flag. Names are the flag fields without the "_paused" suffix.
+pub fn get_operation_flags(operation_controls: &OperationControls) -> Vec<(&'static str, bool)> {
+    vec![
+        ("deposit", operation_controls.deposit_paused),
+        (
+            "queue_undelegate",
+            operation_controls.queue_undelegate_paused,
+        ),
+        ("undelegate", operation_controls.undelegate_paused),
+        ("withdraw", operation_controls.withdraw_paused),
+        ("reinvest", operation_controls.reinvest_paused),
+        ("reconcile", operation_controls.reconcile_paused),
+        ("claim_airdrops", operation_controls.claim_airdrops_paused),
+        ("redeem_rewards", operation_controls.redeem_rewards_paused),
+        ("swap", operation_controls.swap_paused),
+        (
+            "reimburse_slashing",
+            operation_controls.reimburse_slashing_paused,
+        ),
+        (
+            "delegate_pending",
+            operation_controls.delegate_pending_paused,
+        ),
+    ]
+}
+
+// Records who paused each newly paused operation and forgets the operations that were unpaused.
+pub fn update_pause_info(
+    storage: &mut dyn Storage,
+    old: &OperationControls,
+    new: &OperationControls,
+    paused_by: &Addr,
+    paused_at: Timestamp,
+    reason: Option<String>,
+) -> StdResult<()> {
+    for ((operation, was_paused), (_, paused)) in get_operation_flags(old)
+        .into_iter()
+        .zip(get_operation_flags(new))
+    {
+        if paused && !was_paused {
+            PAUSE_INFO.save(
+                storage,
+                operation,
+                &PauseInfo {
+                    operation: operation.to_string(),
+                    paused_by: paused_by.clone(),
+                    paused_at,
+                    reason: reason.clone(),
+                },
+            )?;
+        } else if !paused && was_paused {
+            PAUSE_INFO.remove(storage, operation);
+        }
+    }
+    Ok(())
+}
+
+fn to_json_string<T: Serialize>(value: &T) -> StdResult<String> {
+    String::from_utf8(to_vec(value)?).map_err(|e| StdError::invalid_utf8(e.to_string()))
+}
+
+// Adds the field to an admin action's diff if its value changed.
+pub fn add_change<T: Serialize + PartialEq>(
+    diff: &mut Vec<AdminActionChange>,
+    field: &str,
+    old: &T,
+    new: &T,
+) -> StdResult<()> {
+    if old.ne(new) {
+        diff.push(AdminActionChange {
+            field: field.to_string(),
+            old_value: to_json_string(old)?,
+            new_value: to_json_string(new)?,
+        });
+    }
+    Ok(())
+}
+
+pub fn get_config_diff(old: &Config, new: &Config) -> StdResult<Vec<AdminActionChange>> {
+    let mut diff = vec![];
+    add_change(&mut diff, "manager", &old.manager, &new.manager)?;
+    add_change(&mut diff, "vault_denom", &old.vault_denom, &new.vault_denom)?;
+    add_change(&mut diff, "min_deposit", &old.min_deposit, &new.min_deposit)?;
+    add_change(&mut diff, "max_deposit", &old.max_deposit, &new.max_deposit)?;
+    add_change(&mut diff, "active", &old.active, &new.active)?;
+    add_change(
+        &mut diff,
+        "reward_contract",
+        &old.reward_contract,
+        &new.reward_contract,
+    )?;
+    add_change(
+        &mut diff,
+        "cw20_token_contract",
+        &old.cw20_token_contract,
+        &new.cw20_token_contract,
+    )?;
+    add_change(
+        &mut diff,
+        "airdrop_registry_contract",
+        &old.airdrop_registry_contract,
+        &new.airdrop_registry_contract,
+    )?;
+    add_change(
+        &mut diff,
+        "airdrop_withdrawal_contract",
+        &old.airdrop_withdrawal_contract,
+        &new.airdrop_withdrawal_contract,
+    )?;
+    add_change(
+        &mut diff,
+        "unbonding_nft_contract",
+        &old.unbonding_nft_contract,
+        &new.unbonding_nft_contract,
+    )?;
+    add_change(
+        &mut diff,
+        "protocol_fee_recipients",
+        &old.protocol_fee_recipients,
+        &new.protocol_fee_recipients,
+    )?;
+    add_change(
+        &mut diff,
+        "protocol_reward_fee",
+        &old.protocol_reward_fee,
+        &new.protocol_reward_fee,
+    )?;
+    add_change(
+        &mut diff,
+        "protocol_deposit_fee",
+        &old.protocol_deposit_fee,
+        &new.protocol_deposit_fee,
+    )?;
+    add_change(
+        &mut diff,
+        "protocol_withdraw_fee",
+        &old.protocol_withdraw_fee,
+        &new.protocol_withdraw_fee,
+    )?;
+    add_change(
+        &mut diff,
+        "reward_fee_as_shares",
+        &old.reward_fee_as_shares,
+        &new.reward_fee_as_shares,
+    )?;
+    add_change(
+        &mut diff,
+        "fee_destination_policy",
+        &old.fee_destination_policy,
+        &new.fee_destination_policy,
+    )?;
+    add_change(
+        &mut diff,
+        "referral_fee_share",
+        &old.referral_fee_share,
+        &new.referral_fee_share,
+    )?;
+    add_change(
+        &mut diff,
+        "unbonding_period",
+        &old.unbonding_period,
+        &new.unbonding_period,
+    )?;
+    add_change(
+        &mut diff,
+        "undelegation_cooldown",
+        &old.undelegation_cooldown,
+        &new.undelegation_cooldown,
+    )?;
+    add_change(
+        &mut diff,
+        "swap_cooldown",
+        &old.swap_cooldown,
+        &new.swap_cooldown,
+    )?;
+    add_change(
+        &mut diff,
+        "reinvest_cooldown",
+        &old.reinvest_cooldown,
+        &new.reinvest_cooldown,
+    )?;
+    add_change(
+        &mut diff,
+        "split_policy",
+        &old.split_policy,
+        &new.split_policy,
+    )?;
+    add_change(
+        &mut diff,
+        "epoch_delegation",
+        &old.epoch_delegation,
+        &new.epoch_delegation,
+    )?;
+    add_change(
+        &mut diff,
+        "delegate_pending_cooldown",
+        &old.delegate_pending_cooldown,
+        &new.delegate_pending_cooldown,
+    )?;
+    add_change(
+        &mut diff,
+        "reward_sniping_policy",
+        &old.reward_sniping_policy,
+        &new.reward_sniping_policy,
+    )?;
+    add_change(&mut diff, "guardian", &old.guardian, &new.guardian)?;
+    add_change(
+        &mut diff,
+        "circuit_breaker_policy",
+        &old.circuit_breaker_policy,
+        &new.circuit_breaker_policy,
+    )?;
+    Ok(diff)
+}
+
+pub fn get_operation_controls_diff(
+    old: &OperationControls,
+    new: &OperationControls,
+) -> StdResult<Vec<AdminActionChange>> {
+    let mut diff = vec![];
+    for ((operation, was_paused), (_, paused)) in get_operation_flags(old)
+        .into_iter()
+        .zip(get_operation_flags(new))
+    {
+        add_change(
+            &mut diff,
+            format!("{}_paused", operation).as_str(),
+            &was_paused,
+            &paused,
+        )?;
+    }
+    Ok(diff)
+}
+
+// Appends an entry to the admin action log and returns its id.
+pub fn record_admin_action(
+    storage: &mut dyn Storage,
+    env: &Env,
+    sender: &Addr,
+    kind: &str,
+    diff: Vec<AdminActionChange>,
+) -> StdResult<u64> {
+    let id = ADMIN_ACTION_COUNT.may_load(storage)?.unwrap_or(0);
+    ADMIN_ACTION_COUNT.save(storage, &(id + 1))?;
+    ADMIN_ACTIONS.save(
+        storage,
+        U64Key::new(id),
+        &AdminAction {
+            id,
+            sender: sender.clone(),
+            time: env.block.time,
+            kind: kind.to_string(),
+            diff,
+        },
+    )?;
+    Ok(id)
+}
+
 pub fn increase_tracked_stake(
     deps: &mut DepsMut,
     val_addr: &Addr,
@@ -191,11 +558,209 @@ pub fn decrease_tracked_slashing(
     Ok(())
 }
 
-pub fn calculate_exchange_rate(total_staked: Uint128, total_token_supply: Uint128) -> Decimal {
+pub fn calculate_exchange_rate(total_staked: Uint128, total_token_supply: Uint128) -> Decimal256 {
     if total_staked.is_zero() || total_token_supply.is_zero() {
-        return Decimal::one();
+        return Decimal256::one();
+    }
+    decimal256_from_ratio(total_staked, total_token_supply)
+}
+
+// Rewards earned by the pool that reinvest has not delegated yet, less the protocol reward fee.
+// Rewards in other denoms are left out until they are swapped to the vault denom.
+pub fn get_pending_rewards(
+    querier: QuerierWrapper,
+    delegator: Addr,
+    validators: &[Addr],
+    config: &Config,
+) -> StdResult<Uint128> {
+    let mut pending_rewards = querier
+        .query_balance(
+            config.reward_contract.to_string(),
+            config.vault_denom.clone(),
+        )?
+        .amount;
+    for val_addr in validators {
+        if let Some(delegation) = querier.query_delegation(delegator.clone(), val_addr)? {
+            for reward in delegation.accumulated_rewards {
+                if reward.denom.eq(&config.vault_denom) {
+                    pending_rewards = pending_rewards.checked_add(reward.amount)?;
+                }
+            }
+        }
+    }
+
+    let protocol_fee = uint128_from_decimal(decimal_multiplication_in_256(
+        get_decimal_from_uint128(pending_rewards),
+        config.protocol_reward_fee,
+    ));
+    Ok(pending_rewards
+        .checked_sub(protocol_fee)
+        .unwrap_or(Uint128::zero()))
+}
+
+// Shares worth `fee` at the exchange rate after they are minted. `total_staked` already includes `fee`.
+pub fn get_fee_shares(total_staked: Uint128, total_shares: Uint128, fee: Uint128) -> Uint128 {
+    if total_staked.le(&fee) {
+        return Uint128::zero();
+    }
+    fee.multiply_ratio(total_shares, total_staked.checked_sub(fee).unwrap())
+}
+
+// Returns how much was given up by rounding `exact` down to `rounded`.
+pub fn get_rounding_dust(exact: Decimal256, rounded: Uint128) -> Decimal256 {
+    let rounded = get_decimal256_from_uint128(rounded);
+    if rounded.gt(&exact) {
+        return Decimal256::zero();
+    }
+    exact - rounded
+}
+
+pub fn validate_protocol_fee_recipients(
+    api: &dyn Api,
+    recipients: Vec<ProtocolFeeRecipient>,
+) -> Result<Vec<ProtocolFeeRecipient>, ContractError> {
+    let mut validated_recipients: Vec<ProtocolFeeRecipient> = vec![];
+    let mut total_weight = Decimal::zero();
+    for recipient in recipients {
+        let recipient_addr = api.addr_validate(recipient.recipient.as_str())?;
+        if recipient.weight.is_zero()
+            || validated_recipients
+                .iter()
+                .any(|validated| validated.recipient.eq(&recipient_addr))
+        {
+            return Err(ContractError::InvalidProtocolFeeRecipients {});
+        }
+
+        total_weight = decimal_summation_in_256(total_weight, recipient.weight);
+        validated_recipients.push(ProtocolFeeRecipient {
+            recipient: recipient_addr,
+            weight: recipient.weight,
+        });
+    }
+
+    if total_weight.ne(&Decimal::one()) {
+        return Err(ContractError::InvalidProtocolFeeRecipients {});
     }
-    Decimal::from_ratio(total_staked, total_token_supply)
+
+    Ok(validated_recipients)
+}
+
+pub fn validate_referral_code(code: &str) -> Result<(), ContractError> {
+    if code.is_empty()
+        || code.len() > get_max_referral_code_length()
+        || !code
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ContractError::InvalidReferralCode {});
+    }
+
+    Ok(())
+}
+
+// Splits a protocol fee by weight. The last recipient takes what is left after rounding down the others.
+pub fn split_protocol_fee(
+    recipients: &[ProtocolFeeRecipient],
+    protocol_fee: Uint128,
+) -> Vec<ProtocolFee> {
+    let mut remaining_fee = protocol_fee;
+    let mut protocol_fees = vec![];
+    for (i, recipient) in recipients.iter().enumerate() {
+        let amount = if i == recipients.len() - 1 {
+            remaining_fee
+        } else {
+            uint128_from_decimal(decimal_multiplication_in_256(
+                get_decimal_from_uint128(protocol_fee),
+                recipient.weight,
+            ))
+        };
+        remaining_fee = remaining_fee.checked_sub(amount).unwrap_or_default();
+
+        if !amount.is_zero() {
+            protocol_fees.push(ProtocolFee {
+                recipient: recipient.recipient.clone(),
+                amount,
+            });
+        }
+    }
+    protocol_fees
+}
+
+pub fn get_protocol_fee_msgs(config: &Config, protocol_fee: Uint128) -> Vec<BankMsg> {
+    split_protocol_fee(&config.protocol_fee_recipients, protocol_fee)
+        .into_iter()
+        .map(|protocol_fee| BankMsg::Send {
+            to_address: protocol_fee.recipient.to_string(),
+            amount: vec![Coin::new(
+                protocol_fee.amount.u128(),
+                config.vault_denom.clone(),
+            )],
+        })
+        .collect()
+}
+
+// Builds the claim message in the format the airdrop's registry entry asks for.
+pub fn get_airdrop_claim_msg(claim_format: &ClaimFormat, rate: &AirdropRate) -> StdResult<Binary> {
+    match claim_format {
+        ClaimFormat::MerkleWithStage {} => to_binary(&MerkleAirdropMsg::Claim {
+            stage: rate.stage,
+            amount: rate.amount,
+            proof: rate.proof.clone(),
+        }),
+        ClaimFormat::MerkleWithoutStage {} => to_binary(&MerkleAirdropWithoutStageMsg::Claim {
+            amount: rate.amount,
+            proof: rate.proof.clone(),
+        }),
+        ClaimFormat::Plain {} => to_binary(&PlainAirdropMsg::Claim {}),
+        ClaimFormat::Template { template } => {
+            let claim_msg = template
+                .replace("{{stage}}", get_json_string(&rate.stage)?.as_str())
+                .replace("{{amount}}", get_json_string(&rate.amount)?.as_str())
+                .replace("{{proof}}", get_json_string(&rate.proof)?.as_str());
+            Ok(Binary::from(claim_msg.into_bytes()))
+        }
+    }
+}
+
+fn get_json_string<T: Serialize>(value: &T) -> StdResult<String> {
+    Ok(String::from_utf8_lossy(to_binary(value)?.as_slice()).into_owned())
+}
+
+// Balance of an airdrop asset held by the given address.
+pub fn get_airdrop_balance(
+    querier_wrapper: QuerierWrapper,
+    asset: &AirdropAsset,
+    addr: Addr,
+) -> StdResult<Uint128> {
+    match asset {
+        AirdropAsset::Cw20 { contract } => {
+            get_user_balance(querier_wrapper, contract.clone(), addr)
+        }
+        AirdropAsset::Native { denom } => Ok(querier_wrapper
+            .query_balance(addr.to_string(), denom.clone())?
+            .amount),
+    }
+}
+
+pub fn get_airdrop_transfer_msg(
+    asset: AirdropAsset,
+    recipient: Addr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    Ok(match asset {
+        AirdropAsset::Cw20 { contract } => CosmosMsg::from(WasmMsg::Execute {
+            contract_addr: contract.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+        AirdropAsset::Native { denom } => CosmosMsg::from(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![Coin::new(amount.u128(), denom)],
+        }),
+    })
 }
 
 pub fn get_airdrop_contracts(
@@ -1,14 +1,17 @@
 use crate::state::{
-    AirdropRate, BatchUndelegationRecord, Config, ConfigUpdateRequest,
-    OperationControlsUpdateRequest, State, TmpManagerStore, VMeta,
+    AdminAction, AirdropRate, BatchUndelegationRecord, CircuitBreakerState, ClaimedAirdropStage,
+    Config, ConfigUpdateRequest, OperationControlsUpdateRequest, PauseInfo, ProtocolFeeRecipient,
+    ReferralInfo, State, TmpManagerStore, UndelegationInfo, UnwindInfo, VMeta,
 };
-use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cosmwasm_bignumber::Decimal256;
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Empty, Uint128};
 use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
+    pub vault_denom: Option<String>, // Defaults to the chain's bonded denom
     pub min_deposit: Uint128,
     pub max_deposit: Uint128,
 
@@ -16,7 +19,7 @@ pub struct InstantiateMsg {
     pub airdrops_registry_contract: String,
     pub airdrop_withdrawal_contract: String,
 
-    pub protocol_fee_contract: String,
+    pub protocol_fee_recipients: Vec<ProtocolFeeRecipient>,
     pub protocol_reward_fee: Decimal, // "1 is 100%, 0.02 is 2%"
     pub protocol_deposit_fee: Decimal,
     pub protocol_withdraw_fee: Decimal, // "1 is 100%, 0.02 is 2%"
@@ -33,13 +36,22 @@ pub struct MigrateMsg {}
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct UserQueryInfo {
     pub total_tokens: Uint128,
-    pub total_amount: Coin, // value of tokens in luna with the exchange rate at that point
+    pub total_amount: Coin, // value of tokens in the vault denom with the exchange rate at that point
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum Cw20HookMsg {
-    QueueUndelegate {},
+    QueueUndelegate {
+        mint_nft: Option<bool>, // Mint the position as a transferable unbonding NFT instead of recording it for the sender
+    },
+    UnwindClaim {}, // Burn LunaX for its share of the unwound pool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw721HookMsg {
+    WithdrawFundsToWallet {}, // Withdraw the unbonding NFT's position to the sender and burn the NFT
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -57,19 +69,40 @@ pub enum ExecuteMsg {
         val_addr: Addr,
         redel_addr: Addr,
     },
-    Deposit {},
+    Deposit {
+        referral_code: Option<String>,
+    },
+    DelegatePending {},
     RedeemRewards {},
     Swap {},
     ReimburseSlashing {
         val_addr: Addr,
     },
     Receive(Cw20ReceiveMsg),
+    ReceiveNft(Cw721ReceiveMsg),
     Reinvest {},
     Undelegate {},
     ReconcileFunds {},
     WithdrawFundsToWallet {
         batch_id: u64,
     },
+    // Permissionless. Always pays the user the undelegation was queued for.
+    WithdrawFor {
+        user: String,
+        batch_id: u64,
+    },
+    // Permissionless. Pays the next users of a reconciled batch that opted in to auto-claim.
+    PushBatchPayouts {
+        batch_id: u64,
+        limit: Option<u64>,
+    },
+    SetAutoClaim {
+        enabled: bool,
+    },
+    // Each call moves the unwind forward: start, undelegate one validator per call until it is unbonding, then finalize.
+    EmergencyUnwind {},
+    // Guardian or manager only. Unpauses the operations the circuit breaker paused.
+    ResumeFromCircuitBreaker {},
     ClaimAirdrops {
         rates: Vec<AirdropRate>,
     },
@@ -83,6 +116,15 @@ pub enum ExecuteMsg {
         manager: String,
     },
     AcceptManager {},
+    SetProtocolFeeRecipients {
+        recipients: Vec<ProtocolFeeRecipient>,
+    },
+    RegisterReferralCode {
+        code: String,
+    },
+    ClaimReferralFees {
+        code: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -95,6 +137,42 @@ pub enum MerkleAirdropMsg {
     },
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MerkleAirdropWithoutStageMsg {
+    Claim { amount: Uint128, proof: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlainAirdropMsg {
+    Claim {},
+}
+
+// Subset of the cw721-base messages used for unbonding NFTs.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw721ExecuteMsg {
+    Mint(Cw721MintMsg),
+    Burn { token_id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw721MintMsg {
+    pub token_id: String,
+    pub owner: String,
+    pub token_uri: Option<String>,
+    pub extension: Option<Empty>,
+}
+
+// Sent by the unbonding NFT contract when an NFT is sent to staking with SendNft.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
@@ -122,7 +200,35 @@ pub enum QueryMsg {
     },
     ComputeDepositBreakdown {
         amount: Uint128,
+        referral_code: Option<String>,
+    },
+    ReferralCode {
+        code: String,
+    },
+    AirdropsClaimed {
+        denom: String,
     },
+    AirdropClaimHistory {
+        denom: String,
+    },
+    UnbondingNft {
+        token_id: String,
+    },
+    AutoClaim {
+        user_addr: String,
+    },
+    UnwindState {},
+    CircuitBreaker {},
+    PauseInfo {},
+    AdminActions {
+        start_after: Option<u64>,
+        limit: Option<u64>,
+    },
+    ComputeUnwindClaim {
+        shares: Uint128,
+    },
+    SharesInvariant {},
+    RoundingDust {},
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -145,6 +251,71 @@ pub struct QueryStateResponse {
     pub state: State,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SharesInvariantResponse {
+    pub total_shares: Uint128,
+    pub cw20_total_supply: Uint128,
+    pub is_consistent: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RoundingDustResponse {
+    pub rounding_dust: Decimal256,
+    pub rounding_dust_amount: Uint128, // Whole units of the vault denom
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralCodeResponse {
+    pub referral_info: Option<ReferralInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AirdropsClaimedResponse {
+    pub denom: String,
+    pub amount: Uint128, // Forwarded to the airdrop withdrawal contract so far
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AirdropClaimHistoryResponse {
+    pub denom: String,
+    pub claimed_stages: Vec<ClaimedAirdropStage>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnwindStateResponse {
+    pub unwind: Option<UnwindInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseInfoResponse {
+    pub pauses: Vec<PauseInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminActionsResponse {
+    pub actions: Vec<AdminAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreakerResponse {
+    pub circuit_breaker: Option<CircuitBreakerState>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ComputeUnwindClaimResponse {
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AutoClaimResponse {
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingNftResponse {
+    pub position: Option<UndelegationInfo>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct QueryBatchUndelegationResponse {
     pub batch: Option<BatchUndelegationRecord>,
@@ -159,13 +330,19 @@ pub struct GetValMetaResponse {
 pub struct GetFundsDepositRecord {
     pub user_deposit_amount: Uint128,
     pub protocol_fee: Uint128,
+    pub pool_fee: Uint128, // Fee staked along with the deposit without minting shares for it
+    pub referral_fee: Uint128, // Fee accrued to the referral code
     pub staked_amount: Uint128,
     pub tokens_to_mint: Uint128,
+    pub locked_tokens: Uint128, // Minted to the staking contract on the first deposit
+    pub rounding_dust: Decimal256,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetFundsClaimRecord {
     pub user_withdrawal_amount: Uint128,
     pub protocol_fee: Uint128,
+    pub pool_fee: Uint128, // Fee returned to the pool to be delegated again
     pub undelegated_tokens: Uint128,
+    pub rounding_dust: Decimal256,
 }
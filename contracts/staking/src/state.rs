@@ -1,8 +1,10 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+use airdrops_registry::state::AirdropAsset;
+use cosmwasm_bignumber::Decimal256;
 use cosmwasm_std::{Addr, Decimal, Timestamp, Uint128};
-use cw_storage_plus::{Item, Map, U64Key};
+use cw_storage_plus::{Item, Map, U64Key, U8Key};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
@@ -12,26 +14,86 @@ pub struct Config {
     pub max_deposit: Uint128,
     pub active: bool,
 
-    pub reward_contract: Addr,             // Non-changeable
-    pub cw20_token_contract: Addr,         // Changeable once
-    pub airdrop_registry_contract: Addr,   // Non-changeable
-    pub airdrop_withdrawal_contract: Addr, // Non-changeable
+    pub reward_contract: Addr,                // Non-changeable
+    pub cw20_token_contract: Addr,            // Changeable once
+    pub airdrop_registry_contract: Addr,      // Non-changeable
+    pub airdrop_withdrawal_contract: Addr,    // Non-changeable
+    pub unbonding_nft_contract: Option<Addr>, // CW721 contract minting unbonding positions. Staking must be its minter.
 
-    pub protocol_fee_contract: Addr, // Non-changeable
+    pub protocol_fee_recipients: Vec<ProtocolFeeRecipient>, // Split of every protocol fee. Weights add up to 1.
     pub protocol_reward_fee: Decimal,
     pub protocol_deposit_fee: Decimal,
     pub protocol_withdraw_fee: Decimal,
+    pub reward_fee_as_shares: bool, // Mint the reward fee as LunaX to the fee recipients instead of taking it in luna
+    pub fee_destination_policy: FeeDestinationPolicy,
+    pub referral_fee_share: Decimal, // Share of the deposit fee accrued to the referral code used for the deposit
 
     pub unbonding_period: u64,
     pub undelegation_cooldown: u64,
     pub swap_cooldown: u64, // cooldown to avoid external users from spamming the swap message
     pub reinvest_cooldown: u64, // cooldown to avoid external users from spamming the reinvest message
+
+    pub split_policy: SplitPolicy,
+
+    pub epoch_delegation: bool, // Hold deposits in a pending pool until the next DelegatePending call
+    pub delegate_pending_cooldown: u64, // cooldown to avoid external users from spamming the delegate pending message
+
+    pub reward_sniping_policy: RewardSnipingPolicy,
+
+    pub guardian: Option<Addr>, // Can resume operations paused by the circuit breaker, alongside the manager
+    pub circuit_breaker_policy: CircuitBreakerPolicy,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ProtocolFeeRecipient {
+    pub recipient: Addr,
+    pub weight: Decimal, // "1 is 100%, 0.6 is 60%"
+}
+
+// Where a fee ends up once it is taken from a user.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeDestination {
+    ProtocolFeeRecipients, // Sent to the protocol fee recipients
+    Pool,                  // Left in the pool, raising the exchange rate for the remaining holders
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FeeDestinationPolicy {
+    pub deposit_fee: FeeDestination,
+    pub withdraw_fee: FeeDestination,
+}
+
+// Amounts above these thresholds are spread over several validators instead of a single one.
+// None disables splitting for that operation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SplitPolicy {
+    pub deposit_threshold: Option<Uint128>, // Split in proportion to each validator's distance from its target stake
+    pub undelegation_threshold: Option<Uint128>, // Split in proportion to each validator's current stake
+}
+
+// Stops deposits made just before reinvest from taking a share of rewards they did not earn.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RewardSnipingPolicy {
+    pub include_pending_rewards: bool, // Price deposits with unclaimed rewards, net of the protocol reward fee
+    pub reinvest_window: u64, // Seconds before reinvest is due in which deposits are restricted. 0 disables the window.
+    pub reinvest_window_fee: Option<Decimal>, // Extra deposit fee charged in the window. None blocks deposits instead.
+}
+
+// Bounds on how far the exchange rate can move before the pool pauses itself. None disables that bound.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreakerPolicy {
+    pub max_rate_drop_per_call: Option<Decimal>, // "0.05 is a 5% drop from the last computed rate"
+    pub max_rate_rise_per_call: Option<Decimal>,
+    pub max_rate_drop_per_day: Option<Decimal>, // Measured from the rate at the start of the current day window
+    pub max_rate_rise_per_day: Option<Decimal>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct State {
     pub total_staked: Uint128,
-    pub exchange_rate: Decimal, // shares to token value. 1 share = (ExchangeRate) tokens.
+    pub total_shares: Uint128, // LunaX minted minus LunaX burnt by this contract. Tracks the cw20 total supply.
+    pub exchange_rate: Decimal256, // shares to token value. 1 share = (ExchangeRate) tokens.
     pub last_reconciled_batch_id: u64,
     pub current_undelegation_batch_id: u64,
     pub last_undelegation_time: Timestamp,
@@ -39,6 +101,11 @@ pub struct State {
     pub last_reinvest_time: Timestamp,
     pub validators: Vec<Addr>,
     pub reconciled_funds_to_withdraw: Uint128,
+    pub pending_delegation: Uint128, // Deposited luna held in the contract and yet to be delegated.
+    pub last_delegate_pending_time: Timestamp,
+    pub rounding_dust: Decimal256, // Luna left in the pool by rounding share conversions in its favour.
+    pub fee_shares_minted: Uint128, // LunaX minted to the fee recipients as the reward fee.
+    pub unclaimed_referral_fees: Uint128, // Referral fees held in the contract until their integrators claim them.
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -53,8 +120,60 @@ pub struct OperationControls {
     pub redeem_rewards_paused: bool,
     pub swap_paused: bool,
     pub reimburse_slashing_paused: bool,
+    #[serde(default)] // Missing from storage written before pending delegations could be paused.
+    pub delegate_pending_paused: bool,
+}
+
+// Why and by whom an operation was paused. Removed once the operation is unpaused.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseInfo {
+    pub operation: String,
+    pub paused_by: Addr,
+    pub paused_at: Timestamp,
+    pub reason: Option<String>,
+}
+
+// (operation) -> PauseInfo
+pub const PAUSE_INFO: Map<&str, PauseInfo> = Map::new("pause_info");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminActionChange {
+    pub field: String,
+    pub old_value: String, // JSON encoded
+    pub new_value: String, // JSON encoded
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AdminAction {
+    pub id: u64,
+    pub sender: Addr,
+    pub time: Timestamp,
+    pub kind: String, // Name of the execute message
+    pub diff: Vec<AdminActionChange>,
+}
+
+pub const ADMIN_ACTION_COUNT: Item<u64> = Item::new("admin_action_count");
+// (action id) -> AdminAction
+pub const ADMIN_ACTIONS: Map<U64Key, AdminAction> = Map::new("admin_actions");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreakerTrip {
+    pub reason: String,
+    pub tripped_at: Timestamp,
+    pub previous_rate: Decimal256,
+    pub new_rate: Decimal256,
+    pub paused_operations: Vec<String>, // Paused by the breaker. Only these are unpaused on resume.
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CircuitBreakerState {
+    pub window_start: Timestamp,
+    pub window_start_rate: Decimal256,
+    pub trip: Option<CircuitBreakerTrip>,
+}
+
+pub const CIRCUIT_BREAKER: Item<CircuitBreakerState> = Item::new("circuit_breaker");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct VMeta {
     pub staked: Uint128, // Staked so far. This is the net sum and does not count filled funds.
@@ -78,6 +197,65 @@ pub struct UndelegationInfo {
     pub token_amount: Uint128, // Shares undelegated
 }
 
+// Map of CW721 token id to the undelegation it represents. The position is paid to the current holder
+// of the NFT instead of being stored under an address in USERS.
+pub const UNBONDING_NFTS: Map<&str, UndelegationInfo> = Map::new("unbonding_nfts");
+pub const UNBONDING_NFT_COUNT: Item<u64> = Item::new("unbonding_nft_count");
+
+// Emergency unwind of the whole pool. Separate from the undelegation batches: the stake is undelegated
+// in steps, and once it has all arrived LunaX holders burn their tokens for a share of the luna.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UnwindPhase {
+    Undelegating, // Deposits and undelegations are stopped and one validator is undelegated every cooldown
+    Unbonding,    // Nothing is delegated anymore. Waiting for the last step to be released.
+    Claimable,    // LunaX can be burnt for claim_rate luna each
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnwindStep {
+    pub amount: Uint128,
+    pub undelegated_at: Timestamp,
+    pub release_time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnwindInfo {
+    pub phase: UnwindPhase,
+    pub started_at: Timestamp,
+    pub steps: Vec<UnwindStep>,
+    pub claimable_amount: Uint128, // Luna set aside for LunaX holders when the unwind was finalized
+    pub claimable_shares: Uint128, // LunaX held outside the contract when the unwind was finalized
+    pub claim_rate: Decimal256,
+    pub claimed_shares: Uint128,
+    pub claimed_amount: Uint128,
+}
+
+impl UnwindInfo {
+    // Luna in the contract balance that belongs to the unwind and not to the undelegation batches.
+    pub fn held_funds(&self, time: Timestamp) -> Uint128 {
+        match self.phase {
+            UnwindPhase::Claimable => self
+                .claimable_amount
+                .checked_sub(self.claimed_amount)
+                .unwrap_or_else(|_| Uint128::zero()),
+            _ => self
+                .steps
+                .iter()
+                .filter(|step| step.release_time.le(&time))
+                .map(|step| step.amount)
+                .sum(),
+        }
+    }
+}
+
+pub const UNWIND: Item<UnwindInfo> = Item::new("unwind");
+
+// Users who opted in to have their reconciled undelegations pushed to them by PushBatchPayouts
+pub const AUTO_CLAIM_USERS: Map<&Addr, bool> = Map::new("auto_claim_users");
+// Undelegations of users with auto-claim on, by batch. Entries are removed once paid.
+pub const AUTO_CLAIM_QUEUE: Map<(U64Key, &Addr), bool> = Map::new("auto_claim_queue");
+
 // Validator address and pool Id as key.
 pub const VALIDATOR_META: Map<&Addr, VMeta> = Map::new("validator_meta");
 
@@ -87,7 +265,7 @@ pub struct BatchUndelegationRecord {
     pub(crate) create_time: Timestamp,
     pub(crate) est_release_time: Option<Timestamp>,
     pub(crate) reconciled: bool,
-    pub(crate) undelegation_er: Decimal,
+    pub(crate) undelegation_er: Decimal256,
     pub(crate) undelegated_stake: Uint128,
     pub(crate) unbonding_slashing_ratio: Decimal, // If Unbonding slashing happens during the 21 day period.
 }
@@ -105,12 +283,22 @@ pub struct ConfigUpdateRequest {
     pub(crate) protocol_reward_fee: Option<Decimal>,
     pub(crate) protocol_withdraw_fee: Option<Decimal>,
     pub(crate) protocol_deposit_fee: Option<Decimal>,
+    pub(crate) reward_fee_as_shares: Option<bool>,
+    pub(crate) fee_destination_policy: Option<FeeDestinationPolicy>,
+    pub(crate) referral_fee_share: Option<Decimal>,
     pub(crate) airdrop_registry_contract: Option<String>,
+    pub(crate) unbonding_nft_contract: Option<String>,
 
     pub(crate) unbonding_period: Option<u64>,
     pub(crate) undelegation_cooldown: Option<u64>,
     pub(crate) swap_cooldown: Option<u64>,
     pub(crate) reinvest_cooldown: Option<u64>,
+    pub(crate) split_policy: Option<SplitPolicy>,
+    pub(crate) epoch_delegation: Option<bool>,
+    pub(crate) delegate_pending_cooldown: Option<u64>,
+    pub(crate) reward_sniping_policy: Option<RewardSnipingPolicy>,
+    pub(crate) guardian: Option<String>,
+    pub(crate) circuit_breaker_policy: Option<CircuitBreakerPolicy>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -125,15 +313,100 @@ pub struct OperationControlsUpdateRequest {
     pub(crate) swap_paused: Option<bool>,
     pub(crate) redeem_rewards_paused: Option<bool>,
     pub(crate) reimburse_slashing_paused: Option<bool>,
+    pub(crate) delegate_pending_paused: Option<bool>,
+    pub(crate) reason: Option<String>, // Stored with every flag this request pauses
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const STATE: Item<State> = Item::new("state");
 pub const OPERATION_CONTROLS: Item<OperationControls> = Item::new("operation_controls");
 
+// Config and State as they were stored before shares were tracked internally and the fee, delegation and
+// safety settings were added. Only read by migrate.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyConfig {
+    pub manager: Addr,
+    pub vault_denom: String,
+    pub min_deposit: Uint128,
+    pub max_deposit: Uint128,
+    pub active: bool,
+
+    pub reward_contract: Addr,
+    pub cw20_token_contract: Addr,
+    pub airdrop_registry_contract: Addr,
+    pub airdrop_withdrawal_contract: Addr,
+
+    pub protocol_fee_contract: Addr,
+    pub protocol_reward_fee: Decimal,
+    pub protocol_deposit_fee: Decimal,
+    pub protocol_withdraw_fee: Decimal,
+
+    pub unbonding_period: u64,
+    pub undelegation_cooldown: u64,
+    pub swap_cooldown: u64,
+    pub reinvest_cooldown: u64,
+}
+pub const LEGACY_CONFIG: Item<LegacyConfig> = Item::new("config");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyState {
+    pub total_staked: Uint128,
+    pub exchange_rate: Decimal,
+    pub last_reconciled_batch_id: u64,
+    pub current_undelegation_batch_id: u64,
+    pub last_undelegation_time: Timestamp,
+    pub last_swap_time: Timestamp,
+    pub last_reinvest_time: Timestamp,
+    pub validators: Vec<Addr>,
+    pub reconciled_funds_to_withdraw: Uint128,
+}
+pub const LEGACY_STATE: Item<LegacyState> = Item::new("state");
+
 // (User_Address, Undelegation Batch)
 pub const USERS: Map<(&Addr, U64Key), UndelegationInfo> = Map::new("users");
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralInfo {
+    pub owner: Addr, // Integrator that registered the code. Only the owner can claim its fees.
+    pub total_deposits: Uint128, // Luna deposited with this code
+    pub outstanding_shares: Uint128, // LunaX minted with this code less what its depositors have queued for undelegation
+    pub unclaimed_fees: Uint128,
+    pub claimed_fees: Uint128,
+}
+
+// (Referral code) -> ReferralInfo
+pub const REFERRAL_CODES: Map<&str, ReferralInfo> = Map::new("referral_codes");
+
+// (User_Address, Referral code) -> LunaX the user minted with the code and has not queued for undelegation
+pub const REFERRED_SHARES: Map<(&Addr, &str), Uint128> = Map::new("referred_shares");
+
+// An airdrop claim sent as a submessage and waiting for its reply.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingAirdropClaim {
+    pub denom: String,
+    pub stage: u8,
+    pub asset: AirdropAsset,
+    pub balance_before: Uint128, // Contract balance of the airdrop asset before the claim ran
+}
+
+// (Reply id) -> PendingAirdropClaim. Entries only live until their reply.
+pub const PENDING_AIRDROP_CLAIMS: Map<U64Key, PendingAirdropClaim> =
+    Map::new("pending_airdrop_claims");
+
+// (Airdrop denom) -> Amount claimed and forwarded to the airdrop withdrawal contract
+pub const AIRDROPS_CLAIMED: Map<&str, Uint128> = Map::new("airdrops_claimed");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ClaimedAirdropStage {
+    pub stage: u8,
+    pub amount: Uint128, // Balance change measured when the claim ran
+    pub claimed_at: Timestamp,
+}
+
+// (Airdrop denom, Stage) -> ClaimedAirdropStage. Claims for a stage in here are rejected.
+pub const AIRDROP_CLAIM_HISTORY: Map<(&str, U8Key), ClaimedAirdropStage> =
+    Map::new("airdrop_claim_history");
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct AirdropRate {
     pub denom: String,
@@ -1,12 +1,12 @@
 use cosmwasm_std::{
     from_binary, from_slice, to_binary, Addr, Binary, Coin, ContractResult, Decimal,
     FullDelegation, OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError, SystemResult,
-    Uint128, Validator, WasmQuery,
+    Timestamp, Uint128, Validator, WasmQuery,
 };
 use std::collections::HashMap;
 
 use airdrops_registry::msg::{GetAirdropContractsResponse, QueryMsg as AirdropsQueryMsg};
-use airdrops_registry::state::AirdropRegistryInfo;
+use airdrops_registry::state::{AirdropAsset, AirdropRegistryInfo, ClaimFormat};
 use cosmwasm_std::testing::{MockApi, MockQuerier, MockStorage};
 use cw20::{BalanceResponse, TokenInfoResponse};
 use reward::msg::QueryMsg as reward_query;
@@ -163,10 +163,12 @@ impl WasmMockQuerier {
                                             "{}_airdrop_contract",
                                             token.clone()
                                         )),
-                                        cw20_contract: Addr::unchecked(format!(
-                                            "{}_cw20_contract",
-                                            token.clone()
-                                        )),
+                                        asset: get_airdrop_asset(token.as_str()),
+                                        claim_format: get_claim_format(token.as_str()),
+                                        enabled: !token.eq("disabled_token"),
+                                        stages: vec![],
+                                        claim_deadline: None,
+                                        added_at: Timestamp::from_seconds(0),
                                     }),
                                 };
                             }
@@ -297,3 +299,27 @@ impl WasmMockQuerier {
         self.tax_querier = TaxQuerier::_new(rate, caps);
     }
 }
+
+// Special token names pick the non-default registry entries.
+fn get_airdrop_asset(token: &str) -> AirdropAsset {
+    match token {
+        "native_token" => AirdropAsset::Native {
+            denom: "unative".to_string(),
+        },
+        _ => AirdropAsset::Cw20 {
+            contract: Addr::unchecked(format!("{}_cw20_contract", token)),
+        },
+    }
+}
+
+fn get_claim_format(token: &str) -> ClaimFormat {
+    match token {
+        "native_token" => ClaimFormat::MerkleWithoutStage {},
+        "plain_token" => ClaimFormat::Plain {},
+        "template_token" => ClaimFormat::Template {
+            template: r#"{"claim_tokens":{"round":{{stage}},"amount":{{amount}},"merkle_proof":{{proof}}}}"#
+                .to_string(),
+        },
+        _ => ClaimFormat::MerkleWithStage {},
+    }
+}
@@ -1,38 +1,52 @@
 #[cfg(test)]
 mod tests {
     use crate::contract::{
-        check_slashing, compute_withdrawable_funds, execute, instantiate, query, queue_undelegation,
+        check_slashing, compute_withdrawable_funds, execute, instantiate, migrate, query,
+        queue_undelegation, reply,
     };
     use crate::error::ContractError;
     use crate::error::ContractError::ValidatorNotDiscoverable;
     use crate::helpers::{
-        get_active_validators_sorted_by_stake, get_validator_for_deposit, validate, Verify,
+        get_active_validators_sorted_by_stake, get_validator_for_deposit, split_protocol_fee,
+        validate, Verify,
     };
     use crate::msg::{
-        Cw20HookMsg, ExecuteMsg, GetFundsClaimRecord, InstantiateMsg, MerkleAirdropMsg,
-        QueryConfigResponse, QueryMsg, QueryStateResponse,
+        AdminActionsResponse, AirdropClaimHistoryResponse, AirdropsClaimedResponse,
+        AutoClaimResponse, CircuitBreakerResponse, ComputeUnwindClaimResponse, Cw20HookMsg,
+        Cw721ExecuteMsg, Cw721HookMsg, Cw721MintMsg, Cw721ReceiveMsg, ExecuteMsg,
+        GetFundsClaimRecord, InstantiateMsg, MerkleAirdropMsg, MerkleAirdropWithoutStageMsg,
+        MigrateMsg, PauseInfoResponse, PlainAirdropMsg, QueryConfigResponse, QueryMsg,
+        QueryStateResponse, ReferralCodeResponse, RoundingDustResponse, SharesInvariantResponse,
+        UnbondingNftResponse, UnwindStateResponse,
     };
     use crate::state::{
-        AirdropRate, BatchUndelegationRecord, Config, ConfigUpdateRequest, OperationControls,
-        OperationControlsUpdateRequest, State, UndelegationInfo, VMeta,
-        BATCH_UNDELEGATION_REGISTRY, CONFIG, OPERATION_CONTROLS, STATE, USERS, VALIDATOR_META,
+        AdminAction, AdminActionChange, AirdropRate, BatchUndelegationRecord, CircuitBreakerPolicy,
+        ClaimedAirdropStage, Config, ConfigUpdateRequest, FeeDestination, FeeDestinationPolicy,
+        LegacyConfig, LegacyState, OperationControls, OperationControlsUpdateRequest, PauseInfo,
+        ProtocolFeeRecipient, ReferralInfo, RewardSnipingPolicy, SplitPolicy, State,
+        UndelegationInfo, UnwindPhase, VMeta, AIRDROPS_CLAIMED, BATCH_UNDELEGATION_REGISTRY,
+        CONFIG, LEGACY_CONFIG, LEGACY_STATE, OPERATION_CONTROLS, REFERRAL_CODES, REFERRED_SHARES,
+        STATE, UNBONDING_NFTS, USERS, VALIDATOR_META,
     };
     use crate::testing::mock_querier;
     use crate::testing::mock_querier::{mock_dependencies, WasmMockQuerier};
     use crate::testing::test_helpers::check_equal_vec;
+    use cosmwasm_bignumber::Decimal256;
     use cosmwasm_std::testing::{
         mock_env, mock_info, MockApi, MockQuerier, MockStorage, MOCK_CONTRACT_ADDR,
     };
     use cosmwasm_std::{
-        attr, from_binary, to_binary, Addr, Attribute, BankMsg, Coin, Decimal, DistributionMsg,
-        Env, FullDelegation, MessageInfo, OwnedDeps, StakingMsg, StdResult, SubMsg, Timestamp,
-        Uint128, Validator, WasmMsg,
+        attr, from_binary, to_binary, Addr, Attribute, BankMsg, Binary, Coin, ContractResult,
+        Decimal, DistributionMsg, Env, FullDelegation, MessageInfo, OwnedDeps, Reply, StakingMsg,
+        StdResult, SubMsg, SubMsgExecutionResponse, Timestamp, Uint128, Validator, WasmMsg,
     };
     use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
     use cw_storage_plus::U64Key;
-    use reward::msg::ExecuteMsg as RewardExecuteMsg;
+    use reward::msg::{ExecuteMsg as RewardExecuteMsg, ProtocolFee};
     use reward::state::{TmpManagerStore, TMP_MANAGER_STORE};
     use stader_utils::coin_utils::{check_equal_deccoin_vector, DecCoin};
+    use std::collections::HashMap;
+    use std::str::FromStr;
 
     fn get_validators() -> Vec<Validator> {
         vec![
@@ -83,12 +97,54 @@ mod tests {
         ]
     }
 
+    fn get_uneven_delegations() -> Vec<FullDelegation> {
+        vec![
+            FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: "valid0001".to_string(),
+                amount: Coin::new(1000, "uluna"),
+                can_redelegate: Coin::new(1000, "uluna"),
+                accumulated_rewards: vec![],
+            },
+            FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: "valid0002".to_string(),
+                amount: Coin::new(2000, "uluna"),
+                can_redelegate: Coin::new(2000, "uluna"),
+                accumulated_rewards: vec![],
+            },
+            FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: "valid0003".to_string(),
+                amount: Coin::new(3000, "uluna"),
+                can_redelegate: Coin::new(3000, "uluna"),
+                accumulated_rewards: vec![],
+            },
+        ]
+    }
+
+    // Keeps the internally tracked shares in line with the mocked cw20 supply.
+    fn update_total_shares(
+        deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
+        total_shares: Uint128,
+    ) {
+        deps.querier
+            .update_stader_balances(Some(total_shares), None);
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.total_shares = total_shares;
+                Ok(state)
+            })
+            .unwrap();
+    }
+
     pub fn instantiate_contract(
         deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
         info: &MessageInfo,
         env: &Env,
     ) {
         let msg = InstantiateMsg {
+            vault_denom: Some("uluna".to_string()),
             unbonding_period: 3600 * 24 * 21,
             undelegation_cooldown: 10,
             swap_cooldown: 10,
@@ -97,7 +153,7 @@ mod tests {
             reward_contract: "reward_contract".to_string(),
             airdrops_registry_contract: "airdrop_registry_contract".to_string(),
             airdrop_withdrawal_contract: "airdrop_withdrawal_contract".to_string(),
-            protocol_fee_contract: "protocol_fee_contract".to_string(),
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
@@ -107,6 +163,13 @@ mod tests {
         instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
     }
 
+    fn get_protocol_fee_recipients() -> Vec<ProtocolFeeRecipient> {
+        vec![ProtocolFeeRecipient {
+            recipient: Addr::unchecked("protocol_fee_contract"),
+            weight: Decimal::one(),
+        }]
+    }
+
     #[test]
     fn proper_initialization_fail() {
         let mut deps = mock_dependencies(&[]);
@@ -117,6 +180,7 @@ mod tests {
            Reward fee above limit
         */
         let msg = InstantiateMsg {
+            vault_denom: Some("uluna".to_string()),
             unbonding_period: 3600 * 24 * 21,
             undelegation_cooldown: 10,
             swap_cooldown: 10,
@@ -125,7 +189,7 @@ mod tests {
             reward_contract: "reward_contract".to_string(),
             airdrops_registry_contract: "airdrop_registry_contract".to_string(),
             airdrop_withdrawal_contract: "airdrop_withdrawal_contract".to_string(),
-            protocol_fee_contract: "protocol_fee_contract".to_string(),
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(11_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
@@ -140,6 +204,7 @@ mod tests {
             Deposit fee above limit
         */
         let msg = InstantiateMsg {
+            vault_denom: Some("uluna".to_string()),
             unbonding_period: 3600 * 24 * 21,
             undelegation_cooldown: 10,
             swap_cooldown: 10,
@@ -148,7 +213,7 @@ mod tests {
             reward_contract: "reward_contract".to_string(),
             airdrops_registry_contract: "airdrop_registry_contract".to_string(),
             airdrop_withdrawal_contract: "airdrop_withdrawal_contract".to_string(),
-            protocol_fee_contract: "protocol_fee_contract".to_string(),
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(6_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
@@ -163,6 +228,7 @@ mod tests {
            Withdraw fee above limit
         */
         let msg = InstantiateMsg {
+            vault_denom: Some("uluna".to_string()),
             unbonding_period: 3600 * 24 * 21,
             undelegation_cooldown: 10,
             swap_cooldown: 10,
@@ -171,7 +237,7 @@ mod tests {
             reward_contract: "reward_contract".to_string(),
             airdrops_registry_contract: "airdrop_registry_contract".to_string(),
             airdrop_withdrawal_contract: "airdrop_withdrawal_contract".to_string(),
-            protocol_fee_contract: "protocol_fee_contract".to_string(),
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(2_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(8_u128, 100_u128), // 1%
@@ -189,7 +255,10 @@ mod tests {
         let info = mock_info("creator", &[]);
         let env = mock_env();
 
+        // The vault denom is taken from the chain's bonded denom when it is not set.
+        deps.querier.update_staking("uluna", &[], &[]);
         let msg = InstantiateMsg {
+            vault_denom: None,
             unbonding_period: 3600 * 24 * 21,
             undelegation_cooldown: 10,
             swap_cooldown: 10,
@@ -198,7 +267,7 @@ mod tests {
             reward_contract: "reward_contract".to_string(),
             airdrops_registry_contract: "airdrop_registry_contract".to_string(),
             airdrop_withdrawal_contract: "airdrop_withdrawal_contract".to_string(),
-            protocol_fee_contract: "protocol_fee_contract".to_string(),
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
@@ -217,11 +286,36 @@ mod tests {
             cw20_token_contract: Addr::unchecked("0"),
             airdrop_registry_contract: Addr::unchecked("airdrop_registry_contract"),
             airdrop_withdrawal_contract: Addr::unchecked("airdrop_withdrawal_contract"),
-            protocol_fee_contract: Addr::unchecked("protocol_fee_contract"),
+            unbonding_nft_contract: None,
+            protocol_fee_recipients: get_protocol_fee_recipients(),
             protocol_reward_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_deposit_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
             protocol_withdraw_fee: Decimal::from_ratio(1_u128, 100_u128), // 1%
+            reward_fee_as_shares: false,
+            fee_destination_policy: FeeDestinationPolicy {
+                deposit_fee: FeeDestination::ProtocolFeeRecipients,
+                withdraw_fee: FeeDestination::ProtocolFeeRecipients,
+            },
+            referral_fee_share: Decimal::zero(),
             reinvest_cooldown: 10,
+            split_policy: SplitPolicy {
+                deposit_threshold: None,
+                undelegation_threshold: None,
+            },
+            epoch_delegation: false,
+            delegate_pending_cooldown: 0,
+            reward_sniping_policy: RewardSnipingPolicy {
+                include_pending_rewards: false,
+                reinvest_window: 0,
+                reinvest_window_fee: None,
+            },
+            guardian: None,
+            circuit_breaker_policy: CircuitBreakerPolicy {
+                max_rate_drop_per_call: None,
+                max_rate_rise_per_call: None,
+                max_rate_drop_per_day: None,
+                max_rate_rise_per_day: None,
+            },
         };
         let info = mock_info("creator", &[]);
 
@@ -244,7 +338,8 @@ mod tests {
             state.state,
             State {
                 total_staked: Uint128::zero(),
-                exchange_rate: Decimal::one(),
+                total_shares: Uint128::zero(),
+                exchange_rate: Decimal256::one(),
                 last_reconciled_batch_id: 0,
                 current_undelegation_batch_id: 1,
                 last_undelegation_time: env
@@ -257,7 +352,12 @@ mod tests {
                     .time
                     .minus_seconds(config.config.reinvest_cooldown),
                 validators: vec![],
-                reconciled_funds_to_withdraw: Uint128::zero()
+                reconciled_funds_to_withdraw: Uint128::zero(),
+                pending_delegation: Uint128::zero(),
+                last_delegate_pending_time: env.block.time,
+                rounding_dust: Decimal256::zero(),
+                fee_shares_minted: Uint128::zero(),
+                unclaimed_referral_fees: Uint128::zero(),
             }
         );
     }
@@ -588,6 +688,8 @@ mod tests {
                     swap_paused: None,
                     redeem_rewards_paused: None,
                     reimburse_slashing_paused: None,
+                    delegate_pending_paused: None,
+                    reason: None,
                 },
             },
         )
@@ -611,6 +713,7 @@ mod tests {
                     redeem_rewards_paused: true,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -630,6 +733,8 @@ mod tests {
                     swap_paused: Some(false),
                     redeem_rewards_paused: None,
                     reimburse_slashing_paused: Some(true),
+                    delegate_pending_paused: Some(true),
+                    reason: None,
                 },
             },
         )
@@ -647,7 +752,8 @@ mod tests {
                 claim_airdrops_paused: false,
                 redeem_rewards_paused: true,
                 swap_paused: false,
-                reimburse_slashing_paused: true
+                reimburse_slashing_paused: true,
+                delegate_pending_paused: true,
             }
         );
     }
@@ -832,10 +938,20 @@ mod tests {
                     protocol_withdraw_fee: None,
                     protocol_deposit_fee: None,
                     airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
                     unbonding_period: None,
                     undelegation_cooldown: None,
                     swap_cooldown: None,
                     reinvest_cooldown: None,
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -856,10 +972,20 @@ mod tests {
                     protocol_withdraw_fee: None,
                     protocol_deposit_fee: None,
                     airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
                     unbonding_period: None,
                     undelegation_cooldown: None,
                     swap_cooldown: None,
                     reinvest_cooldown: None,
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -883,10 +1009,20 @@ mod tests {
                     protocol_withdraw_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     protocol_deposit_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
                     unbonding_period: Some(100u64),
                     undelegation_cooldown: Some(10000u64),
                     swap_cooldown: Some(123u64),
                     reinvest_cooldown: Some(234u64),
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -909,10 +1045,20 @@ mod tests {
                     protocol_withdraw_fee: Some(Decimal::from_ratio(6_u128, 100_u128)),
                     protocol_deposit_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     airdrop_registry_contract: Some("airdrop_registry_contract".to_string()),
+                    unbonding_nft_contract: None,
                     unbonding_period: Some(100u64),
                     undelegation_cooldown: Some(10000u64),
                     swap_cooldown: Some(123u64),
                     reinvest_cooldown: Some(234u64),
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -935,10 +1081,20 @@ mod tests {
                     protocol_withdraw_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     protocol_deposit_fee: Some(Decimal::from_ratio(8_u128, 100_u128)),
                     airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
                     unbonding_period: Some(100u64),
                     undelegation_cooldown: Some(10000u64),
                     swap_cooldown: Some(123u64),
                     reinvest_cooldown: Some(234u64),
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -961,10 +1117,30 @@ mod tests {
                     protocol_withdraw_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     protocol_deposit_fee: Some(Decimal::from_ratio(2_u128, 100_u128)),
                     airdrop_registry_contract: Some("airdrop_registry_contract".to_string()),
+                    unbonding_nft_contract: None,
                     unbonding_period: Some(100u64),
                     undelegation_cooldown: Some(10000u64),
                     swap_cooldown: Some(123u64),
                     reinvest_cooldown: Some(234u64),
+                    split_policy: Some(SplitPolicy {
+                        deposit_threshold: Some(Uint128::new(5000_u128)),
+                        undelegation_threshold: None,
+                    }),
+                    epoch_delegation: Some(true),
+                    delegate_pending_cooldown: Some(345u64),
+                    reward_sniping_policy: Some(RewardSnipingPolicy {
+                        include_pending_rewards: true,
+                        reinvest_window: 60,
+                        reinvest_window_fee: Some(Decimal::from_ratio(1_u128, 100_u128)),
+                    }),
+                    reward_fee_as_shares: Some(true),
+                    fee_destination_policy: Some(FeeDestinationPolicy {
+                        deposit_fee: FeeDestination::Pool,
+                        withdraw_fee: FeeDestination::ProtocolFeeRecipients,
+                    }),
+                    referral_fee_share: Some(Decimal::from_ratio(3_u128, 10_u128)),
+                    guardian: None,
+                    circuit_breaker_policy: None,
                 },
             },
         )
@@ -978,8 +1154,8 @@ mod tests {
             Addr::unchecked("cw20_token_contract")
         );
         assert_eq!(
-            config.protocol_fee_contract,
-            Addr::unchecked("protocol_fee_contract")
+            config.protocol_fee_recipients,
+            get_protocol_fee_recipients()
         );
         assert_eq!(
             config.airdrop_withdrawal_contract,
@@ -1005,6 +1181,120 @@ mod tests {
         assert_eq!(config.undelegation_cooldown, 10000u64);
         assert_eq!(config.swap_cooldown, 123u64);
         assert_eq!(config.reinvest_cooldown, 234u64);
+        assert_eq!(
+            config.split_policy,
+            SplitPolicy {
+                deposit_threshold: Some(Uint128::new(5000_u128)),
+                undelegation_threshold: None,
+            }
+        );
+        assert!(config.epoch_delegation);
+        assert_eq!(config.delegate_pending_cooldown, 345u64);
+        assert_eq!(
+            config.reward_sniping_policy,
+            RewardSnipingPolicy {
+                include_pending_rewards: true,
+                reinvest_window: 60,
+                reinvest_window_fee: Some(Decimal::from_ratio(1_u128, 100_u128)),
+            }
+        );
+        assert!(config.reward_fee_as_shares);
+        assert_eq!(
+            config.fee_destination_policy,
+            FeeDestinationPolicy {
+                deposit_fee: FeeDestination::Pool,
+                withdraw_fee: FeeDestination::ProtocolFeeRecipients,
+            }
+        );
+        assert_eq!(
+            config.referral_fee_share,
+            Decimal::from_ratio(3_u128, 10_u128)
+        );
+
+        /*
+           Test - 3. Deposit fee and reinvest window fee together cannot exceed the deposit fee cap
+        */
+        // Raising the deposit fee while a 1% window fee is set.
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                config_request: ConfigUpdateRequest {
+                    min_deposit: None,
+                    max_deposit: None,
+                    cw20_token_contract: None,
+                    protocol_reward_fee: None,
+                    protocol_withdraw_fee: None,
+                    protocol_deposit_fee: Some(Decimal::from_ratio(5_u128, 100_u128)),
+                    airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
+                    unbonding_period: None,
+                    undelegation_cooldown: None,
+                    swap_cooldown: None,
+                    reinvest_cooldown: None,
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
+                },
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ProtocolFeeAboveLimit {}));
+
+        // Raising the window fee while a 2% deposit fee is set.
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateConfig {
+                config_request: ConfigUpdateRequest {
+                    min_deposit: None,
+                    max_deposit: None,
+                    cw20_token_contract: None,
+                    protocol_reward_fee: None,
+                    protocol_withdraw_fee: None,
+                    protocol_deposit_fee: None,
+                    airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
+                    unbonding_period: None,
+                    undelegation_cooldown: None,
+                    swap_cooldown: None,
+                    reinvest_cooldown: None,
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: Some(RewardSnipingPolicy {
+                        include_pending_rewards: true,
+                        reinvest_window: 60,
+                        reinvest_window_fee: Some(Decimal::from_ratio(4_u128, 100_u128)),
+                    }),
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
+                },
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ProtocolFeeAboveLimit {}));
+
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(
+            config.protocol_deposit_fee,
+            Decimal::from_ratio(2_u128, 100_u128)
+        );
+        assert_eq!(
+            config.reward_sniping_policy.reinvest_window_fee,
+            Some(Decimal::from_ratio(1_u128, 100_u128))
+        );
     }
 
     #[test]
@@ -1024,8 +1314,7 @@ mod tests {
         */
         deps.querier
             .update_staking("uluna", &*get_validators(), &*get_delegations());
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
         VALIDATOR_META
             .save(
                 deps.as_mut().storage,
@@ -1100,7 +1389,7 @@ mod tests {
 
         let state = STATE.load(deps.as_mut().storage).unwrap();
         assert_eq!(state.total_staked, Uint128::new(3000_u128));
-        assert_eq!(state.exchange_rate, Decimal::one());
+        assert_eq!(state.exchange_rate, Decimal256::one());
 
         /*
             Test - 2. There is some slashing
@@ -1158,8 +1447,7 @@ mod tests {
             &*get_validators_test_2(),
             &*get_delegations_test_2(),
         );
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
         VALIDATOR_META
             .save(
                 deps.as_mut().storage,
@@ -1227,7 +1515,7 @@ mod tests {
         assert_eq!(state.total_staked, Uint128::new(2500_u128));
         assert_eq!(
             state.exchange_rate,
-            Decimal::from_ratio(2500_u128, 3000_u128)
+            Decimal256::from_ratio(2500_u64, 3000_u64)
         );
 
         /*
@@ -1286,8 +1574,7 @@ mod tests {
             &*get_validators_test_3(),
             &*get_delegations_test_3(),
         );
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
         VALIDATOR_META
             .save(
                 deps.as_mut().storage,
@@ -1355,7 +1642,93 @@ mod tests {
         assert_eq!(state.total_staked, Uint128::new(3500_u128));
         assert_eq!(
             state.exchange_rate,
-            Decimal::from_ratio(3500_u128, 3000_u128)
+            Decimal256::from_ratio(3500_u64, 3000_u64)
+        );
+    }
+
+    #[test]
+    fn test_query_shares_invariant() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        let res: SharesInvariantResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::SharesInvariant {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            res,
+            SharesInvariantResponse {
+                total_shares: Uint128::new(3000_u128),
+                cw20_total_supply: Uint128::new(3000_u128),
+                is_consistent: true
+            }
+        );
+
+        deps.querier
+            .update_stader_balances(Some(Uint128::new(3100_u128)), None);
+        let res: SharesInvariantResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::SharesInvariant {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            res,
+            SharesInvariantResponse {
+                total_shares: Uint128::new(3000_u128),
+                cw20_total_supply: Uint128::new(3100_u128),
+                is_consistent: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_query_rounding_dust() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![
+                        Addr::unchecked("valid0001"),
+                        Addr::unchecked("valid0002"),
+                        Addr::unchecked("valid0003"),
+                    ];
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        update_total_shares(&mut deps, Uint128::new(2000_u128));
+
+        // 991 uluna at an exchange rate of 1.5 is 660.66 shares, which rounds down to 660.
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1002_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(2660_u128));
+
+        let res: RoundingDustResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::RoundingDust {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            res,
+            RoundingDustResponse {
+                rounding_dust: Decimal256::from_str("0.999999999999999999").unwrap(),
+                rounding_dust_amount: Uint128::zero()
+            }
         );
     }
 
@@ -1483,6 +1856,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: true,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -1509,6 +1883,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -1589,6 +1964,7 @@ mod tests {
                     redeem_rewards_paused: true,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -1615,6 +1991,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -2370,6 +2747,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -2377,7 +2755,9 @@ mod tests {
             deps.as_mut(),
             env.clone(),
             mock_info("other", &[Coin::new(10_u128, "uluna".to_string())]),
-            ExecuteMsg::Deposit {},
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::OperationPaused(String { .. })));
@@ -2396,6 +2776,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -2463,7 +2844,9 @@ mod tests {
             deps.as_mut(),
             env.clone(),
             mock_info("other", &[Coin::new(120_u128, "uluna".to_string())]),
-            ExecuteMsg::Deposit {},
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::MaxDeposit {}));
@@ -2484,7 +2867,9 @@ mod tests {
             deps.as_mut(),
             env.clone(),
             mock_info("other", &[Coin::new(5_u128, "uluna".to_string())]),
-            ExecuteMsg::Deposit {},
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::MinDeposit {}));
@@ -2516,8 +2901,7 @@ mod tests {
             .unwrap();
         deps.querier
             .update_staking("uluna", &*get_validators(), &*get_delegations());
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
         VALIDATOR_META
             .save(
                 deps.as_mut().storage,
@@ -2555,7 +2939,9 @@ mod tests {
             deps.as_mut(),
             env.clone(),
             mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
-            ExecuteMsg::Deposit {},
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap();
         let config = CONFIG.load(deps.as_mut().storage).unwrap();
@@ -2584,6 +2970,7 @@ mod tests {
         );
         let state = STATE.load(deps.as_mut().storage).unwrap();
         assert_eq!(state.total_staked, Uint128::new(3990_u128));
+        assert_eq!(state.total_shares, Uint128::new(3990_u128));
         let val1_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid1).unwrap();
         assert_eq!(
             val1_meta,
@@ -2596,564 +2983,622 @@ mod tests {
     }
 
     #[test]
-    fn test_queue_undelegation() {
+    fn test_deposit_first_depositor_inflation() {
         let mut deps = mock_dependencies(&[]);
         let info = mock_info("creator", &[]);
         let env = mock_env();
 
-        let _res = instantiate_contract(&mut deps, &info, &env);
+        instantiate_contract(&mut deps, &info, &env);
 
-        CONFIG
+        let valid1 = Addr::unchecked("valid0001");
+        STATE
             .update(
                 deps.as_mut().storage,
-                |mut config| -> Result<_, ContractError> {
-                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
-                    Ok(config)
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone()];
+                    Ok(state)
                 },
             )
             .unwrap();
+        deps.querier.update_staking("uluna", &get_validators(), &[]);
 
         /*
-           Test - 1. Successful undelegation
+           Test - 1. First deposit which cannot cover the locked shares
         */
-        let valid1 = Addr::unchecked("valid0001");
-        let valid2 = Addr::unchecked("valid0002");
-        let valid3 = Addr::unchecked("valid0003");
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("attacker", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ZeroMint {}));
+
+        /*
+           Test - 2. First deposit locks shares in the contract
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("attacker", &[Coin::new(2000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "protocol_fee_contract".to_string(),
+                    amount: vec![Coin::new(20_u128, "uluna")]
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(1980_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "attacker".to_string(),
+                        amount: Uint128::new(980_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: MOCK_CONTRACT_ADDR.to_string(),
+                        amount: Uint128::new(1000_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(1980_u128));
+        assert_eq!(state.total_shares, Uint128::new(1980_u128));
+
+        /*
+           Test - 3. Stake donated to inflate the exchange rate cannot round a deposit down to nothing
+        */
+        deps.querier.update_staking(
+            "uluna",
+            &get_validators(),
+            &[FullDelegation {
+                delegator: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                validator: valid1.to_string(),
+                amount: Coin::new(10_001_980, "uluna"),
+                can_redelegate: Coin::new(10_001_980, "uluna"),
+                accumulated_rewards: vec![],
+            }],
+        );
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("victim", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ZeroMint {}));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(1980_u128));
+    }
+
+    #[test]
+    fn test_deposit_reward_sniping_policy() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
         STATE
             .update(
                 deps.as_mut().storage,
                 |mut state| -> Result<_, ContractError> {
-                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
-                    state.total_staked = Uint128::new(3000_u128);
-                    state.current_undelegation_batch_id = 3;
+                    state.validators = vec![
+                        Addr::unchecked("valid0001"),
+                        Addr::unchecked("valid0002"),
+                        Addr::unchecked("valid0003"),
+                    ];
                     Ok(state)
                 },
             )
             .unwrap();
         deps.querier
-            .update_staking("uluna", &*get_validators(), &*get_delegations());
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
-        VALIDATOR_META
-            .save(
-                deps.as_mut().storage,
-                &valid2,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
-                },
-            )
-            .unwrap();
-        VALIDATOR_META
-            .save(
-                deps.as_mut().storage,
-                &valid3,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
-                },
-            )
-            .unwrap();
-        VALIDATOR_META
-            .save(
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        deps.querier.update_balance(
+            Addr::unchecked("reward_contract"),
+            vec![Coin::new(243_u128, "uluna")],
+        );
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+
+        /*
+           Test - 1. Deposits are priced with pending rewards
+        */
+        CONFIG
+            .update(
                 deps.as_mut().storage,
-                &valid1,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
+                |mut config| -> Result<_, ContractError> {
+                    config.reward_sniping_policy.include_pending_rewards = true;
+                    Ok(config)
                 },
             )
             .unwrap();
-        BATCH_UNDELEGATION_REGISTRY
-            .save(
+        // 60 uluna accrued on validators and 243 in the reward contract, less the 1% reward fee,
+        // gives an exchange rate of 3300 / 3000.
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
+        assert_eq!(state.total_shares, Uint128::new(3900_u128));
+        // Pending rewards are not counted in the stored exchange rate.
+        assert_eq!(state.exchange_rate, Decimal256::one());
+
+        /*
+           Test - 2. Deposits in the reinvest window are charged an extra fee
+        */
+        CONFIG
+            .update(
                 deps.as_mut().storage,
-                U64Key::new(3),
-                &BatchUndelegationRecord {
-                    undelegated_tokens: Uint128::new(1000_u128),
-                    create_time: Default::default(),
-                    est_release_time: None,
-                    reconciled: false,
-                    undelegation_er: Default::default(),
-                    undelegated_stake: Default::default(),
-                    unbonding_slashing_ratio: Default::default(),
+                |mut config| -> Result<_, ContractError> {
+                    config.reward_sniping_policy = RewardSnipingPolicy {
+                        include_pending_rewards: false,
+                        reinvest_window: 5,
+                        reinvest_window_fee: Some(Decimal::from_ratio(1_u128, 100_u128)),
+                    };
+                    Ok(config)
                 },
             )
             .unwrap();
-        let user1 = Addr::unchecked("user1");
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
         let res = execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("cw20_contract", &[]),
-            ExecuteMsg::Receive(Cw20ReceiveMsg {
-                sender: "user1".to_string(),
-                amount: Uint128::new(100_u128),
-                msg: to_binary(&Cw20HookMsg::QueueUndelegate {}).unwrap(),
-            }),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap();
-        assert!(res.messages.is_empty());
-        let user_undelegation_record = USERS
-            .load(deps.as_mut().storage, (&user1, U64Key::new(3)))
-            .unwrap();
-        assert_eq!(
-            user_undelegation_record,
-            UndelegationInfo {
-                batch_id: 3,
-                token_amount: Uint128::new(100_u128)
-            }
-        );
-        let batch_undel_record = BATCH_UNDELEGATION_REGISTRY
-            .load(deps.as_mut().storage, U64Key::new(3))
-            .unwrap();
         assert_eq!(
-            batch_undel_record.undelegated_tokens,
-            Uint128::new(1100_u128)
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "protocol_fee_contract".to_string(),
+                amount: vec![Coin::new(20_u128, "uluna")]
+            })
         );
-    }
-
-    #[test]
-    fn test_reinvest() {
-        let mut deps = mock_dependencies(&[]);
-        let info = mock_info("creator", &[]);
-        let env = mock_env();
-
-        let _res = instantiate_contract(&mut deps, &info, &env);
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(3980_u128));
 
         /*
-           reinvest paused
+           Test - 3. Deposits in the reinvest window are blocked
         */
-        OPERATION_CONTROLS
-            .save(
+        CONFIG
+            .update(
                 deps.as_mut().storage,
-                &OperationControls {
-                    deposit_paused: false,
-                    queue_undelegate_paused: false,
-                    undelegate_paused: false,
-                    withdraw_paused: false,
-                    reinvest_paused: true,
-                    reconcile_paused: false,
-                    claim_airdrops_paused: false,
-                    redeem_rewards_paused: false,
-                    swap_paused: false,
-                    reimburse_slashing_paused: false,
+                |mut config| -> Result<_, ContractError> {
+                    config.reward_sniping_policy.reinvest_window_fee = None;
+                    Ok(config)
                 },
             )
             .unwrap();
         let err = execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("other", &[]),
-            ExecuteMsg::Reinvest {},
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap_err();
-        assert!(matches!(err, ContractError::OperationPaused(String { .. })));
-
-        OPERATION_CONTROLS
-            .save(
-                deps.as_mut().storage,
-                &OperationControls {
-                    deposit_paused: false,
-                    queue_undelegate_paused: false,
-                    undelegate_paused: false,
-                    withdraw_paused: false,
-                    reinvest_paused: false,
-                    reconcile_paused: false,
-                    claim_airdrops_paused: false,
-                    redeem_rewards_paused: false,
-                    swap_paused: false,
-                    reimburse_slashing_paused: false,
-                },
-            )
-            .unwrap();
+        assert!(matches!(err, ContractError::DepositInReinvestWindow {}));
 
+        /*
+           Test - 4. Deposits right after reinvest are outside the window
+        */
         STATE
             .update(
                 deps.as_mut().storage,
                 |mut state| -> Result<_, ContractError> {
-                    state.last_reinvest_time = env.block.time.minus_seconds(3);
+                    state.last_reinvest_time = env.block.time;
                     Ok(state)
                 },
             )
             .unwrap();
-        let err = execute(
+        execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("not-creator", &[]),
-            ExecuteMsg::Reinvest {},
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
-        .unwrap_err();
-        assert!(matches!(err, ContractError::ReinvestInCooldown {}));
-        let state = STATE.load(deps.as_mut().storage).unwrap();
-        assert_eq!(state.last_reinvest_time, env.block.time.minus_seconds(3));
+        .unwrap();
+    }
+
+    #[test]
+    fn test_deposit_split_success() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
 
-        /*
-           Test - 1. Successful run
-        */
         let valid1 = Addr::unchecked("valid0001");
         let valid2 = Addr::unchecked("valid0002");
         let valid3 = Addr::unchecked("valid0003");
-        let config = CONFIG.load(deps.as_mut().storage).unwrap();
-        deps.querier.update_balance(
-            config.reward_contract.clone(),
-            vec![Coin::new(1000_u128, "uluna".to_string())],
-        );
-        deps.querier
-            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
-        deps.querier
-            .update_staking("uluna", &*get_validators(), &*get_delegations());
-        STATE
+        CONFIG
             .update(
                 deps.as_mut().storage,
-                |mut state| -> Result<_, ContractError> {
-                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
-                    state.total_staked = Uint128::new(3000_u128);
-                    Ok(state)
-                },
-            )
-            .unwrap();
-        VALIDATOR_META
-            .save(
-                deps.as_mut().storage,
-                &valid2,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
-                },
-            )
-            .unwrap();
-        VALIDATOR_META
-            .save(
-                deps.as_mut().storage,
-                &valid3,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
+                |mut config| -> Result<_, ContractError> {
+                    config.split_policy.deposit_threshold = Some(Uint128::new(5000_u128));
+                    Ok(config)
                 },
             )
             .unwrap();
-        VALIDATOR_META
-            .save(
+        STATE
+            .update(
                 deps.as_mut().storage,
-                &valid1,
-                &VMeta {
-                    staked: Uint128::new(1000_u128),
-                    slashed: Default::default(),
-                    filled: Default::default(),
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(6000_u128);
+                    Ok(state)
                 },
             )
             .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_uneven_delegations());
+        update_total_shares(&mut deps, Uint128::new(6000_u128));
+
+        /*
+           Test - 1. Deposit below threshold goes to a single validator
+        */
         let res = execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("creator", &[]),
-            ExecuteMsg::Reinvest {},
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
         )
         .unwrap();
-        assert_eq!(res.messages.len(), 2);
-        assert!(check_equal_vec(
+        assert_eq!(res.messages.len(), 3);
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(StakingMsg::Delegate {
+                validator: valid1.to_string(),
+                amount: Coin::new(990_u128, "uluna".to_string())
+            })
+        );
+
+        /*
+           Test - 2. Deposit above threshold is split towards an even stake
+        */
+        // The mocked delegations do not include the first deposit.
+        update_total_shares(&mut deps, Uint128::new(6000_u128));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(10000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(
             res.messages,
             vec![
-                SubMsg::new(WasmMsg::Execute {
-                    contract_addr: config.reward_contract.to_string(),
-                    msg: to_binary(&RewardExecuteMsg::Transfer {
-                        reward_amount: Uint128::new(990_u128),
-                        reward_withdraw_contract: env.contract.address,
-                        protocol_fee_amount: Uint128::new(10_u128),
-                        protocol_fee_contract: config.protocol_fee_contract
-                    })
-                    .unwrap(),
-                    funds: vec![]
+                SubMsg::new(BankMsg::Send {
+                    to_address: "protocol_fee_contract".to_string(),
+                    amount: vec![Coin::new(100_u128, "uluna")]
                 }),
                 SubMsg::new(StakingMsg::Delegate {
                     validator: valid1.to_string(),
-                    amount: Coin::new(990_u128, "uluna".to_string())
-                })
-            ]
-        ));
-        let state = STATE.load(deps.as_mut().storage).unwrap();
-        assert_eq!(state.last_reinvest_time, env.block.time);
-        assert_eq!(state.total_staked, Uint128::new(3990_u128));
-        assert_eq!(
-            state.exchange_rate,
-            Decimal::from_ratio(3990_u128, 3000_u128)
+                    amount: Coin::new(4300_u128, "uluna".to_string())
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid2.to_string(),
+                    amount: Coin::new(3300_u128, "uluna".to_string())
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid3.to_string(),
+                    amount: Coin::new(2300_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "other".to_string(),
+                        amount: Uint128::new(9900_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
         );
-        assert_eq!(state.last_reinvest_time, env.block.time);
+        // check_slashing syncs the tracked stake with the chain before the deposit is added.
         let val1_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid1).unwrap();
-        assert_eq!(val1_meta.staked, Uint128::new(1990_u128));
+        let val2_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid2).unwrap();
+        let val3_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid3).unwrap();
+        assert_eq!(val1_meta.staked, Uint128::new(5300_u128));
+        assert_eq!(val2_meta.staked, Uint128::new(5300_u128));
+        assert_eq!(val3_meta.staked, Uint128::new(5300_u128));
     }
 
     #[test]
-    fn test_claim_airdrops_fail() {
+    fn test_deposit_epoch_delegation() {
         let mut deps = mock_dependencies(&[]);
         let info = mock_info("creator", &[]);
-        let env = mock_env();
+        let mut env = mock_env();
 
-        let _res = instantiate_contract(&mut deps, &info, &env);
+        instantiate_contract(&mut deps, &info, &env);
 
-        /*
-           Test - 1. Airdrop not registered
-        */
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
         CONFIG
             .update(
                 deps.as_mut().storage,
                 |mut config| -> Result<_, ContractError> {
-                    config.airdrop_withdrawal_contract =
-                        Addr::unchecked("airdrop_withdrawal_contract");
-                    config.airdrop_registry_contract = Addr::unchecked("airdrop_registry_contract");
+                    config.epoch_delegation = true;
+                    config.delegate_pending_cooldown = 100;
                     Ok(config)
                 },
             )
             .unwrap();
-        let err = execute(
-            deps.as_mut(),
-            env.clone(),
-            mock_info("creator", &[]),
-            ExecuteMsg::ClaimAirdrops {
-                rates: vec![
-                    AirdropRate {
-                        denom: "unreg_token".to_string(), // special name to test for an unregistered token
-                        amount: Uint128::new(1000_u128),
-                        stage: 0,
-                        proof: vec![
-                            "unreg_token_proof1".to_string(),
-                            "unreg_token_proof2".to_string(),
-                        ],
-                    },
-                    AirdropRate {
-                        denom: "anc".to_string(),
-                        amount: Uint128::new(1000_u128),
-                        stage: 0,
-                        proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
-                    },
-                ],
-            },
-        )
-        .unwrap_err();
-        assert!(matches!(
-            err,
-            ContractError::AirdropNotRegistered(String { .. })
-        ));
-    }
-
-    #[test]
-    fn test_claim_airdrops_success() {
-        let mut deps = mock_dependencies(&[]);
-        let info = mock_info("creator", &[]);
-        let env = mock_env();
-
-        let _res = instantiate_contract(&mut deps, &info, &env);
-
-        /*
-           Test - 1. Success
-        */
-        CONFIG
+        STATE
             .update(
                 deps.as_mut().storage,
-                |mut config| -> Result<_, ContractError> {
-                    config.airdrop_withdrawal_contract =
-                        Addr::unchecked("airdrop_withdrawal_contract");
-                    config.airdrop_registry_contract = Addr::unchecked("airdrop_registry_contract");
-                    Ok(config)
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    Ok(state)
                 },
             )
             .unwrap();
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+
+        /*
+           Test - 1. Deposit is minted and held back even if every validator is jailed
+        */
+        deps.querier
+            .update_staking("uluna", &[], &get_delegations());
         let res = execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("creator", &[]),
-            ExecuteMsg::ClaimAirdrops {
-                rates: vec![
-                    AirdropRate {
-                        denom: "anc".to_string(),
-                        amount: Uint128::new(1000_u128),
-                        stage: 0,
-                        proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
-                    },
-                    AirdropRate {
-                        denom: "mir".to_string(),
-                        amount: Uint128::new(2000_u128),
-                        stage: 0,
-                        proof: vec!["mir_proof1".to_string(), "mir_proof2".to_string()],
-                    },
-                ],
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
             },
         )
         .unwrap();
         let config = CONFIG.load(deps.as_mut().storage).unwrap();
-        assert_eq!(res.messages.len(), 4);
-        assert!(check_equal_vec(
+        assert_eq!(
             res.messages,
             vec![
-                SubMsg::new(WasmMsg::Execute {
-                    contract_addr: "anc_airdrop_contract".to_string(),
-                    msg: to_binary(&MerkleAirdropMsg::Claim {
-                        stage: 0,
-                        amount: Uint128::new(1000_u128),
-                        proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
-                    })
-                    .unwrap(),
-                    funds: vec![]
-                }),
-                SubMsg::new(WasmMsg::Execute {
-                    contract_addr: "mir_airdrop_contract".to_string(),
-                    msg: to_binary(&MerkleAirdropMsg::Claim {
-                        stage: 0,
-                        amount: Uint128::new(2000_u128),
-                        proof: vec!["mir_proof1".to_string(), "mir_proof2".to_string()],
-                    })
-                    .unwrap(),
-                    funds: vec![]
-                }),
-                SubMsg::new(WasmMsg::Execute {
-                    contract_addr: "anc_cw20_contract".to_string(),
-                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                        recipient: config.airdrop_withdrawal_contract.to_string(),
-                        amount: Uint128::new(1000_u128)
-                    })
-                    .unwrap(),
-                    funds: vec![]
+                SubMsg::new(BankMsg::Send {
+                    to_address: "protocol_fee_contract".to_string(),
+                    amount: vec![Coin::new(10_u128, "uluna")]
                 }),
                 SubMsg::new(WasmMsg::Execute {
-                    contract_addr: "mir_cw20_contract".to_string(),
-                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
-                        recipient: config.airdrop_withdrawal_contract.to_string(),
-                        amount: Uint128::new(2000_u128)
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "other".to_string(),
+                        amount: Uint128::new(990_u128)
                     })
                     .unwrap(),
                     funds: vec![]
                 })
             ]
-        ));
-    }
-
-    #[test]
-    fn test_compute_withdrawable_funds_fail() {
-        let mut deps = mock_dependencies(&[]);
-        let info = mock_info("creator", &[]);
-        let env = mock_env();
-
-        let _res = instantiate_contract(&mut deps, &info, &env);
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
+        assert_eq!(state.pending_delegation, Uint128::new(990_u128));
 
-        let user1 = Addr::unchecked("user1");
+        // Pending luna counts towards the exchange rate.
+        update_total_shares(&mut deps, Uint128::new(3990_u128));
+        check_slashing(&mut deps.as_mut(), &env).unwrap();
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
+        assert_eq!(state.exchange_rate, Decimal256::one());
 
         /*
-           Test - 1. Undelegation batch not found
+           Test - 2. Delegate pending in cooldown
         */
-        let err = compute_withdrawable_funds(deps.as_mut().storage, 0, &user1).unwrap_err();
-        assert!(matches!(err, ContractError::UndelegationBatchNotFound {}));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::DelegatePending {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::DelegatePendingInCooldown {}));
 
         /*
-           Test - 2. Batch not reconciled
+           Test - 3. Pending pool is kept while all validators are jailed
         */
-        BATCH_UNDELEGATION_REGISTRY
-            .save(
-                deps.as_mut().storage,
-                U64Key::new(1),
-                &BatchUndelegationRecord {
-                    undelegated_tokens: Uint128::new(10000_u128),
-                    create_time: Default::default(),
-                    est_release_time: None,
-                    reconciled: false,
-                    undelegation_er: Default::default(),
-                    undelegated_stake: Default::default(),
-                    unbonding_slashing_ratio: Default::default(),
-                },
-            )
-            .unwrap();
-        let err = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap_err();
-        assert!(matches!(
-            err,
-            ContractError::UndelegationBatchNotReconciled {}
-        ));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::DelegatePending {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AllValidatorsJailed {}));
 
         /*
-            Test - 3. User undelegation record not found
+           Test - 4. Successful epoch delegation
         */
-        BATCH_UNDELEGATION_REGISTRY
-            .save(
-                deps.as_mut().storage,
-                U64Key::new(1),
-                &BatchUndelegationRecord {
-                    undelegated_tokens: Uint128::new(10000_u128),
-                    create_time: Default::default(),
-                    est_release_time: None,
-                    reconciled: true,
-                    undelegation_er: Default::default(),
-                    undelegated_stake: Default::default(),
-                    unbonding_slashing_ratio: Default::default(),
-                },
-            )
-            .unwrap();
-        let err = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap_err();
-        assert!(matches!(err, ContractError::UndelegationEntryNotFound {}));
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        env.block.time = env.block.time.plus_seconds(200);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::DelegatePending {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Delegate {
+                validator: valid1.to_string(),
+                amount: Coin::new(990_u128, "uluna".to_string())
+            })]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
+        assert_eq!(state.pending_delegation, Uint128::zero());
+        assert_eq!(state.last_delegate_pending_time, env.block.time);
+        let val1_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid1).unwrap();
+        assert_eq!(val1_meta.staked, Uint128::new(1990_u128));
     }
 
     #[test]
-    fn test_compute_withdrawable_funds_success() {
+    fn test_queue_undelegation() {
         let mut deps = mock_dependencies(&[]);
         let info = mock_info("creator", &[]);
         let env = mock_env();
 
         let _res = instantiate_contract(&mut deps, &info, &env);
 
-        let user1 = Addr::unchecked("user1");
-
-        /*
-           Test - 1. Success
-        */
-        BATCH_UNDELEGATION_REGISTRY
-            .save(
+        CONFIG
+            .update(
                 deps.as_mut().storage,
-                U64Key::new(1),
-                &BatchUndelegationRecord {
-                    undelegated_tokens: Uint128::new(10000_u128),
-                    create_time: Default::default(),
-                    est_release_time: None,
-                    reconciled: true,
-                    undelegation_er: Decimal::one(),
-                    undelegated_stake: Uint128::new(10000_u128),
-                    unbonding_slashing_ratio: Decimal::from_ratio(3_u128, 4_u128),
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    Ok(config)
                 },
             )
             .unwrap();
-        USERS
-            .save(
-                deps.as_mut().storage,
-                (&user1, U64Key::new(1)),
-                &UndelegationInfo {
-                    batch_id: 1,
-                    token_amount: Uint128::new(1000_u128),
+
+        /*
+           Test - 1. Successful undelegation
+        */
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    state.current_undelegation_batch_id = 3;
+                    Ok(state)
                 },
             )
             .unwrap();
-        let res = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap();
+        deps.querier
+            .update_staking("uluna", &*get_validators(), &*get_delegations());
+        deps.querier
+            .update_stader_balances(Some(Uint128::new(3000_u128)), None);
+        VALIDATOR_META
+            .save(
+                deps.as_mut().storage,
+                &valid2,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
+                },
+            )
+            .unwrap();
+        VALIDATOR_META
+            .save(
+                deps.as_mut().storage,
+                &valid3,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
+                },
+            )
+            .unwrap();
+        VALIDATOR_META
+            .save(
+                deps.as_mut().storage,
+                &valid1,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
+                },
+            )
+            .unwrap();
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(3),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(1000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: false,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+        let user1 = Addr::unchecked("user1");
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "user1".to_string(),
+                amount: Uint128::new(100_u128),
+                msg: to_binary(&Cw20HookMsg::QueueUndelegate { mint_nft: None }).unwrap(),
+            }),
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        let user_undelegation_record = USERS
+            .load(deps.as_mut().storage, (&user1, U64Key::new(3)))
+            .unwrap();
         assert_eq!(
-            res,
-            GetFundsClaimRecord {
-                user_withdrawal_amount: Uint128::new(743_u128),
-                protocol_fee: Uint128::new(7_u128),
-                undelegated_tokens: Uint128::new(1000_u128)
+            user_undelegation_record,
+            UndelegationInfo {
+                batch_id: 3,
+                token_amount: Uint128::new(100_u128)
             }
         );
+        let batch_undel_record = BATCH_UNDELEGATION_REGISTRY
+            .load(deps.as_mut().storage, U64Key::new(3))
+            .unwrap();
+        assert_eq!(
+            batch_undel_record.undelegated_tokens,
+            Uint128::new(1100_u128)
+        );
     }
 
     #[test]
-    fn test_withdraw_funds_to_wallet() {
+    fn test_reinvest() {
         let mut deps = mock_dependencies(&[]);
         let info = mock_info("creator", &[]);
         let env = mock_env();
 
         let _res = instantiate_contract(&mut deps, &info, &env);
 
-        let user1 = Addr::unchecked("user1");
-
         /*
-           withdraw paused
+           reinvest paused
         */
         OPERATION_CONTROLS
             .save(
@@ -3162,13 +3607,14 @@ mod tests {
                     deposit_paused: false,
                     queue_undelegate_paused: false,
                     undelegate_paused: false,
-                    withdraw_paused: true,
-                    reinvest_paused: false,
+                    withdraw_paused: false,
+                    reinvest_paused: true,
                     reconcile_paused: false,
                     claim_airdrops_paused: false,
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -3176,7 +3622,7 @@ mod tests {
             deps.as_mut(),
             env.clone(),
             mock_info("other", &[]),
-            ExecuteMsg::WithdrawFundsToWallet { batch_id: 0 },
+            ExecuteMsg::Reinvest {},
         )
         .unwrap_err();
         assert!(matches!(err, ContractError::OperationPaused(String { .. })));
@@ -3195,69 +3641,2805 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
 
         STATE
-            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
-                state.reconciled_funds_to_withdraw = Uint128::new(1200);
-                Ok(state)
-            })
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.last_reinvest_time = env.block.time.minus_seconds(3);
+                    Ok(state)
+                },
+            )
             .unwrap();
-        BATCH_UNDELEGATION_REGISTRY
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("not-creator", &[]),
+            ExecuteMsg::Reinvest {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ReinvestInCooldown {}));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.last_reinvest_time, env.block.time.minus_seconds(3));
+
+        /*
+           Test - 1. Successful run
+        */
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        deps.querier.update_balance(
+            config.reward_contract.clone(),
+            vec![Coin::new(1000_u128, "uluna".to_string())],
+        );
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        deps.querier
+            .update_staking("uluna", &*get_validators(), &*get_delegations());
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        VALIDATOR_META
             .save(
                 deps.as_mut().storage,
-                U64Key::new(1),
-                &BatchUndelegationRecord {
-                    undelegated_tokens: Uint128::new(10000_u128),
-                    create_time: Default::default(),
-                    est_release_time: None,
-                    reconciled: true,
-                    undelegation_er: Decimal::one(),
-                    undelegated_stake: Uint128::new(10000_u128),
-                    unbonding_slashing_ratio: Decimal::from_ratio(3_u128, 4_u128),
+                &valid2,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
                 },
             )
             .unwrap();
-        USERS
+        VALIDATOR_META
             .save(
                 deps.as_mut().storage,
-                (&user1, U64Key::new(1)),
-                &UndelegationInfo {
-                    batch_id: 1,
-                    token_amount: Uint128::new(1000_u128),
+                &valid3,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
+                },
+            )
+            .unwrap();
+        VALIDATOR_META
+            .save(
+                deps.as_mut().storage,
+                &valid1,
+                &VMeta {
+                    staked: Uint128::new(1000_u128),
+                    slashed: Default::default(),
+                    filled: Default::default(),
                 },
             )
             .unwrap();
         let res = execute(
             deps.as_mut(),
             env.clone(),
-            mock_info("user1", &[]),
-            ExecuteMsg::WithdrawFundsToWallet { batch_id: 1 },
+            mock_info("creator", &[]),
+            ExecuteMsg::Reinvest {},
         )
         .unwrap();
         assert_eq!(res.messages.len(), 2);
+        assert!(check_equal_vec(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.reward_contract.to_string(),
+                    msg: to_binary(&RewardExecuteMsg::Transfer {
+                        reward_amount: Uint128::new(990_u128),
+                        reward_withdraw_contract: env.contract.address,
+                        protocol_fees: vec![ProtocolFee {
+                            recipient: Addr::unchecked("protocol_fee_contract"),
+                            amount: Uint128::new(10_u128),
+                        }],
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(990_u128, "uluna".to_string())
+                })
+            ]
+        ));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.last_reinvest_time, env.block.time);
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
         assert_eq!(
-            res.messages[0],
-            SubMsg::new(BankMsg::Send {
-                to_address: user1.to_string(),
-                amount: vec![Coin::new(743_u128, "uluna".to_string())]
-            })
-        );
-        assert_eq!(
-            res.messages[1],
-            SubMsg::new(BankMsg::Send {
-                to_address: "protocol_fee_contract".to_string(),
-                amount: vec![Coin::new(7_u128, "uluna".to_string())]
-            })
+            state.exchange_rate,
+            Decimal256::from_ratio(3990_u64, 3000_u64)
+        );
+        assert_eq!(state.last_reinvest_time, env.block.time);
+        let val1_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid1).unwrap();
+        assert_eq!(val1_meta.staked, Uint128::new(1990_u128));
+    }
+
+    #[test]
+    fn test_claim_airdrops_fail() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        let _res = instantiate_contract(&mut deps, &info, &env);
+
+        /*
+           Test - 1. Airdrop not registered
+        */
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.airdrop_withdrawal_contract =
+                        Addr::unchecked("airdrop_withdrawal_contract");
+                    config.airdrop_registry_contract = Addr::unchecked("airdrop_registry_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![
+                    AirdropRate {
+                        denom: "unreg_token".to_string(), // special name to test for an unregistered token
+                        amount: Uint128::new(1000_u128),
+                        stage: 0,
+                        proof: vec![
+                            "unreg_token_proof1".to_string(),
+                            "unreg_token_proof2".to_string(),
+                        ],
+                    },
+                    AirdropRate {
+                        denom: "anc".to_string(),
+                        amount: Uint128::new(1000_u128),
+                        stage: 0,
+                        proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
+                    },
+                ],
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::AirdropNotRegistered(String { .. })
+        ));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![AirdropRate {
+                    denom: "disabled_token".to_string(), // special name to test for a disabled airdrop
+                    amount: Uint128::new(1000_u128),
+                    stage: 0,
+                    proof: vec!["disabled_token_proof1".to_string()],
+                }],
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::AirdropDisabled(String { .. })));
+    }
+
+    #[test]
+    fn test_claim_airdrops_success() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        let _res = instantiate_contract(&mut deps, &info, &env);
+
+        /*
+           Test - 1. Success
+        */
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.airdrop_withdrawal_contract =
+                        Addr::unchecked("airdrop_withdrawal_contract");
+                    config.airdrop_registry_contract = Addr::unchecked("airdrop_registry_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![
+                    AirdropRate {
+                        denom: "anc".to_string(),
+                        amount: Uint128::new(1000_u128),
+                        stage: 0,
+                        proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
+                    },
+                    AirdropRate {
+                        denom: "mir".to_string(),
+                        amount: Uint128::new(2000_u128),
+                        stage: 0,
+                        proof: vec!["mir_proof1".to_string(), "mir_proof2".to_string()],
+                    },
+                ],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "anc_airdrop_contract".to_string(),
+                        msg: to_binary(&MerkleAirdropMsg::Claim {
+                            stage: 0,
+                            amount: Uint128::new(1000_u128),
+                            proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
+                        })
+                        .unwrap(),
+                        funds: vec![]
+                    },
+                    0
+                ),
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "mir_airdrop_contract".to_string(),
+                        msg: to_binary(&MerkleAirdropMsg::Claim {
+                            stage: 0,
+                            amount: Uint128::new(2000_u128),
+                            proof: vec!["mir_proof1".to_string(), "mir_proof2".to_string()],
+                        })
+                        .unwrap(),
+                        funds: vec![]
+                    },
+                    1
+                ),
+            ]
+        );
+
+        /*
+           Test - 2. The reply forwards the balance the claim actually paid out
+        */
+        deps.querier.update_stader_balances(
+            None,
+            Some(HashMap::from([(
+                Addr::unchecked(MOCK_CONTRACT_ADDR),
+                Uint128::new(900_u128),
+            )])),
+        );
+        let res = reply(
+            deps.as_mut(),
+            env.clone(),
+            Reply {
+                id: 0,
+                result: ContractResult::Ok(SubMsgExecutionResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "anc_cw20_contract".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "airdrop_withdrawal_contract".to_string(),
+                    amount: Uint128::new(900_u128)
+                })
+                .unwrap(),
+                funds: vec![]
+            })]
+        );
+        let claimed: AirdropsClaimedResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AirdropsClaimed {
+                    denom: "anc".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(claimed.amount, Uint128::new(900_u128));
+
+        /*
+           Test - 3. A failed claim is reported and nothing is forwarded
+        */
+        let res = reply(
+            deps.as_mut(),
+            env.clone(),
+            Reply {
+                id: 1,
+                result: ContractResult::Err("already claimed".to_string()),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "airdrop_claim_failed"),
+                attr("denom", "mir"),
+                attr("stage", "0"),
+                attr("error", "already claimed"),
+            ]
+        );
+        assert!(AIRDROPS_CLAIMED
+            .may_load(deps.as_mut().storage, "mir")
+            .unwrap()
+            .is_none());
+
+        let err = reply(
+            deps.as_mut(),
+            env.clone(),
+            Reply {
+                id: 1,
+                result: ContractResult::Err("already claimed".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnknownReplyId(1)));
+
+        /*
+           Test - 4. Only successful claims are recorded, and recorded stages are rejected
+        */
+        let history: AirdropClaimHistoryResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AirdropClaimHistory {
+                    denom: "ANC".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            history,
+            AirdropClaimHistoryResponse {
+                denom: "anc".to_string(),
+                claimed_stages: vec![ClaimedAirdropStage {
+                    stage: 0,
+                    amount: Uint128::new(900_u128),
+                    claimed_at: env.block.time,
+                }],
+            }
+        );
+        let history: AirdropClaimHistoryResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AirdropClaimHistory {
+                    denom: "mir".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(history.claimed_stages.is_empty());
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![AirdropRate {
+                    denom: "anc".to_string(),
+                    amount: Uint128::new(1000_u128),
+                    stage: 0,
+                    proof: vec!["anc_proof1".to_string(), "anc_proof2".to_string()],
+                }],
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::AirdropStageAlreadyClaimed(String { .. }, 0)
+        ));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![
+                    AirdropRate {
+                        denom: "mir".to_string(),
+                        amount: Uint128::new(2000_u128),
+                        stage: 0,
+                        proof: vec!["mir_proof1".to_string()],
+                    },
+                    AirdropRate {
+                        denom: "MIR".to_string(),
+                        amount: Uint128::new(2000_u128),
+                        stage: 0,
+                        proof: vec!["mir_proof1".to_string()],
+                    },
+                ],
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::AirdropStageAlreadyClaimed(String { .. }, 0)
+        ));
+
+        // The failed mir claim can be retried.
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![AirdropRate {
+                    denom: "mir".to_string(),
+                    amount: Uint128::new(2000_u128),
+                    stage: 0,
+                    proof: vec!["mir_proof1".to_string()],
+                }],
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_claim_airdrops_formats() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.airdrop_withdrawal_contract =
+                        Addr::unchecked("airdrop_withdrawal_contract");
+                    config.airdrop_registry_contract = Addr::unchecked("airdrop_registry_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ClaimAirdrops {
+                rates: vec![
+                    AirdropRate {
+                        denom: "native_token".to_string(),
+                        amount: Uint128::new(1000_u128),
+                        stage: 1,
+                        proof: vec!["native_proof".to_string()],
+                    },
+                    AirdropRate {
+                        denom: "plain_token".to_string(),
+                        amount: Uint128::new(2000_u128),
+                        stage: 1,
+                        proof: vec![],
+                    },
+                    AirdropRate {
+                        denom: "template_token".to_string(),
+                        amount: Uint128::new(3000_u128),
+                        stage: 2,
+                        proof: vec!["template_proof".to_string()],
+                    },
+                ],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "native_token_airdrop_contract".to_string(),
+                        msg: to_binary(&MerkleAirdropWithoutStageMsg::Claim {
+                            amount: Uint128::new(1000_u128),
+                            proof: vec!["native_proof".to_string()],
+                        })
+                        .unwrap(),
+                        funds: vec![]
+                    },
+                    0
+                ),
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "plain_token_airdrop_contract".to_string(),
+                        msg: to_binary(&PlainAirdropMsg::Claim {}).unwrap(),
+                        funds: vec![]
+                    },
+                    1
+                ),
+                SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: "template_token_airdrop_contract".to_string(),
+                        msg: Binary::from(
+                            r#"{"claim_tokens":{"round":2,"amount":"3000","merkle_proof":["template_proof"]}}"#
+                                .as_bytes()
+                        ),
+                        funds: vec![]
+                    },
+                    2
+                ),
+            ]
+        );
+
+        /*
+           Native airdrops are forwarded with a bank send
+        */
+        deps.querier.update_balance(
+            Addr::unchecked(MOCK_CONTRACT_ADDR),
+            vec![Coin::new(1000_u128, "unative")],
+        );
+        let res = reply(
+            deps.as_mut(),
+            env,
+            Reply {
+                id: 0,
+                result: ContractResult::Ok(SubMsgExecutionResponse {
+                    events: vec![],
+                    data: None,
+                }),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "airdrop_withdrawal_contract".to_string(),
+                amount: vec![Coin::new(1000_u128, "unative")]
+            })]
+        );
+    }
+
+    #[test]
+    fn test_compute_withdrawable_funds_fail() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        let _res = instantiate_contract(&mut deps, &info, &env);
+
+        let user1 = Addr::unchecked("user1");
+
+        /*
+           Test - 1. Undelegation batch not found
+        */
+        let err = compute_withdrawable_funds(deps.as_mut().storage, 0, &user1).unwrap_err();
+        assert!(matches!(err, ContractError::UndelegationBatchNotFound {}));
+
+        /*
+           Test - 2. Batch not reconciled
+        */
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(10000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: false,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+        let err = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::UndelegationBatchNotReconciled {}
+        ));
+
+        /*
+            Test - 3. User undelegation record not found
+        */
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(10000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: true,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+        let err = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap_err();
+        assert!(matches!(err, ContractError::UndelegationEntryNotFound {}));
+    }
+
+    #[test]
+    fn test_compute_withdrawable_funds_success() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        let _res = instantiate_contract(&mut deps, &info, &env);
+
+        let user1 = Addr::unchecked("user1");
+
+        /*
+           Test - 1. Success
+        */
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(10000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: true,
+                    undelegation_er: Decimal256::one(),
+                    undelegated_stake: Uint128::new(10000_u128),
+                    unbonding_slashing_ratio: Decimal::from_ratio(3_u128, 4_u128),
+                },
+            )
+            .unwrap();
+        USERS
+            .save(
+                deps.as_mut().storage,
+                (&user1, U64Key::new(1)),
+                &UndelegationInfo {
+                    batch_id: 1,
+                    token_amount: Uint128::new(1000_u128),
+                },
+            )
+            .unwrap();
+        let res = compute_withdrawable_funds(deps.as_mut().storage, 1, &user1).unwrap();
+        assert_eq!(
+            res,
+            GetFundsClaimRecord {
+                user_withdrawal_amount: Uint128::new(742_u128),
+                protocol_fee: Uint128::new(8_u128),
+                pool_fee: Uint128::zero(),
+                undelegated_tokens: Uint128::new(1000_u128),
+                rounding_dust: Decimal256::zero(),
+            }
+        );
+
+        /*
+           Test - 2. Luna owed to the user rounds down, the withdraw fee rounds up
+        */
+        let user2 = Addr::unchecked("user2");
+        USERS
+            .save(
+                deps.as_mut().storage,
+                (&user2, U64Key::new(1)),
+                &UndelegationInfo {
+                    batch_id: 1,
+                    token_amount: Uint128::new(999_u128),
+                },
+            )
+            .unwrap();
+        // 999 * 0.75 = 749.25 is floored to 749, and the 1% fee of 7.49 is ceiled to 8.
+        let res = compute_withdrawable_funds(deps.as_mut().storage, 1, &user2).unwrap();
+        assert_eq!(
+            res,
+            GetFundsClaimRecord {
+                user_withdrawal_amount: Uint128::new(741_u128),
+                protocol_fee: Uint128::new(8_u128),
+                pool_fee: Uint128::zero(),
+                undelegated_tokens: Uint128::new(999_u128),
+                rounding_dust: Decimal256::zero(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_withdraw_funds_to_wallet() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        let _res = instantiate_contract(&mut deps, &info, &env);
+
+        let user1 = Addr::unchecked("user1");
+
+        /*
+           withdraw paused
+        */
+        OPERATION_CONTROLS
+            .save(
+                deps.as_mut().storage,
+                &OperationControls {
+                    deposit_paused: false,
+                    queue_undelegate_paused: false,
+                    undelegate_paused: false,
+                    withdraw_paused: true,
+                    reinvest_paused: false,
+                    reconcile_paused: false,
+                    claim_airdrops_paused: false,
+                    redeem_rewards_paused: false,
+                    swap_paused: false,
+                    reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
+                },
+            )
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::WithdrawFundsToWallet { batch_id: 0 },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::OperationPaused(String { .. })));
+
+        OPERATION_CONTROLS
+            .save(
+                deps.as_mut().storage,
+                &OperationControls {
+                    deposit_paused: false,
+                    queue_undelegate_paused: false,
+                    undelegate_paused: false,
+                    withdraw_paused: false,
+                    reinvest_paused: false,
+                    reconcile_paused: false,
+                    claim_airdrops_paused: false,
+                    redeem_rewards_paused: false,
+                    swap_paused: false,
+                    reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
+                },
+            )
+            .unwrap();
+
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reconciled_funds_to_withdraw = Uint128::new(1200);
+                Ok(state)
+            })
+            .unwrap();
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(10000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: true,
+                    undelegation_er: Decimal256::one(),
+                    undelegated_stake: Uint128::new(10000_u128),
+                    unbonding_slashing_ratio: Decimal::from_ratio(3_u128, 4_u128),
+                },
+            )
+            .unwrap();
+        USERS
+            .save(
+                deps.as_mut().storage,
+                (&user1, U64Key::new(1)),
+                &UndelegationInfo {
+                    batch_id: 1,
+                    token_amount: Uint128::new(1000_u128),
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[]),
+            ExecuteMsg::WithdrawFundsToWallet { batch_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: user1.to_string(),
+                amount: vec![Coin::new(742_u128, "uluna".to_string())]
+            })
+        );
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "protocol_fee_contract".to_string(),
+                amount: vec![Coin::new(8_u128, "uluna".to_string())]
+            })
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.reconciled_funds_to_withdraw, Uint128::new(450));
+        let user_undel_info = USERS
+            .may_load(deps.as_mut().storage, (&user1, U64Key::new(1)))
+            .unwrap();
+        assert_eq!(user_undel_info, None);
+    }
+
+    #[test]
+    fn test_unbonding_nft() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    state.current_undelegation_batch_id = 3;
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        for val_addr in [&valid1, &valid2, &valid3] {
+            VALIDATOR_META
+                .save(
+                    deps.as_mut().storage,
+                    val_addr,
+                    &VMeta {
+                        staked: Uint128::new(1000_u128),
+                        slashed: Default::default(),
+                        filled: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(3),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(1000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: false,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+        let queue_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: "user1".to_string(),
+            amount: Uint128::new(100_u128),
+            msg: to_binary(&Cw20HookMsg::QueueUndelegate {
+                mint_nft: Some(true),
+            })
+            .unwrap(),
+        });
+
+        /*
+           Test - 1. NFT contract not configured
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            queue_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnbondingNftNotConfigured {}));
+
+        /*
+           Test - 2. The position is minted to the user instead of being recorded under its address
+        */
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.unbonding_nft_contract = Some(Addr::unchecked("unbonding_nft_contract"));
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            queue_msg,
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "unbonding_nft_contract".to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::Mint(Cw721MintMsg {
+                    token_id: "0".to_string(),
+                    owner: "user1".to_string(),
+                    token_uri: None,
+                    extension: None,
+                }))
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        assert_eq!(res.attributes, vec![attr("unbonding_nft", "0")]);
+        assert!(USERS
+            .may_load(
+                deps.as_mut().storage,
+                (&Addr::unchecked("user1"), U64Key::new(3))
+            )
+            .unwrap()
+            .is_none());
+        let unbonding_nft_response: UnbondingNftResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::UnbondingNft {
+                    token_id: "0".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            unbonding_nft_response.position,
+            Some(UndelegationInfo {
+                batch_id: 3,
+                token_amount: Uint128::new(100_u128),
+            })
+        );
+        let batch_undel_record = BATCH_UNDELEGATION_REGISTRY
+            .load(deps.as_mut().storage, U64Key::new(3))
+            .unwrap();
+        assert_eq!(
+            batch_undel_record.undelegated_tokens,
+            Uint128::new(1100_u128)
+        );
+
+        /*
+           Test - 3. Holders withdraw by sending the NFT to staking, which burns it
+        */
+        BATCH_UNDELEGATION_REGISTRY
+            .update(deps.as_mut().storage, U64Key::new(3), |x| -> StdResult<_> {
+                let mut batch = x.unwrap();
+                batch.reconciled = true;
+                batch.undelegation_er = Decimal256::one();
+                batch.unbonding_slashing_ratio = Decimal::one();
+                Ok(batch)
+            })
+            .unwrap();
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reconciled_funds_to_withdraw = Uint128::new(1100_u128);
+                Ok(state)
+            })
+            .unwrap();
+        // user1 transferred the NFT to user2, who sends it back to staking to withdraw.
+        let withdraw_msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: "user2".to_string(),
+            token_id: "0".to_string(),
+            msg: to_binary(&Cw721HookMsg::WithdrawFundsToWallet {}).unwrap(),
+        });
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other_nft_contract", &[]),
+            withdraw_msg.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("unbonding_nft_contract", &[]),
+            withdraw_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "user2".to_string(),
+                amount: vec![Coin::new(99_u128, "uluna".to_string())]
+            })
+        );
+        assert_eq!(
+            res.messages.last().unwrap(),
+            &SubMsg::new(WasmMsg::Execute {
+                contract_addr: "unbonding_nft_contract".to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::Burn {
+                    token_id: "0".to_string()
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+        assert!(UNBONDING_NFTS
+            .may_load(deps.as_mut().storage, "0")
+            .unwrap()
+            .is_none());
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("unbonding_nft_contract", &[]),
+            withdraw_msg,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UndelegationEntryNotFound {}));
+    }
+
+    #[test]
+    fn test_withdraw_for_and_push_batch_payouts() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    state.current_undelegation_batch_id = 1;
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        for val_addr in [&valid1, &valid2, &valid3] {
+            VALIDATOR_META
+                .save(
+                    deps.as_mut().storage,
+                    val_addr,
+                    &VMeta {
+                        staked: Uint128::new(1000_u128),
+                        slashed: Default::default(),
+                        filled: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::zero(),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: false,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+
+        // user2 and user3 opt in to auto-claim before queueing, user1 does not.
+        for user in ["user2", "user3"] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(user, &[]),
+                ExecuteMsg::SetAutoClaim { enabled: true },
+            )
+            .unwrap();
+        }
+        let auto_claim_response: AutoClaimResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AutoClaim {
+                    user_addr: "user2".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(auto_claim_response.enabled);
+        for user in ["user1", "user2", "user3"] {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("cw20_contract", &[]),
+                ExecuteMsg::Receive(Cw20ReceiveMsg {
+                    sender: user.to_string(),
+                    amount: Uint128::new(1000_u128),
+                    msg: to_binary(&Cw20HookMsg::QueueUndelegate { mint_nft: None }).unwrap(),
+                }),
+            )
+            .unwrap();
+        }
+
+        /*
+           Test - 1. Batch must be reconciled before payouts are pushed
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::PushBatchPayouts {
+                batch_id: 1,
+                limit: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::UndelegationBatchNotReconciled {}
+        ));
+
+        BATCH_UNDELEGATION_REGISTRY
+            .update(deps.as_mut().storage, U64Key::new(1), |x| -> StdResult<_> {
+                let mut batch = x.unwrap();
+                batch.reconciled = true;
+                batch.undelegation_er = Decimal256::one();
+                batch.unbonding_slashing_ratio = Decimal::one();
+                Ok(batch)
+            })
+            .unwrap();
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reconciled_funds_to_withdraw = Uint128::new(3000_u128);
+                Ok(state)
+            })
+            .unwrap();
+
+        /*
+           Test - 2. Anyone can withdraw for a user, and the user is paid
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::WithdrawFor {
+                user: "user1".to_string(),
+                batch_id: 1,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "user1".to_string(),
+                amount: vec![Coin::new(990_u128, "uluna".to_string())]
+            })
+        );
+        assert!(USERS
+            .may_load(
+                deps.as_mut().storage,
+                (&Addr::unchecked("user1"), U64Key::new(1))
+            )
+            .unwrap()
+            .is_none());
+
+        /*
+           Test - 3. Payouts are pushed to opted in users, a page at a time
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::PushBatchPayouts {
+                batch_id: 1,
+                limit: Some(1),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "user2".to_string(),
+                amount: vec![Coin::new(990_u128, "uluna".to_string())]
+            })
+        );
+        assert_eq!(res.attributes[2], attr("paid_users", "1"));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.reconciled_funds_to_withdraw, Uint128::new(1000_u128));
+
+        /*
+           Test - 4. Users who opt out after queueing are skipped
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user3", &[]),
+            ExecuteMsg::SetAutoClaim { enabled: false },
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::PushBatchPayouts {
+                batch_id: 1,
+                limit: None,
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[2], attr("paid_users", "0"));
+        assert!(USERS
+            .may_load(
+                deps.as_mut().storage,
+                (&Addr::unchecked("user3"), U64Key::new(1))
+            )
+            .unwrap()
+            .is_some());
+
+        /*
+           Test - 5. Opting in picks up undelegations queued before
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user3", &[]),
+            ExecuteMsg::SetAutoClaim { enabled: true },
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("keeper", &[]),
+            ExecuteMsg::PushBatchPayouts {
+                batch_id: 1,
+                limit: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0],
+            SubMsg::new(BankMsg::Send {
+                to_address: "user3".to_string(),
+                amount: vec![Coin::new(990_u128, "uluna".to_string())]
+            })
+        );
+        assert_eq!(res.attributes[2], attr("paid_users", "1"));
+        assert!(USERS
+            .may_load(
+                deps.as_mut().storage,
+                (&Addr::unchecked("user3"), U64Key::new(1))
+            )
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_emergency_unwind() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let mut env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3100_u128);
+                    state.pending_delegation = Uint128::new(100_u128);
+                    state.total_shares = Uint128::new(3100_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        // 100 LunaX are locked in the contract and are never claimed.
+        deps.querier.update_stader_balances(
+            Some(Uint128::new(3100_u128)),
+            Some(HashMap::from([(
+                Addr::unchecked(MOCK_CONTRACT_ADDR),
+                Uint128::new(100_u128),
+            )])),
+        );
+
+        /*
+           Test - 1. Only the manager can unwind, and only with an empty open batch
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        BATCH_UNDELEGATION_REGISTRY
+            .update(deps.as_mut().storage, U64Key::new(1), |x| -> StdResult<_> {
+                let mut batch = x.unwrap();
+                batch.undelegated_tokens = Uint128::new(10_u128);
+                Ok(batch)
+            })
+            .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnwindOpenBatchNotEmpty {}));
+        BATCH_UNDELEGATION_REGISTRY
+            .update(deps.as_mut().storage, U64Key::new(1), |x| -> StdResult<_> {
+                let mut batch = x.unwrap();
+                batch.undelegated_tokens = Uint128::zero();
+                Ok(batch)
+            })
+            .unwrap();
+
+        /*
+           Test - 2. The first call stops the pool and undelegates the first validator
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Undelegate {
+                validator: "valid0001".to_string(),
+                amount: Coin::new(1000_u128, "uluna".to_string()),
+            })]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "emergency_unwind"),
+                attr("validator", "valid0001"),
+                attr("undelegated_amount", "1000"),
+                attr("phase", "undelegating"),
+            ]
+        );
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::OperationPaused(_)));
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::UpdateOperationFlags {
+                operation_controls_update_request: OperationControlsUpdateRequest {
+                    deposit_paused: Some(false),
+                    queue_undelegate_paused: None,
+                    undelegate_paused: None,
+                    withdraw_paused: None,
+                    reinvest_paused: None,
+                    reconcile_paused: None,
+                    claim_airdrops_paused: None,
+                    redeem_rewards_paused: None,
+                    swap_paused: None,
+                    reimburse_slashing_paused: None,
+                    delegate_pending_paused: None,
+                    reason: None,
+                },
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnwindInProgress {}));
+
+        /*
+           Test - 3. Each further validator waits for the cooldown, and the unwind moves on with the last one
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UndelegationInCooldown {}));
+
+        let get_delegations_without = |undelegated: &[&str]| -> Vec<FullDelegation> {
+            get_delegations()
+                .into_iter()
+                .filter(|delegation| !undelegated.contains(&delegation.validator.as_str()))
+                .collect()
+        };
+        env.block.time = env.block.time.plus_seconds(10);
+        deps.querier.update_staking(
+            "uluna",
+            &get_validators(),
+            &get_delegations_without(&["valid0001"]),
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Undelegate {
+                validator: "valid0002".to_string(),
+                amount: Coin::new(1000_u128, "uluna".to_string()),
+            })]
+        );
+        assert_eq!(res.attributes[3], attr("phase", "undelegating"));
+
+        env.block.time = env.block.time.plus_seconds(10);
+        deps.querier.update_staking(
+            "uluna",
+            &get_validators(),
+            &get_delegations_without(&["valid0001", "valid0002"]),
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(StakingMsg::Undelegate {
+                validator: "valid0003".to_string(),
+                amount: Coin::new(1000_u128, "uluna".to_string()),
+            })]
+        );
+        assert_eq!(res.attributes[3], attr("phase", "unbonding"));
+        let unwind_state_response: UnwindStateResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::UnwindState {}).unwrap())
+                .unwrap();
+        let steps = unwind_state_response.unwind.unwrap().steps;
+        assert_eq!(steps.len(), 3);
+        assert!(steps
+            .iter()
+            .all(|step| step.amount == Uint128::new(1000_u128)));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(100_u128));
+        deps.querier.update_staking("uluna", &get_validators(), &[]);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnwindFundsNotReleased {}));
+
+        /*
+           Test - 4. Once released, holders get the unwound stake, pending deposits and rewards pro rata
+        */
+        env.block.time = env.block.time.plus_seconds(3600 * 24 * 21);
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reconciled_funds_to_withdraw = Uint128::new(50_u128);
+                Ok(state)
+            })
+            .unwrap();
+        deps.querier.update_balance(
+            Addr::unchecked(MOCK_CONTRACT_ADDR),
+            vec![Coin::new(3150_u128, "uluna".to_string())],
+        );
+        // Rewards withdrawn by the undelegations. 1% of them is the protocol fee.
+        deps.querier.update_balance(
+            Addr::unchecked("reward_contract"),
+            vec![Coin::new(202_u128, "uluna".to_string())],
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap();
+        assert_eq!(res.attributes[1], attr("phase", "claimable"));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "reward_contract".to_string(),
+                msg: to_binary(&RewardExecuteMsg::Transfer {
+                    reward_amount: Uint128::new(200_u128),
+                    reward_withdraw_contract: Addr::unchecked(MOCK_CONTRACT_ADDR),
+                    protocol_fees: vec![ProtocolFee {
+                        recipient: Addr::unchecked("protocol_fee_contract"),
+                        amount: Uint128::new(2_u128),
+                    }],
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        let unwind_state_response: UnwindStateResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::UnwindState {}).unwrap())
+                .unwrap();
+        let unwind = unwind_state_response.unwind.unwrap();
+        assert_eq!(unwind.phase, UnwindPhase::Claimable);
+        assert_eq!(unwind.claimable_amount, Uint128::new(3300_u128));
+        assert_eq!(unwind.claimable_shares, Uint128::new(3000_u128));
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.pending_delegation, Uint128::zero());
+
+        let compute_response: ComputeUnwindClaimResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::ComputeUnwindClaim {
+                    shares: Uint128::new(300_u128),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(compute_response.amount, Uint128::new(330_u128));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "user1".to_string(),
+                amount: Uint128::new(300_u128),
+                msg: to_binary(&Cw20HookMsg::UnwindClaim {}).unwrap(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "cw20_contract".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Burn {
+                        amount: Uint128::new(300_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![],
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "user1".to_string(),
+                    amount: vec![Coin::new(330_u128, "uluna".to_string())]
+                }),
+            ]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(2800_u128));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::UnwindAlreadyFinalized {}));
+
+        /*
+           Test - 5. A migration keeps the pool stopped and leaves the pause records alone
+        */
+        let pause_info_before = query(deps.as_ref(), env.clone(), QueryMsg::PauseInfo {}).unwrap();
+        let admin_actions_before = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::AdminActions {
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            query(deps.as_ref(), env.clone(), QueryMsg::PauseInfo {}).unwrap(),
+            pause_info_before
+        );
+        assert_eq!(
+            query(
+                deps.as_ref(),
+                env,
+                QueryMsg::AdminActions {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+            admin_actions_before
+        );
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(operation_controls.deposit_paused);
+        assert!(operation_controls.queue_undelegate_paused);
+        assert!(operation_controls.undelegate_paused);
+        assert!(operation_controls.reinvest_paused);
+        assert!(operation_controls.delegate_pending_paused);
+        assert!(!operation_controls.withdraw_paused);
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![
+                        Addr::unchecked("valid0001"),
+                        Addr::unchecked("valid0002"),
+                        Addr::unchecked("valid0003"),
+                    ];
+                    state.total_staked = Uint128::new(3000_u128);
+                    state.total_shares = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        let get_delegations_with_amount = |amount: u128| -> Vec<FullDelegation> {
+            get_delegations()
+                .into_iter()
+                .map(|mut delegation| {
+                    delegation.amount = Coin::new(amount, "uluna");
+                    delegation
+                })
+                .collect()
+        };
+        deps.querier.update_staking(
+            "uluna",
+            &get_validators(),
+            &get_delegations_with_amount(900),
+        );
+
+        /*
+           Test - 1. Drop limits above 100% are rejected
+        */
+        let config_request = ConfigUpdateRequest {
+            min_deposit: None,
+            max_deposit: None,
+            cw20_token_contract: None,
+            protocol_reward_fee: None,
+            protocol_withdraw_fee: None,
+            protocol_deposit_fee: None,
+            airdrop_registry_contract: None,
+            unbonding_nft_contract: None,
+            unbonding_period: None,
+            undelegation_cooldown: None,
+            swap_cooldown: None,
+            reinvest_cooldown: None,
+            split_policy: None,
+            epoch_delegation: None,
+            delegate_pending_cooldown: None,
+            reward_sniping_policy: None,
+            reward_fee_as_shares: None,
+            fee_destination_policy: None,
+            referral_fee_share: None,
+            guardian: Some("guardian".to_string()),
+            circuit_breaker_policy: Some(CircuitBreakerPolicy {
+                max_rate_drop_per_call: Some(Decimal::from_ratio(3_u128, 2_u128)),
+                max_rate_rise_per_call: None,
+                max_rate_drop_per_day: None,
+                max_rate_rise_per_day: None,
+            }),
+        };
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::UpdateConfig {
+                config_request: config_request.clone(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCircuitBreakerPolicy {}));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::UpdateConfig {
+                config_request: ConfigUpdateRequest {
+                    circuit_breaker_policy: Some(CircuitBreakerPolicy {
+                        max_rate_drop_per_call: Some(Decimal::from_ratio(5_u128, 100_u128)),
+                        max_rate_rise_per_call: None,
+                        max_rate_drop_per_day: None,
+                        max_rate_rise_per_day: Some(Decimal::from_ratio(10_u128, 100_u128)),
+                    }),
+                    ..config_request
+                },
+            },
+        )
+        .unwrap();
+
+        /*
+           Test - 2. A 10% slash trips the breaker, and the deposit is refunded instead of failing
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "user1".to_string(),
+                amount: vec![Coin::new(1000_u128, "uluna".to_string())],
+            })]
+        );
+        assert_eq!(
+            res.attributes,
+            vec![
+                attr("action", "deposit"),
+                attr("circuit_breaker_tripped", "rate_drop_per_call"),
+            ]
+        );
+        let circuit_breaker_response: CircuitBreakerResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::CircuitBreaker {}).unwrap())
+                .unwrap();
+        let trip = circuit_breaker_response
+            .circuit_breaker
+            .unwrap()
+            .trip
+            .unwrap();
+        assert_eq!(trip.reason, "rate_drop_per_call");
+        assert_eq!(trip.previous_rate, Decimal256::one());
+        assert_eq!(trip.new_rate, Decimal256::from_ratio(9_u64, 10_u64));
+        assert_eq!(
+            trip.paused_operations,
+            vec![
+                "deposit".to_string(),
+                "queue_undelegate".to_string(),
+                "undelegate".to_string(),
+                "reinvest".to_string(),
+                "delegate_pending".to_string(),
+            ]
+        );
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(operation_controls.deposit_paused);
+        assert!(operation_controls.delegate_pending_paused);
+        assert!(!operation_controls.withdraw_paused);
+        let pause_info_response: PauseInfoResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::PauseInfo {}).unwrap())
+                .unwrap();
+        assert_eq!(pause_info_response.pauses.len(), 5);
+        assert_eq!(
+            pause_info_response.pauses[0].paused_by,
+            Addr::unchecked(MOCK_CONTRACT_ADDR)
+        );
+        assert_eq!(
+            pause_info_response.pauses[0].reason,
+            Some("circuit_breaker: rate_drop_per_call".to_string())
+        );
+
+        /*
+           Test - 3. A migration keeps the breaker's pauses
+        */
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(operation_controls.deposit_paused);
+        assert!(operation_controls.delegate_pending_paused);
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::OperationPaused(_)));
+        let circuit_breaker_response: CircuitBreakerResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::CircuitBreaker {}).unwrap())
+                .unwrap();
+        assert!(circuit_breaker_response
+            .circuit_breaker
+            .unwrap()
+            .trip
+            .is_some());
+
+        /*
+           Test - 4. Only the guardian or the manager can resume, and the new rate becomes the baseline
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::ResumeFromCircuitBreaker {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("guardian", &[]),
+            ExecuteMsg::ResumeFromCircuitBreaker {},
+        )
+        .unwrap();
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(!operation_controls.deposit_paused);
+        assert!(!operation_controls.queue_undelegate_paused);
+        let circuit_breaker_response: CircuitBreakerResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::CircuitBreaker {}).unwrap())
+                .unwrap();
+        let circuit_breaker = circuit_breaker_response.circuit_breaker.unwrap();
+        assert_eq!(circuit_breaker.trip, None);
+        assert_eq!(
+            circuit_breaker.window_start_rate,
+            Decimal256::from_ratio(9_u64, 10_u64)
+        );
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::ResumeFromCircuitBreaker {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CircuitBreakerNotTripped {}));
+
+        /*
+           Test - 5. A rise beyond the daily limit trips the breaker, and queued LunaX is sent back
+        */
+        deps.querier.update_staking(
+            "uluna",
+            &get_validators(),
+            &get_delegations_with_amount(1000),
+        );
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "user1".to_string(),
+                amount: Uint128::new(100_u128),
+                msg: to_binary(&Cw20HookMsg::QueueUndelegate { mint_nft: None }).unwrap(),
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(WasmMsg::Execute {
+                contract_addr: "cw20_contract".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "user1".to_string(),
+                    amount: Uint128::new(100_u128),
+                })
+                .unwrap(),
+                funds: vec![],
+            })]
+        );
+        assert_eq!(
+            res.attributes[1],
+            attr("circuit_breaker_tripped", "rate_rise_per_day")
+        );
+        let batch = BATCH_UNDELEGATION_REGISTRY
+            .load(deps.as_mut().storage, U64Key::new(1))
+            .unwrap();
+        assert_eq!(batch.undelegated_tokens, Uint128::zero());
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(operation_controls.queue_undelegate_paused);
+    }
+
+    #[test]
+    fn test_admin_action_log() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+
+        /*
+           Test - 1. Pausing stores who paused, when and why. Unpausing clears it.
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::UpdateOperationFlags {
+                operation_controls_update_request: OperationControlsUpdateRequest {
+                    deposit_paused: Some(true),
+                    queue_undelegate_paused: None,
+                    undelegate_paused: None,
+                    withdraw_paused: None,
+                    reinvest_paused: None,
+                    reconcile_paused: None,
+                    claim_airdrops_paused: None,
+                    redeem_rewards_paused: None,
+                    swap_paused: None,
+                    reimburse_slashing_paused: None,
+                    delegate_pending_paused: None,
+                    reason: Some("incident".to_string()),
+                },
+            },
+        )
+        .unwrap();
+        let pause_info_response: PauseInfoResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::PauseInfo {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            pause_info_response.pauses,
+            vec![PauseInfo {
+                operation: "deposit".to_string(),
+                paused_by: Addr::unchecked("creator"),
+                paused_at: env.block.time,
+                reason: Some("incident".to_string()),
+            }]
+        );
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::UpdateConfig {
+                config_request: ConfigUpdateRequest {
+                    min_deposit: Some(Uint128::new(2000_u128)),
+                    max_deposit: None,
+                    cw20_token_contract: None,
+                    protocol_reward_fee: None,
+                    protocol_withdraw_fee: None,
+                    protocol_deposit_fee: None,
+                    airdrop_registry_contract: None,
+                    unbonding_nft_contract: None,
+                    unbonding_period: None,
+                    undelegation_cooldown: None,
+                    swap_cooldown: None,
+                    reinvest_cooldown: None,
+                    split_policy: None,
+                    epoch_delegation: None,
+                    delegate_pending_cooldown: None,
+                    reward_sniping_policy: None,
+                    reward_fee_as_shares: None,
+                    fee_destination_policy: None,
+                    referral_fee_share: None,
+                    guardian: None,
+                    circuit_breaker_policy: None,
+                },
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::AddValidator {
+                val_addr: Addr::unchecked("valid0001"),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            info.clone(),
+            ExecuteMsg::SetManager {
+                manager: "new_manager".to_string(),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("new_manager", &[]),
+            ExecuteMsg::AcceptManager {},
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("new_manager", &[]),
+            ExecuteMsg::UpdateOperationFlags {
+                operation_controls_update_request: OperationControlsUpdateRequest {
+                    deposit_paused: Some(false),
+                    queue_undelegate_paused: None,
+                    undelegate_paused: None,
+                    withdraw_paused: None,
+                    reinvest_paused: None,
+                    reconcile_paused: None,
+                    claim_airdrops_paused: None,
+                    redeem_rewards_paused: None,
+                    swap_paused: None,
+                    reimburse_slashing_paused: None,
+                    delegate_pending_paused: None,
+                    reason: None,
+                },
+            },
+        )
+        .unwrap();
+        let pause_info_response: PauseInfoResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::PauseInfo {}).unwrap())
+                .unwrap();
+        assert!(pause_info_response.pauses.is_empty());
+
+        /*
+           Test - 2. Every admin action is logged in order with its sender and diff
+        */
+        let admin_actions_response: AdminActionsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env.clone(),
+                QueryMsg::AdminActions {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let actions = admin_actions_response.actions;
+        assert_eq!(
+            actions
+                .iter()
+                .map(|action| action.kind.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "update_operation_flags",
+                "update_config",
+                "add_validator",
+                "set_manager",
+                "accept_manager",
+                "update_operation_flags",
+            ]
+        );
+        assert_eq!(
+            actions[0],
+            AdminAction {
+                id: 0,
+                sender: Addr::unchecked("creator"),
+                time: env.block.time,
+                kind: "update_operation_flags".to_string(),
+                diff: vec![AdminActionChange {
+                    field: "deposit_paused".to_string(),
+                    old_value: "false".to_string(),
+                    new_value: "true".to_string(),
+                }],
+            }
+        );
+        assert_eq!(
+            actions[1].diff,
+            vec![AdminActionChange {
+                field: "min_deposit".to_string(),
+                old_value: "\"1000\"".to_string(),
+                new_value: "\"2000\"".to_string(),
+            }]
+        );
+        assert_eq!(
+            actions[2].diff,
+            vec![AdminActionChange {
+                field: "validators".to_string(),
+                old_value: "[]".to_string(),
+                new_value: "[\"valid0001\"]".to_string(),
+            }]
+        );
+        assert_eq!(
+            actions[3].diff,
+            vec![AdminActionChange {
+                field: "pending_manager".to_string(),
+                old_value: "null".to_string(),
+                new_value: "\"new_manager\"".to_string(),
+            }]
+        );
+        assert_eq!(actions[4].sender, Addr::unchecked("new_manager"));
+        assert_eq!(
+            actions[4].diff,
+            vec![AdminActionChange {
+                field: "manager".to_string(),
+                old_value: "\"creator\"".to_string(),
+                new_value: "\"new_manager\"".to_string(),
+            }]
+        );
+
+        let admin_actions_response: AdminActionsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                env,
+                QueryMsg::AdminActions {
+                    start_after: Some(2),
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(admin_actions_response.actions.len(), 1);
+        assert_eq!(admin_actions_response.actions[0].kind, "set_manager");
+    }
+
+    #[test]
+    fn test_migrate_from_legacy_storage() {
+        let mut deps = mock_dependencies(&[]);
+        let env = mock_env();
+
+        LEGACY_CONFIG
+            .save(
+                deps.as_mut().storage,
+                &LegacyConfig {
+                    manager: Addr::unchecked("creator"),
+                    vault_denom: "uluna".to_string(),
+                    min_deposit: Uint128::new(1000),
+                    max_deposit: Uint128::new(1_000_000_000_000),
+                    active: true,
+                    reward_contract: Addr::unchecked("reward_contract"),
+                    cw20_token_contract: Addr::unchecked("cw20_contract"),
+                    airdrop_registry_contract: Addr::unchecked("airdrop_registry_contract"),
+                    airdrop_withdrawal_contract: Addr::unchecked("airdrop_withdrawal_contract"),
+                    protocol_fee_contract: Addr::unchecked("protocol_fee_contract"),
+                    protocol_reward_fee: Decimal::from_ratio(1_u128, 100_u128),
+                    protocol_deposit_fee: Decimal::from_ratio(1_u128, 100_u128),
+                    protocol_withdraw_fee: Decimal::from_ratio(1_u128, 100_u128),
+                    unbonding_period: 3600 * 24 * 21,
+                    undelegation_cooldown: 10,
+                    swap_cooldown: 10,
+                    reinvest_cooldown: 10,
+                },
+            )
+            .unwrap();
+        LEGACY_STATE
+            .save(
+                deps.as_mut().storage,
+                &LegacyState {
+                    total_staked: Uint128::new(5500_u128),
+                    exchange_rate: Decimal::from_ratio(11_u128, 10_u128),
+                    last_reconciled_batch_id: 1,
+                    current_undelegation_batch_id: 2,
+                    last_undelegation_time: env.block.time,
+                    last_swap_time: env.block.time,
+                    last_reinvest_time: env.block.time,
+                    validators: vec![Addr::unchecked("valid0001")],
+                    reconciled_funds_to_withdraw: Uint128::new(100_u128),
+                },
+            )
+            .unwrap();
+        // Batches were stored with a Decimal exchange rate.
+        deps.as_mut().storage.set(
+            &BATCH_UNDELEGATION_REGISTRY.key(U64Key::new(2)),
+            br#"{"undelegated_tokens":"10","create_time":"1571797419879305533","est_release_time":null,"reconciled":false,"undelegation_er":"1.1","undelegated_stake":"0","unbonding_slashing_ratio":"1"}"#,
+        );
+        // Operation flags were stored before pending delegations could be paused.
+        deps.as_mut().storage.set(
+            b"operation_controls",
+            br#"{"deposit_paused":true,"queue_undelegate_paused":false,"undelegate_paused":false,"withdraw_paused":false,"reinvest_paused":false,"reconcile_paused":false,"claim_airdrops_paused":false,"redeem_rewards_paused":false,"swap_paused":false,"reimburse_slashing_paused":false}"#,
+        );
+        deps.querier
+            .update_stader_balances(Some(Uint128::new(5000_u128)), None);
+
+        migrate(deps.as_mut(), env.clone(), MigrateMsg {}).unwrap();
+
+        let operation_controls = OPERATION_CONTROLS.load(deps.as_mut().storage).unwrap();
+        assert!(operation_controls.deposit_paused);
+        assert!(!operation_controls.delegate_pending_paused);
+
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(config.cw20_token_contract, Addr::unchecked("cw20_contract"));
+        assert_eq!(
+            config.protocol_fee_recipients,
+            vec![ProtocolFeeRecipient {
+                recipient: Addr::unchecked("protocol_fee_contract"),
+                weight: Decimal::one(),
+            }]
+        );
+        assert_eq!(config.referral_fee_share, Decimal::zero());
+        assert_eq!(config.guardian, None);
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(5000_u128));
+        assert_eq!(state.total_staked, Uint128::new(5500_u128));
+        assert_eq!(
+            state.exchange_rate,
+            Decimal256::from(Decimal::from_ratio(11_u128, 10_u128))
+        );
+        assert_eq!(state.current_undelegation_batch_id, 2);
+        assert_eq!(state.pending_delegation, Uint128::zero());
+        let batch = BATCH_UNDELEGATION_REGISTRY
+            .load(deps.as_mut().storage, U64Key::new(2))
+            .unwrap();
+        assert_eq!(
+            batch.undelegation_er,
+            Decimal256::from(Decimal::from_ratio(11_u128, 10_u128))
+        );
+        query(deps.as_ref(), env.clone(), QueryMsg::Config {}).unwrap();
+        query(deps.as_ref(), env.clone(), QueryMsg::State {}).unwrap();
+
+        // Storage already in the current shape is left alone.
+        deps.querier
+            .update_stader_balances(Some(Uint128::new(6000_u128)), None);
+        migrate(deps.as_mut(), env, MigrateMsg {}).unwrap();
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_shares, Uint128::new(5000_u128));
+    }
+
+    #[test]
+    fn test_emergency_unwind_skips_circuit_breaker() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.circuit_breaker_policy = CircuitBreakerPolicy {
+                        max_rate_drop_per_call: Some(Decimal::from_ratio(5_u128, 100_u128)),
+                        max_rate_rise_per_call: None,
+                        max_rate_drop_per_day: None,
+                        max_rate_rise_per_day: None,
+                    };
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![
+                        Addr::unchecked("valid0001"),
+                        Addr::unchecked("valid0002"),
+                        Addr::unchecked("valid0003"),
+                    ];
+                    state.total_staked = Uint128::new(3000_u128);
+                    state.total_shares = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        // Every validator was slashed by 10%, which is above the 5% drop allowed per call.
+        let delegations = get_delegations()
+            .into_iter()
+            .map(|mut delegation| {
+                delegation.amount = Coin::new(900_u128, "uluna");
+                delegation
+            })
+            .collect::<Vec<FullDelegation>>();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &delegations);
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            info,
+            ExecuteMsg::EmergencyUnwind {},
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(res.attributes[2], attr("undelegated_amount", "900"));
+
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.exchange_rate, Decimal256::from_ratio(9_u64, 10_u64));
+        let circuit_breaker_response: CircuitBreakerResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::CircuitBreaker {}).unwrap())
+                .unwrap();
+        assert!(circuit_breaker_response
+            .circuit_breaker
+            .and_then(|circuit_breaker| circuit_breaker.trip)
+            .is_none());
+        let unwind_state_response: UnwindStateResponse =
+            from_binary(&query(deps.as_ref(), env, QueryMsg::UnwindState {}).unwrap()).unwrap();
+        assert_eq!(
+            unwind_state_response.unwind.unwrap().phase,
+            UnwindPhase::Undelegating
+        );
+    }
+
+    #[test]
+    fn test_fee_destination_pool() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        let user1 = Addr::unchecked("user1");
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.fee_destination_policy = FeeDestinationPolicy {
+                        deposit_fee: FeeDestination::Pool,
+                        withdraw_fee: FeeDestination::Pool,
+                    };
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        for val_addr in [&valid1, &valid2, &valid3] {
+            VALIDATOR_META
+                .save(
+                    deps.as_mut().storage,
+                    val_addr,
+                    &VMeta {
+                        staked: Uint128::new(1000_u128),
+                        slashed: Default::default(),
+                        filled: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+
+        /*
+           Test - 1. The deposit fee is staked but no shares are minted for it
+        */
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: None,
+            },
+        )
+        .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(1000_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "other".to_string(),
+                        amount: Uint128::new(990_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.total_staked, Uint128::new(4000_u128));
+        assert_eq!(state.total_shares, Uint128::new(3990_u128));
+        assert_eq!(
+            state.exchange_rate,
+            Decimal256::from_ratio(4000_u64, 3990_u64)
+        );
+
+        /*
+           Test - 2. The withdraw fee is returned to the pool for delegation
+        */
+        STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reconciled_funds_to_withdraw = Uint128::new(1200);
+                Ok(state)
+            })
+            .unwrap();
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(10000_u128),
+                    create_time: Default::default(),
+                    est_release_time: None,
+                    reconciled: true,
+                    undelegation_er: Decimal256::one(),
+                    undelegated_stake: Uint128::new(10000_u128),
+                    unbonding_slashing_ratio: Decimal::from_ratio(3_u128, 4_u128),
+                },
+            )
+            .unwrap();
+        USERS
+            .save(
+                deps.as_mut().storage,
+                (&user1, U64Key::new(1)),
+                &UndelegationInfo {
+                    batch_id: 1,
+                    token_amount: Uint128::new(1000_u128),
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[]),
+            ExecuteMsg::WithdrawFundsToWallet { batch_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: user1.to_string(),
+                amount: vec![Coin::new(742_u128, "uluna".to_string())]
+            })]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.reconciled_funds_to_withdraw, Uint128::new(450));
+        assert_eq!(state.pending_delegation, Uint128::new(8));
+        assert_eq!(state.total_staked, Uint128::new(4008_u128));
+        assert_eq!(
+            state.exchange_rate,
+            Decimal256::from_ratio(4008_u64, 3990_u64)
+        );
+    }
+
+    #[test]
+    fn test_referral_codes() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_contract");
+                    config.referral_fee_share = Decimal::from_ratio(3_u128, 10_u128);
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        for val_addr in [&valid1, &valid2, &valid3] {
+            VALIDATOR_META
+                .save(
+                    deps.as_mut().storage,
+                    val_addr,
+                    &VMeta {
+                        staked: Uint128::new(1000_u128),
+                        slashed: Default::default(),
+                        filled: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+
+        /*
+           Test - 1. Registering codes
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator", &[]),
+            ExecuteMsg::RegisterReferralCode {
+                code: "bad code!".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidReferralCode {}));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator", &[]),
+            ExecuteMsg::RegisterReferralCode {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::RegisterReferralCode {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::ReferralCodeAlreadyRegistered {}
+        ));
+
+        /*
+           Test - 2. Referred deposits
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: Some("wallet-2".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ReferralCodeNotFound {}));
+
+        // 30% of the 10 luna deposit fee is kept for the code.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: Some("wallet-1".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "protocol_fee_contract".to_string(),
+                    amount: vec![Coin::new(7_u128, "uluna")]
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(990_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "cw20_contract".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "other".to_string(),
+                        amount: Uint128::new(990_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.unclaimed_referral_fees, Uint128::new(3_u128));
+        assert_eq!(state.total_staked, Uint128::new(3990_u128));
+
+        /*
+           Test - 3. Undelegations reduce the code's outstanding shares
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("cw20_contract", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "other".to_string(),
+                amount: Uint128::new(400_u128),
+                msg: to_binary(&Cw20HookMsg::QueueUndelegate { mint_nft: None }).unwrap(),
+            }),
+        )
+        .unwrap();
+        let referral_res = query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::ReferralCode {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap();
+        let referral_res: ReferralCodeResponse = from_binary(&referral_res).unwrap();
+        assert_eq!(
+            referral_res.referral_info,
+            Some(ReferralInfo {
+                owner: Addr::unchecked("integrator"),
+                total_deposits: Uint128::new(1000_u128),
+                outstanding_shares: Uint128::new(590_u128),
+                unclaimed_fees: Uint128::new(3_u128),
+                claimed_fees: Uint128::zero(),
+            })
+        );
+
+        /*
+           Test - 4. Claiming fees
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::ClaimReferralFees {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator", &[]),
+            ExecuteMsg::ClaimReferralFees {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "integrator".to_string(),
+                amount: vec![Coin::new(3_u128, "uluna")]
+            })]
+        );
+        let state = STATE.load(deps.as_mut().storage).unwrap();
+        assert_eq!(state.unclaimed_referral_fees, Uint128::zero());
+        let referral_info = REFERRAL_CODES
+            .load(deps.as_mut().storage, "wallet-1")
+            .unwrap();
+        assert_eq!(referral_info.unclaimed_fees, Uint128::zero());
+        assert_eq!(referral_info.claimed_fees, Uint128::new(3_u128));
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator", &[]),
+            ExecuteMsg::ClaimReferralFees {
+                code: "wallet-1".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::ZeroAmount {}));
+
+        /*
+           Test - 5. Owners cannot refer themselves
+        */
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: Some("wallet-1".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SelfReferral {}));
+
+        /*
+           Test - 6. Undelegations are attributed to the codes the user deposited with
+        */
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("integrator2", &[]),
+            ExecuteMsg::RegisterReferralCode {
+                code: "wallet-2".to_string(),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[Coin::new(1000_u128, "uluna".to_string())]),
+            ExecuteMsg::Deposit {
+                referral_code: Some("wallet-2".to_string()),
+            },
+        )
+        .unwrap();
+        let minted = REFERRAL_CODES
+            .load(deps.as_mut().storage, "wallet-2")
+            .unwrap()
+            .outstanding_shares;
+        assert_eq!(
+            REFERRED_SHARES
+                .load(
+                    deps.as_mut().storage,
+                    (&Addr::unchecked("other"), "wallet-2")
+                )
+                .unwrap(),
+            minted
+        );
+
+        // 590 shares are left from wallet-1, so the other 210 come from wallet-2.
+        execute(
+            deps.as_mut(),
+            env,
+            mock_info("cw20_contract", &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "other".to_string(),
+                amount: Uint128::new(800_u128),
+                msg: to_binary(&Cw20HookMsg::QueueUndelegate { mint_nft: None }).unwrap(),
+            }),
+        )
+        .unwrap();
+        let referral_info = REFERRAL_CODES
+            .load(deps.as_mut().storage, "wallet-1")
+            .unwrap();
+        assert_eq!(referral_info.outstanding_shares, Uint128::zero());
+        let referral_info = REFERRAL_CODES
+            .load(deps.as_mut().storage, "wallet-2")
+            .unwrap();
+        assert_eq!(
+            referral_info.outstanding_shares,
+            minted.checked_sub(Uint128::new(210_u128)).unwrap()
+        );
+        assert!(!REFERRED_SHARES.has(
+            deps.as_mut().storage,
+            (&Addr::unchecked("other"), "wallet-1")
+        ));
+        assert_eq!(
+            REFERRED_SHARES
+                .load(
+                    deps.as_mut().storage,
+                    (&Addr::unchecked("other"), "wallet-2")
+                )
+                .unwrap(),
+            minted.checked_sub(Uint128::new(210_u128)).unwrap()
         );
-        let state = STATE.load(deps.as_mut().storage).unwrap();
-        assert_eq!(state.reconciled_funds_to_withdraw, Uint128::new(450));
-        let user_undel_info = USERS
-            .may_load(deps.as_mut().storage, (&user1, U64Key::new(1)))
-            .unwrap();
-        assert_eq!(user_undel_info, None);
     }
 
     #[test]
@@ -3335,6 +6517,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -3361,6 +6544,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -3622,6 +6806,7 @@ mod tests {
                     state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
                     state.current_undelegation_batch_id = 1;
                     state.total_staked = Uint128::new(3000_u128);
+                    state.total_shares = Uint128::new(3000_u128);
                     Ok(state)
                 },
             )
@@ -3737,13 +6922,14 @@ mod tests {
                 create_time: env.block.time.minus_seconds(10000),
                 est_release_time: Some(env.block.time.plus_seconds(config.unbonding_period)),
                 reconciled: false,
-                undelegation_er: Decimal::one(),
+                undelegation_er: Decimal256::one(),
                 undelegated_stake: Uint128::new(2000_u128),
                 unbonding_slashing_ratio: Default::default()
             }
         );
         let state = STATE.load(deps.as_mut().storage).unwrap();
         assert_eq!(state.total_staked, Uint128::new(1000_u128));
+        assert_eq!(state.total_shares, Uint128::new(1000_u128));
         assert_eq!(state.last_undelegation_time, env.block.time);
         let new_undel_batch = BATCH_UNDELEGATION_REGISTRY
             .may_load(deps.as_mut().storage, U64Key::new(2))
@@ -3751,6 +6937,97 @@ mod tests {
         assert_ne!(new_undel_batch, None);
     }
 
+    #[test]
+    fn test_undelegate_stake_split_success() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.split_policy.undelegation_threshold = Some(Uint128::new(1000_u128));
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.current_undelegation_batch_id = 1;
+                    state.total_staked = Uint128::new(6000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_uneven_delegations());
+        deps.querier
+            .update_stader_balances(Some(Uint128::new(6000_u128)), None);
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(3000_u128),
+                    create_time: env.block.time.minus_seconds(10000),
+                    est_release_time: None,
+                    reconciled: false,
+                    undelegation_er: Default::default(),
+                    undelegated_stake: Default::default(),
+                    unbonding_slashing_ratio: Default::default(),
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Undelegate {},
+        )
+        .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert!(check_equal_vec(
+            res.messages,
+            vec![
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: valid3.to_string(),
+                    amount: Coin::new(1500_u128, "uluna".to_string())
+                }),
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: valid2.to_string(),
+                    amount: Coin::new(1000_u128, "uluna".to_string())
+                }),
+                SubMsg::new(StakingMsg::Undelegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(500_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.cw20_token_contract.to_string(),
+                    msg: to_binary(&cw20::Cw20ExecuteMsg::Burn {
+                        amount: Uint128::new(3000_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                })
+            ]
+        ));
+        let val1_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid1).unwrap();
+        let val2_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid2).unwrap();
+        let val3_meta = VALIDATOR_META.load(deps.as_mut().storage, &valid3).unwrap();
+        assert_eq!(val1_meta.staked, Uint128::new(500_u128));
+        assert_eq!(val2_meta.staked, Uint128::new(1000_u128));
+        assert_eq!(val3_meta.staked, Uint128::new(1500_u128));
+    }
+
     #[test]
     fn test_reconcile_funds() {
         let mut deps = mock_dependencies(&[]);
@@ -3773,6 +7050,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -3799,6 +7077,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -3826,7 +7105,7 @@ mod tests {
                     create_time: env.block.time.minus_seconds(20000),
                     est_release_time: Some(env.block.time.minus_seconds(300)),
                     reconciled: false,
-                    undelegation_er: Decimal::one(),
+                    undelegation_er: Decimal256::one(),
                     undelegated_stake: Uint128::new(3000_u128),
                     unbonding_slashing_ratio: Decimal::one(),
                 },
@@ -3841,7 +7120,7 @@ mod tests {
                     create_time: env.block.time.minus_seconds(10000),
                     est_release_time: Some(env.block.time.minus_seconds(100)),
                     reconciled: false,
-                    undelegation_er: Decimal::one(),
+                    undelegation_er: Decimal256::one(),
                     undelegated_stake: Uint128::new(2000_u128),
                     unbonding_slashing_ratio: Decimal::one(),
                 },
@@ -3873,7 +7152,7 @@ mod tests {
                 create_time: env.block.time.minus_seconds(20000),
                 est_release_time: Some(env.block.time.minus_seconds(300)),
                 reconciled: true,
-                undelegation_er: Decimal::one(),
+                undelegation_er: Decimal256::one(),
                 undelegated_stake: Uint128::new(3000_u128),
                 unbonding_slashing_ratio: Decimal::one()
             }
@@ -3885,7 +7164,7 @@ mod tests {
                 create_time: env.block.time.minus_seconds(10000),
                 est_release_time: Some(env.block.time.minus_seconds(100)),
                 reconciled: true,
-                undelegation_er: Decimal::one(),
+                undelegation_er: Decimal256::one(),
                 undelegated_stake: Uint128::new(2000_u128),
                 unbonding_slashing_ratio: Decimal::one()
             }
@@ -3914,7 +7193,7 @@ mod tests {
                     create_time: env.block.time.minus_seconds(20000),
                     est_release_time: Some(env.block.time.minus_seconds(300)),
                     reconciled: false,
-                    undelegation_er: Decimal::one(),
+                    undelegation_er: Decimal256::one(),
                     undelegated_stake: Uint128::new(3000_u128),
                     unbonding_slashing_ratio: Default::default(),
                 },
@@ -3929,7 +7208,7 @@ mod tests {
                     create_time: env.block.time.minus_seconds(10000),
                     est_release_time: Some(env.block.time.minus_seconds(100)),
                     reconciled: false,
-                    undelegation_er: Decimal::one(),
+                    undelegation_er: Decimal256::one(),
                     undelegated_stake: Uint128::new(2000_u128),
                     unbonding_slashing_ratio: Default::default(),
                 },
@@ -3961,7 +7240,7 @@ mod tests {
                 create_time: env.block.time.minus_seconds(20000),
                 est_release_time: Some(env.block.time.minus_seconds(300)),
                 reconciled: true,
-                undelegation_er: Decimal::one(),
+                undelegation_er: Decimal256::one(),
                 undelegated_stake: Uint128::new(3000_u128),
                 unbonding_slashing_ratio: Decimal::from_ratio(42_u128, 50_u128)
             }
@@ -3973,7 +7252,7 @@ mod tests {
                 create_time: env.block.time.minus_seconds(10000),
                 est_release_time: Some(env.block.time.minus_seconds(100)),
                 reconciled: true,
-                undelegation_er: Decimal::one(),
+                undelegation_er: Decimal256::one(),
                 undelegated_stake: Uint128::new(2000_u128),
                 unbonding_slashing_ratio: Decimal::from_ratio(42_u128, 50_u128)
             }
@@ -4003,6 +7282,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: true,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -4031,6 +7311,7 @@ mod tests {
                     redeem_rewards_paused: false,
                     swap_paused: false,
                     reimburse_slashing_paused: false,
+                    delegate_pending_paused: false,
                 },
             )
             .unwrap();
@@ -4103,4 +7384,294 @@ mod tests {
             ]
         ))
     }
+
+    #[test]
+    fn test_set_protocol_fee_recipients() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        let recipients = vec![
+            ProtocolFeeRecipient {
+                recipient: Addr::unchecked("treasury"),
+                weight: Decimal::percent(60),
+            },
+            ProtocolFeeRecipient {
+                recipient: Addr::unchecked("insurance_fund"),
+                weight: Decimal::percent(30),
+            },
+            ProtocolFeeRecipient {
+                recipient: Addr::unchecked("keeper_pool"),
+                weight: Decimal::percent(10),
+            },
+        ];
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::SetProtocolFeeRecipients {
+                recipients: recipients.clone(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let mut duplicate_recipients = recipients.clone();
+        duplicate_recipients[2].recipient = Addr::unchecked("treasury");
+        let mut zero_weight_recipients = recipients.clone();
+        zero_weight_recipients[2].weight = Decimal::zero();
+        for invalid_recipients in [
+            vec![],
+            recipients[..2].to_vec(),
+            duplicate_recipients,
+            zero_weight_recipients,
+        ] {
+            let err = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetProtocolFeeRecipients {
+                    recipients: invalid_recipients,
+                },
+            )
+            .unwrap_err();
+            assert!(matches!(
+                err,
+                ContractError::InvalidProtocolFeeRecipients {}
+            ));
+        }
+
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetProtocolFeeRecipients {
+                recipients: recipients.clone(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.events.len(), 1);
+        assert_eq!(res.events[0].ty, "protocol_fee_recipients_updated");
+        assert_eq!(
+            res.events[0].attributes,
+            vec![
+                attr("treasury", "0.6"),
+                attr("insurance_fund", "0.3"),
+                attr("keeper_pool", "0.1"),
+            ]
+        );
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        assert_eq!(config.protocol_fee_recipients, recipients);
+
+        // The last recipient takes the rounding remainder.
+        assert_eq!(
+            split_protocol_fee(&recipients, Uint128::new(11_u128)),
+            vec![
+                ProtocolFee {
+                    recipient: Addr::unchecked("treasury"),
+                    amount: Uint128::new(6_u128),
+                },
+                ProtocolFee {
+                    recipient: Addr::unchecked("insurance_fund"),
+                    amount: Uint128::new(3_u128),
+                },
+                ProtocolFee {
+                    recipient: Addr::unchecked("keeper_pool"),
+                    amount: Uint128::new(2_u128),
+                },
+            ]
+        );
+        assert!(split_protocol_fee(&recipients, Uint128::zero()).is_empty());
+
+        // Withdraw fees are split the same way.
+        deps.querier.update_balance(
+            Addr::unchecked(MOCK_CONTRACT_ADDR),
+            vec![Coin::new(1000_u128, "uluna")],
+        );
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.reconciled_funds_to_withdraw = Uint128::new(1000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        BATCH_UNDELEGATION_REGISTRY
+            .save(
+                deps.as_mut().storage,
+                U64Key::new(1),
+                &BatchUndelegationRecord {
+                    undelegated_tokens: Uint128::new(1000_u128),
+                    create_time: env.block.time,
+                    est_release_time: Some(env.block.time),
+                    reconciled: true,
+                    undelegation_er: Decimal256::one(),
+                    undelegated_stake: Uint128::new(1000_u128),
+                    unbonding_slashing_ratio: Decimal::one(),
+                },
+            )
+            .unwrap();
+        USERS
+            .save(
+                deps.as_mut().storage,
+                (&Addr::unchecked("user1"), U64Key::new(1)),
+                &UndelegationInfo {
+                    batch_id: 1,
+                    token_amount: Uint128::new(1000_u128),
+                },
+            )
+            .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("user1", &[]),
+            ExecuteMsg::WithdrawFundsToWallet { batch_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "user1".to_string(),
+                    amount: vec![Coin::new(990_u128, "uluna")]
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: vec![Coin::new(6_u128, "uluna")]
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "insurance_fund".to_string(),
+                    amount: vec![Coin::new(3_u128, "uluna")]
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "keeper_pool".to_string(),
+                    amount: vec![Coin::new(1_u128, "uluna")]
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reinvest_reward_fee_as_shares() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let env = mock_env();
+
+        instantiate_contract(&mut deps, &info, &env);
+
+        let valid1 = Addr::unchecked("valid0001");
+        let valid2 = Addr::unchecked("valid0002");
+        let valid3 = Addr::unchecked("valid0003");
+        CONFIG
+            .update(
+                deps.as_mut().storage,
+                |mut config| -> Result<_, ContractError> {
+                    config.cw20_token_contract = Addr::unchecked("cw20_token_contract");
+                    config.reward_fee_as_shares = true;
+                    config.protocol_fee_recipients = vec![
+                        ProtocolFeeRecipient {
+                            recipient: Addr::unchecked("treasury"),
+                            weight: Decimal::percent(60),
+                        },
+                        ProtocolFeeRecipient {
+                            recipient: Addr::unchecked("insurance_fund"),
+                            weight: Decimal::percent(40),
+                        },
+                    ];
+                    Ok(config)
+                },
+            )
+            .unwrap();
+        let config = CONFIG.load(deps.as_mut().storage).unwrap();
+        deps.querier.update_balance(
+            config.reward_contract.clone(),
+            vec![Coin::new(1000_u128, "uluna".to_string())],
+        );
+        update_total_shares(&mut deps, Uint128::new(3000_u128));
+        deps.querier
+            .update_staking("uluna", &get_validators(), &get_delegations());
+        STATE
+            .update(
+                deps.as_mut().storage,
+                |mut state| -> Result<_, ContractError> {
+                    state.validators = vec![valid1.clone(), valid2.clone(), valid3.clone()];
+                    state.total_staked = Uint128::new(3000_u128);
+                    Ok(state)
+                },
+            )
+            .unwrap();
+        for val_addr in [&valid1, &valid2, &valid3] {
+            VALIDATOR_META
+                .save(
+                    deps.as_mut().storage,
+                    val_addr,
+                    &VMeta {
+                        staked: Uint128::new(1000_u128),
+                        slashed: Default::default(),
+                        filled: Default::default(),
+                    },
+                )
+                .unwrap();
+        }
+
+        // All 1000 luna is staked. The 10 luna fee is paid as 7 shares, worth 9.3 luna at the new rate.
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("creator", &[]),
+            ExecuteMsg::Reinvest {},
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: config.reward_contract.to_string(),
+                    msg: to_binary(&RewardExecuteMsg::Transfer {
+                        reward_amount: Uint128::new(1000_u128),
+                        reward_withdraw_contract: env.contract.address.clone(),
+                        protocol_fees: vec![],
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                }),
+                SubMsg::new(StakingMsg::Delegate {
+                    validator: valid1.to_string(),
+                    amount: Coin::new(1000_u128, "uluna".to_string())
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "cw20_token_contract".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "treasury".to_string(),
+                        amount: Uint128::new(4_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                }),
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: "cw20_token_contract".to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Mint {
+                        recipient: "insurance_fund".to_string(),
+                        amount: Uint128::new(3_u128)
+                    })
+                    .unwrap(),
+                    funds: vec![]
+                }),
+            ]
+        );
+
+        let state_res = query(deps.as_ref(), env.clone(), QueryMsg::State {}).unwrap();
+        let state: QueryStateResponse = from_binary(&state_res).unwrap();
+        assert_eq!(state.state.total_staked, Uint128::new(4000_u128));
+        assert_eq!(state.state.total_shares, Uint128::new(3007_u128));
+        assert_eq!(state.state.fee_shares_minted, Uint128::new(7_u128));
+        assert_eq!(
+            state.state.exchange_rate,
+            Decimal256::from_ratio(4000_u64, 3007_u64)
+        );
+    }
 }
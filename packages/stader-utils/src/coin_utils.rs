@@ -1,4 +1,4 @@
-use cosmwasm_bignumber::Decimal256;
+use cosmwasm_bignumber::{Decimal256, Uint256};
 use cosmwasm_std::{Coin, Decimal, Fraction, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -205,6 +205,61 @@ pub fn get_decimal_from_uint128(a: Uint128) -> Decimal {
     Decimal::from_ratio(a, 1_u128)
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum Rounding {
+    Floor,
+    Ceil,
+}
+
+pub fn get_decimal256_from_uint128(a: Uint128) -> Decimal256 {
+    Decimal256::from_uint256(a)
+}
+
+/// return a / b, rounded down to 18 decimal places
+pub fn decimal256_from_ratio(a: Uint128, b: Uint128) -> Decimal256 {
+    if b.is_zero() {
+        panic!("decimal256_from_ratio: Divide by 0!")
+    }
+    Decimal256::from_ratio(Uint256::from(a), Uint256::from(b))
+}
+
+/// return a * b, rounded to an integer in the given direction
+pub fn multiply_uint128_with_decimal256(a: Uint128, b: Decimal256, rounding: Rounding) -> Uint128 {
+    uint128_from_ratio_in_256(
+        Uint256::from(a),
+        Uint256::from(b.0),
+        Uint256::from(Decimal256::DECIMAL_FRACTIONAL),
+        rounding,
+    )
+}
+
+/// return a / b, rounded to an integer in the given direction
+pub fn divide_uint128_by_decimal256(a: Uint128, b: Decimal256, rounding: Rounding) -> Uint128 {
+    if b.is_zero() {
+        panic!("divide_uint128_by_decimal256: Divide by 0!")
+    }
+    uint128_from_ratio_in_256(
+        Uint256::from(a),
+        Uint256::from(Decimal256::DECIMAL_FRACTIONAL),
+        Uint256::from(b.0),
+        rounding,
+    )
+}
+
+/// return a * nom / denom
+fn uint128_from_ratio_in_256(
+    a: Uint256,
+    nom: Uint256,
+    denom: Uint256,
+    rounding: Rounding,
+) -> Uint128 {
+    let floor = a.multiply_ratio(nom, denom);
+    if rounding == Rounding::Ceil && floor * denom != a * nom {
+        return (floor + Uint256::one()).into();
+    }
+    floor.into()
+}
+
 pub fn merge_decimal(decimal1: Decimal, decimal_op: DecimalOp) -> Decimal {
     let fund = decimal_op.fund;
     let operation = decimal_op.operation;
@@ -478,11 +533,12 @@ pub fn deccoin_vec_to_coin_vec(deccoins: &[DecCoin]) -> Vec<Coin> {
 mod tests {
     use crate::coin_utils::{
         add_coin_vector_to_map, add_deccoin_vector_to_map, check_equal_coin_vector,
-        check_equal_deccoin_vector, decimal_division_in_256, decimal_multiplication_in_256,
-        decimal_subtraction_in_256, decimal_summation_in_256, map_to_coin_vec, map_to_deccoin_vec,
-        merge_coin, merge_coin_vector, merge_dec_coin_vector, merge_decimal,
+        check_equal_deccoin_vector, decimal256_from_ratio, decimal_division_in_256,
+        decimal_multiplication_in_256, decimal_subtraction_in_256, decimal_summation_in_256,
+        divide_uint128_by_decimal256, map_to_coin_vec, map_to_deccoin_vec, merge_coin,
+        merge_coin_vector, merge_dec_coin_vector, merge_decimal, multiply_uint128_with_decimal256,
         subtract_coin_vector_from_map, subtract_deccoin_vector_from_map, CoinOp, CoinVecOp,
-        DecCoin, DecCoinVecOp, DecimalOp, Operation,
+        DecCoin, DecCoinVecOp, DecimalOp, Operation, Rounding,
     };
     use cosmwasm_std::{Coin, Decimal, Fraction, Uint128};
     use std::borrow::Borrow;
@@ -1295,4 +1351,45 @@ mod tests {
             ]
         ));
     }
+
+    #[test]
+    fn test_decimal256_rounding() {
+        let rate = decimal256_from_ratio(Uint128::new(10_u128), Uint128::new(3_u128));
+
+        assert_eq!(
+            multiply_uint128_with_decimal256(Uint128::new(3_u128), rate, Rounding::Floor),
+            Uint128::new(9_u128)
+        );
+        assert_eq!(
+            multiply_uint128_with_decimal256(Uint128::new(3_u128), rate, Rounding::Ceil),
+            Uint128::new(10_u128)
+        );
+        assert_eq!(
+            divide_uint128_by_decimal256(Uint128::new(10_u128), rate, Rounding::Floor),
+            Uint128::new(3_u128)
+        );
+        assert_eq!(
+            divide_uint128_by_decimal256(Uint128::new(10_u128), rate, Rounding::Ceil),
+            Uint128::new(4_u128)
+        );
+
+        // Exact results are not bumped by ceiling
+        let rate = decimal256_from_ratio(Uint128::new(3_u128), Uint128::new(2_u128));
+        assert_eq!(
+            multiply_uint128_with_decimal256(Uint128::new(4_u128), rate, Rounding::Ceil),
+            Uint128::new(6_u128)
+        );
+        assert_eq!(
+            divide_uint128_by_decimal256(Uint128::new(6_u128), rate, Rounding::Ceil),
+            Uint128::new(4_u128)
+        );
+
+        // Does not overflow for supplies past u64
+        let large = Uint128::new(u128::MAX / 4);
+        let rate = decimal256_from_ratio(large, large);
+        assert_eq!(
+            multiply_uint128_with_decimal256(large, rate, Rounding::Floor),
+            large
+        );
+    }
 }